@@ -0,0 +1,92 @@
+//! Embeds information about the build, served by `/health/info`, as environment variables
+//! read with `env!`.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={}", git_commit());
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", build_timestamp());
+    println!("cargo:rustc-env=BUILD_RUSTC_VERSION={}", rustc_version());
+    println!("cargo:rustc-env=BUILD_FEATURES={}", features().join(","));
+
+    // sources are listed, so that the build timestamp follows them
+    for path in ["src", "build.rs", "Cargo.toml", "Cargo.lock"] {
+        println!("cargo:rerun-if-changed={path}");
+    }
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
+/// `GIT_COMMIT` if it's set, e.g. in Docker builds which don't see the repository, or the
+/// commit checked out.
+fn git_commit() -> String {
+    if let Some(commit) = std::env::var("GIT_COMMIT")
+        .ok()
+        .filter(|commit| !commit.is_empty())
+    {
+        return commit;
+    }
+
+    // the commit changes with the checked out branch or the ref it points to
+    let head = Path::new(".git/HEAD");
+    if head.exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        if let Some(head_ref) = std::fs::read_to_string(head)
+            .ok()
+            .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_owned()))
+        {
+            let ref_path = Path::new(".git").join(head_ref);
+            if ref_path.exists() {
+                println!("cargo:rerun-if-changed={}", ref_path.display());
+            }
+        }
+    }
+
+    command_output("git", &["rev-parse", "HEAD"]).unwrap_or_else(|| "unknown".to_owned())
+}
+
+/// Unix timestamp of the build, or `SOURCE_DATE_EPOCH` for reproducible builds.
+fn build_timestamp() -> u64 {
+    if let Some(timestamp) = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|timestamp| timestamp.parse().ok())
+    {
+        return timestamp;
+    }
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn rustc_version() -> String {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+
+    command_output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_owned())
+}
+
+/// Cargo features the crate is built with, cargo passes them as `CARGO_FEATURE_<NAME>`.
+fn features() -> Vec<String> {
+    let mut features = std::env::vars()
+        .filter_map(|(name, _)| {
+            name.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+
+    features
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout)
+        .ok()
+        .map(|output| output.trim().to_owned())
+}
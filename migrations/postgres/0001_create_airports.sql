@@ -0,0 +1,23 @@
+-- Mirrors the `airports` table of the SQLite dataset, missing codes and names are stored as 'N/A'.
+CREATE TABLE IF NOT EXISTS airports
+(
+    id          serial primary key,
+    icao_code   varchar(4)  not null,
+    iata_code   varchar(3)  not null,
+    name        varchar(50) not null,
+    city        varchar(50) not null,
+    country     varchar(50) not null,
+    lat_deg     bigint      not null,
+    lat_min     bigint      not null,
+    lat_sec     bigint      not null,
+    lat_dir     varchar(1)  not null,
+    lon_deg     bigint      not null,
+    lon_min     bigint      not null,
+    lon_sec     bigint      not null,
+    lon_dir     varchar(1)  not null,
+    altitude    bigint      not null,
+    lat_decimal double precision not null,
+    lon_decimal double precision not null
+);
+
+CREATE INDEX IF NOT EXISTS idx_airports_iata ON airports (iata_code);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS dataset_metadata
+(
+    source         varchar(50) not null,
+    version        varchar(50) not null,
+    imported_at    bigint      not null,
+    rows_read      bigint      not null,
+    airports_count bigint      not null,
+    rejected_count bigint      not null,
+    checksum       varchar(64) not null
+);
@@ -0,0 +1,570 @@
+#![allow(non_camel_case_types)]
+
+use std::time::UNIX_EPOCH;
+
+use actix_web::error::{ErrorBadRequest, ErrorConflict, ErrorInternalServerError, ErrorNotFound};
+use actix_web::web::Data;
+use actix_web::HttpRequest;
+use paperclip::actix::web::{Json, Path};
+use paperclip::actix::{api_v2_operation, delete, get, post, put};
+use serde_json::json;
+use serde_valid::Validate;
+use tracing::log;
+
+use crate::api::admin::schemas::{AirportRequest, OverlayAuditLogResponse};
+use crate::auth::{request_user, AdminScope, RequireScope};
+use crate::models::Airport;
+use crate::services::airports::{
+    AirportFilter, AirportSort, AirportsOverlayStore, AirportsQuery, OverlayAction, OverlayChange,
+    OverlayError,
+};
+use crate::services::app_state::AppState;
+
+#[api_v2_operation]
+#[post("/airports/{iata_code}")]
+#[tracing::instrument(skip_all)]
+pub async fn create_airport_handler(
+    _scope: RequireScope<AdminScope>,
+    http_request: HttpRequest,
+    iata_code: Path<String>,
+    request: Json<AirportRequest>,
+    app_state: Data<AppState>,
+    overlay: Option<Data<AirportsOverlayStore>>,
+) -> Result<Json<Airport>, actix_web::Error> {
+    let overlay = overlay_store(overlay)?;
+    let iata_code = normalize_iata(&iata_code)?;
+    let request = validate(request.into_inner())?;
+    let id = next_airport_id(&app_state).await?;
+
+    let change = record_change(
+        &overlay,
+        &app_state,
+        &http_request,
+        OverlayAction::Create,
+        &iata_code,
+        Some(request.into_airport(id, &iata_code)),
+    )
+    .await?;
+
+    recorded_airport(change.current, &iata_code)
+}
+
+#[api_v2_operation]
+#[put("/airports/{iata_code}")]
+#[tracing::instrument(skip_all)]
+pub async fn update_airport_handler(
+    _scope: RequireScope<AdminScope>,
+    http_request: HttpRequest,
+    iata_code: Path<String>,
+    request: Json<AirportRequest>,
+    app_state: Data<AppState>,
+    overlay: Option<Data<AirportsOverlayStore>>,
+) -> Result<Json<Airport>, actix_web::Error> {
+    let overlay = overlay_store(overlay)?;
+    let iata_code = normalize_iata(&iata_code)?;
+    let request = validate(request.into_inner())?;
+
+    // the id is taken over from the airport being updated
+    let change = record_change(
+        &overlay,
+        &app_state,
+        &http_request,
+        OverlayAction::Update,
+        &iata_code,
+        Some(request.into_airport(0, &iata_code)),
+    )
+    .await?;
+
+    recorded_airport(change.current, &iata_code)
+}
+
+#[api_v2_operation]
+#[delete("/airports/{iata_code}")]
+#[tracing::instrument(skip_all)]
+pub async fn delete_airport_handler(
+    _scope: RequireScope<AdminScope>,
+    http_request: HttpRequest,
+    iata_code: Path<String>,
+    app_state: Data<AppState>,
+    overlay: Option<Data<AirportsOverlayStore>>,
+) -> Result<Json<Airport>, actix_web::Error> {
+    let overlay = overlay_store(overlay)?;
+    let iata_code = normalize_iata(&iata_code)?;
+
+    let change = record_change(
+        &overlay,
+        &app_state,
+        &http_request,
+        OverlayAction::Delete,
+        &iata_code,
+        None,
+    )
+    .await?;
+
+    recorded_airport(change.previous, &iata_code)
+}
+
+#[api_v2_operation]
+#[get("/airports/audit")]
+#[tracing::instrument(skip_all)]
+pub async fn overlay_audit_log_handler(
+    _scope: RequireScope<AdminScope>,
+    overlay: Option<Data<AirportsOverlayStore>>,
+) -> Result<Json<OverlayAuditLogResponse>, actix_web::Error> {
+    let overlay = overlay_store(overlay)?;
+
+    match overlay.audit_log().await {
+        Ok(entries) => Ok(Json(OverlayAuditLogResponse { entries })),
+        Err(e) => {
+            log::error!("Failed to fetch overlay audit log: {e}");
+            Err(ErrorInternalServerError(json!({"error": "Database fail"})))
+        }
+    }
+}
+
+fn overlay_store(
+    overlay: Option<Data<AirportsOverlayStore>>,
+) -> Result<Data<AirportsOverlayStore>, actix_web::Error> {
+    overlay.ok_or_else(|| ErrorNotFound(json!({"error": "Airports overlay is not configured"})))
+}
+
+fn normalize_iata(iata_code: &str) -> Result<String, actix_web::Error> {
+    let iata_code = iata_code.trim().to_uppercase();
+
+    match iata_code.len() == 3 && iata_code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        true => Ok(iata_code),
+        false => Err(ErrorBadRequest(json!({
+            "error": "IATA code has to consist of 3 letters or digits"
+        }))),
+    }
+}
+
+fn validate(request: AirportRequest) -> Result<AirportRequest, actix_web::Error> {
+    match request.validate() {
+        Ok(()) => Ok(request),
+        Err(validation_errors) => {
+            log::warn!("Failed to validate request: {validation_errors:?}");
+            Err(ErrorBadRequest(validation_errors))
+        }
+    }
+}
+
+fn airport_not_found(iata_code: &str) -> actix_web::Error {
+    ErrorNotFound(json!({
+        "error": "Airport not found",
+        "details": {"iata_code": iata_code}
+    }))
+}
+
+async fn fetch_airport(
+    app_state: &AppState,
+    iata_code: &str,
+) -> Result<Option<Airport>, actix_web::Error> {
+    let airports_repository = app_state.airports_repository.snapshot();
+
+    airports_repository
+        .fetch_airport_by_iata(iata_code)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch airport from database: {e}");
+            ErrorInternalServerError(json!({"error": "Database fail"}))
+        })
+}
+
+/// Id following the ids of all served airports, listings are paged by ids so they have to be
+/// unique. The overlay raises it further if one of its airports was created meanwhile.
+async fn next_airport_id(app_state: &AppState) -> Result<u32, actix_web::Error> {
+    let query = AirportsQuery {
+        filter: AirportFilter::default(),
+        sort: AirportSort::Id,
+        descending: true,
+        after: None,
+        limit: 1,
+    };
+
+    let last_airport = app_state
+        .airports_repository
+        .snapshot()
+        .list_airports(&query)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch airports from database: {e}");
+            ErrorInternalServerError(json!({"error": "Database fail"}))
+        })?;
+
+    Ok(last_airport.first().map_or(1, |airport| airport.id + 1))
+}
+
+/// Records the change in the overlay, which checks it against the airport served at the time
+/// of the write and returns it as the previous one.
+async fn record_change(
+    overlay: &AirportsOverlayStore,
+    app_state: &AppState,
+    http_request: &HttpRequest,
+    action: OverlayAction,
+    iata_code: &str,
+    current: Option<Airport>,
+) -> Result<OverlayChange, actix_web::Error> {
+    // only used by the overlay when it has no entry for the airport yet, and entries are never
+    // removed from it, so this is the airport of the dataset
+    let dataset = fetch_airport(app_state, iata_code).await?;
+
+    let changed_at = app_state
+        .time_provider
+        .now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let changed_by = request_user(http_request);
+
+    let change = overlay
+        .record(
+            action,
+            iata_code,
+            dataset.as_ref(),
+            current,
+            &changed_by,
+            changed_at,
+        )
+        .await
+        .map_err(|e| match e {
+            OverlayError::AlreadyExists => ErrorConflict(json!({
+                "error": "Airport already exists",
+                "details": {"iata_code": iata_code}
+            })),
+            OverlayError::NotFound => airport_not_found(iata_code),
+            OverlayError::Database(e) => {
+                log::error!("Failed to store overlay change: {e}");
+                ErrorInternalServerError(json!({"error": "Database fail"}))
+            }
+        })?;
+
+    log::info!("{changed_by} applied {action:?} of airport {iata_code} to the overlay");
+
+    Ok(change)
+}
+
+fn recorded_airport(
+    airport: Option<Airport>,
+    iata_code: &str,
+) -> Result<Json<Airport>, actix_web::Error> {
+    airport
+        .map(Json)
+        .ok_or_else(|| airport_not_found(iata_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    use actix_web::{http, test, App};
+
+    use crate::services::{
+        airports::{DummyAirportsRepository, OverlayAirportsRepository},
+        healthcheck::{
+            hostname_provider::MockSuccessfulHostnameProvider, time_provider::MockTimeProvider,
+        },
+    };
+
+    use crate::auth::AuthenticateAs;
+    use actix_web::web;
+
+    use super::*;
+
+    async fn overlay_app_data() -> (Data<AppState>, Data<AirportsOverlayStore>) {
+        overlay_app_data_with(vec![]).await
+    }
+
+    async fn overlay_app_data_with(
+        airports: Vec<Airport>,
+    ) -> (Data<AppState>, Data<AirportsOverlayStore>) {
+        let overlay = Arc::new(AirportsOverlayStore::open("sqlite::memory:").await.unwrap());
+        let repository = OverlayAirportsRepository::new(
+            Arc::new(DummyAirportsRepository::new(airports)),
+            overlay.clone(),
+        );
+        let state = AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            Box::new(MockTimeProvider::new(
+                UNIX_EPOCH + Duration::from_secs(1683000000),
+            )),
+            Box::new(repository),
+        );
+
+        (Data::new(state), Data::from(overlay))
+    }
+
+    fn heliport() -> AirportRequest {
+        AirportRequest {
+            icao_code: None,
+            name: "PRIVATE HELIPORT".to_owned(),
+            city: "KRAKOW".to_owned(),
+            country: "POLAND".to_owned(),
+            altitude: 220,
+            latitude: 50.0647,
+            longitude: -19.945,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_create_update_and_delete_airport() {
+        let (state, overlay) = overlay_app_data().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .app_data(overlay)
+                .service(
+                    web::scope("/admin")
+                        .service(overlay_audit_log_handler)
+                        .service(create_airport_handler)
+                        .service(update_airport_handler)
+                        .service(delete_airport_handler),
+                )
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/airports/xhp")
+            .set_json(heliport())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let airport: Airport = test::read_body_json(resp).await;
+        assert_eq!(airport.iata_code, "XHP");
+        assert_eq!(
+            (airport.lat_deg, airport.lat_min, airport.lat_sec),
+            (50, 3, 53)
+        );
+        assert_eq!(airport.lon_dir, "W");
+
+        let req = test::TestRequest::post()
+            .uri("/admin/airports/XHP")
+            .set_json(heliport())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::CONFLICT);
+
+        let mut corrected = heliport();
+        corrected.longitude = 19.945;
+        let req = test::TestRequest::put()
+            .uri("/admin/airports/XHP")
+            .set_json(corrected)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let served = state
+            .airports_repository
+            .snapshot()
+            .fetch_airport_by_iata("XHP")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(served.lon_decimal, 19.945);
+
+        let req = test::TestRequest::delete()
+            .uri("/admin/airports/XHP")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let req = test::TestRequest::delete()
+            .uri("/admin/airports/XHP")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/airports/audit")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let audit_log: OverlayAuditLogResponse = test::read_body_json(resp).await;
+        let actions = audit_log
+            .entries
+            .iter()
+            .map(|entry| entry.action)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                OverlayAction::Delete,
+                OverlayAction::Update,
+                OverlayAction::Create
+            ]
+        );
+        assert!(audit_log
+            .entries
+            .iter()
+            .all(|entry| entry.changed_at == 1683000000 && entry.changed_by == "test"));
+    }
+
+    #[actix_web::test]
+    async fn test_concurrent_deletes_are_recorded_once() {
+        let (state, overlay) = overlay_app_data().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(overlay.clone())
+                .service(
+                    web::scope("/admin")
+                        .service(create_airport_handler)
+                        .service(delete_airport_handler),
+                )
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/airports/XHP")
+            .set_json(heliport())
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            http::StatusCode::OK
+        );
+
+        let delete = || {
+            test::call_service(
+                &app,
+                test::TestRequest::delete()
+                    .uri("/admin/airports/XHP")
+                    .to_request(),
+            )
+        };
+        let (first, second) = futures::join!(delete(), delete());
+
+        let mut statuses = vec![first.status(), second.status()];
+        statuses.sort();
+        assert_eq!(
+            statuses,
+            vec![http::StatusCode::OK, http::StatusCode::NOT_FOUND]
+        );
+        assert_eq!(overlay.audit_log().await.unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_pages_through_created_airports() {
+        let (state, overlay) = overlay_app_data_with(vec![
+            heliport().into_airport(1, "KRK"),
+            heliport().into_airport(2, "KTW"),
+        ])
+        .await;
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .app_data(overlay)
+                .service(
+                    web::scope("/admin")
+                        .service(create_airport_handler)
+                        .service(update_airport_handler),
+                )
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        for (method, iata_code) in [
+            (http::Method::POST, "XHP"),
+            (http::Method::POST, "XHQ"),
+            (http::Method::PUT, "KRK"),
+            (http::Method::POST, "XHR"),
+        ] {
+            let req = test::TestRequest::default()
+                .method(method)
+                .uri(&format!("/admin/airports/{iata_code}"))
+                .set_json(heliport())
+                .to_request();
+            assert_eq!(
+                test::call_service(&app, req).await.status(),
+                http::StatusCode::OK
+            );
+        }
+
+        let repository = state.airports_repository.snapshot();
+        let mut query = AirportsQuery {
+            filter: AirportFilter::default(),
+            sort: AirportSort::Id,
+            descending: false,
+            after: None,
+            limit: 2,
+        };
+        let mut listed = Vec::new();
+        loop {
+            let page = repository.list_airports(&query).await.unwrap();
+            let Some(last) = page.last() else {
+                break;
+            };
+            query.after = Some(query.cursor(last));
+            listed.extend(
+                page.into_iter()
+                    .map(|airport| (airport.id, airport.iata_code)),
+            );
+        }
+
+        assert_eq!(
+            listed,
+            vec![
+                (1, "KRK".to_owned()),
+                (2, "KTW".to_owned()),
+                (3, "XHP".to_owned()),
+                (4, "XHQ".to_owned()),
+                (5, "XHR".to_owned()),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_rejects_invalid_airport() {
+        let (state, overlay) = overlay_app_data().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(overlay)
+                .service(web::scope("/admin").service(create_airport_handler))
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let mut invalid = heliport();
+        invalid.latitude = 91.;
+        let req = test::TestRequest::post()
+            .uri("/admin/airports/XHP")
+            .set_json(invalid)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/admin/airports/TOOLONG")
+            .set_json(heliport())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_returns_404_when_overlay_not_configured() {
+        let state = AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            Box::new(MockTimeProvider::new(SystemTime::now())),
+            Box::new(DummyAirportsRepository::new(vec![])),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(state))
+                .service(web::scope("/admin").service(create_airport_handler))
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/airports/XHP")
+            .set_json(heliport())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+    }
+}
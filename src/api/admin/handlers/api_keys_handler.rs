@@ -0,0 +1,271 @@
+#![allow(non_camel_case_types)]
+
+use std::time::UNIX_EPOCH;
+
+use actix_web::error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound};
+use actix_web::web::Data;
+use paperclip::actix::web::{Json, Path};
+use paperclip::actix::{api_v2_operation, delete, get, post};
+use serde_json::json;
+use serde_valid::Validate;
+use tracing::log;
+
+use crate::api::admin::schemas::{ApiKeysResponse, IssueApiKeyRequest, IssuedApiKeyResponse};
+use crate::auth::{AdminScope, ApiKey, ApiKeyStore, Identity, Principal, RequireScope};
+use crate::services::app_state::AppState;
+
+#[api_v2_operation]
+#[post("/api-keys")]
+#[tracing::instrument(skip_all)]
+pub async fn issue_api_key_handler(
+    scope: RequireScope<AdminScope>,
+    request: Json<IssueApiKeyRequest>,
+    app_state: Data<AppState>,
+    api_key_store: Option<Data<ApiKeyStore>>,
+) -> Result<Json<IssuedApiKeyResponse>, actix_web::Error> {
+    let api_key_store = api_key_store_or_not_found(api_key_store)?;
+    let created_by = request_user_id(scope.principal())?;
+    let request = request.into_inner();
+
+    if let Err(validation_errors) = request.validate() {
+        log::warn!("Failed to validate request: {validation_errors:?}");
+        return Err(ErrorBadRequest(validation_errors));
+    }
+
+    let now = now(&app_state);
+    if request
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+    {
+        return Err(ErrorBadRequest(
+            json!({"error": "expires_at has to be in the future"}),
+        ));
+    }
+
+    let issued = api_key_store
+        .issue(&request.label, request.expires_at, &created_by, now)
+        .await
+        .map_err(database_error)?;
+
+    log::info!(
+        "{created_by} issued API key {} ({})",
+        issued.api_key.id,
+        issued.api_key.label
+    );
+
+    Ok(Json(IssuedApiKeyResponse {
+        key: issued.key,
+        api_key: issued.api_key,
+    }))
+}
+
+#[api_v2_operation]
+#[get("/api-keys")]
+#[tracing::instrument(skip_all)]
+pub async fn list_api_keys_handler(
+    scope: RequireScope<AdminScope>,
+    api_key_store: Option<Data<ApiKeyStore>>,
+) -> Result<Json<ApiKeysResponse>, actix_web::Error> {
+    let api_key_store = api_key_store_or_not_found(api_key_store)?;
+    request_user_id(scope.principal())?;
+
+    let api_keys = api_key_store.list().await.map_err(database_error)?;
+
+    Ok(Json(ApiKeysResponse { api_keys }))
+}
+
+#[api_v2_operation]
+#[delete("/api-keys/{id}")]
+#[tracing::instrument(skip_all)]
+pub async fn revoke_api_key_handler(
+    scope: RequireScope<AdminScope>,
+    id: Path<i64>,
+    app_state: Data<AppState>,
+    api_key_store: Option<Data<ApiKeyStore>>,
+) -> Result<Json<ApiKey>, actix_web::Error> {
+    let api_key_store = api_key_store_or_not_found(api_key_store)?;
+    let revoked_by = request_user_id(scope.principal())?;
+    let id = id.into_inner();
+
+    match api_key_store
+        .revoke(id, now(&app_state))
+        .await
+        .map_err(database_error)?
+    {
+        Some(api_key) => {
+            log::info!("{revoked_by} revoked API key {id} ({})", api_key.label);
+            Ok(Json(api_key))
+        }
+        None => Err(ErrorNotFound(json!({
+            "error": "API key not found",
+            "details": {"id": id}
+        }))),
+    }
+}
+
+fn api_key_store_or_not_found(
+    api_key_store: Option<Data<ApiKeyStore>>,
+) -> Result<Data<ApiKeyStore>, actix_web::Error> {
+    api_key_store.ok_or_else(|| ErrorNotFound(json!({"error": "API keys are not configured"})))
+}
+
+/// Keys are managed by users only, so that a leaked key can't be used to issue new ones.
+fn request_user_id(principal: &Principal) -> Result<String, actix_web::Error> {
+    match &principal.identity {
+        Identity::User(user_id) => Ok(user_id.clone()),
+        _ => Err(ErrorForbidden(json!({
+            "error": "API keys can only be managed by users"
+        }))),
+    }
+}
+
+fn now(app_state: &AppState) -> i64 {
+    app_state
+        .time_provider
+        .now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+fn database_error(e: sqlx::Error) -> actix_web::Error {
+    log::error!("Failed to access API keys database: {e}");
+    ErrorInternalServerError(json!({"error": "Database fail"}))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use actix_web::{http, test, App};
+    use actix_web_httpauth::headers::authorization::{Authorization, Basic};
+    use actix_web_httpauth::middleware::HttpAuthentication;
+
+    use crate::auth::{api_auth_validator, ApiCredentials, API_KEY_HEADER};
+    use crate::services::{
+        airports::DummyAirportsRepository,
+        healthcheck::{
+            hostname_provider::MockSuccessfulHostnameProvider, time_provider::MockTimeProvider,
+        },
+    };
+
+    use crate::auth::{RequireRole, ADMIN_ROLE};
+    use actix_web::web;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_issues_and_revokes_api_keys() {
+        let app_state = Data::new(AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            Box::new(MockTimeProvider::new(
+                UNIX_EPOCH + Duration::from_secs(1683000000),
+            )),
+            Box::new(DummyAirportsRepository::new(vec![])),
+        ));
+        let api_key_store = Data::new(ApiKeyStore::open("sqlite::memory:").await.unwrap());
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state)
+                .app_data(api_key_store)
+                .app_data(Data::new(ApiCredentials::new("admin", "secret")))
+                .service(
+                    web::scope("/admin")
+                        .wrap(RequireRole::new(ADMIN_ROLE))
+                        .service(issue_api_key_handler)
+                        .service(list_api_keys_handler)
+                        .service(revoke_api_key_handler),
+                )
+                .wrap(HttpAuthentication::with_fn(api_auth_validator)),
+        )
+        .await;
+        let basic_auth = Authorization::<Basic>::from(Basic::new("admin", Some("secret")));
+
+        let req = test::TestRequest::post()
+            .uri("/admin/api-keys")
+            .insert_header(basic_auth.clone())
+            .set_json(json!({"label": "importer"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let issued: IssuedApiKeyResponse = test::read_body_json(resp).await;
+        assert_eq!(issued.api_key.created_by, "admin");
+        assert_eq!(issued.api_key.created_at, 1683000000);
+
+        // keys authenticate requests, but can't manage other keys
+        for req in [
+            test::TestRequest::get()
+                .uri("/admin/api-keys")
+                .insert_header((API_KEY_HEADER, issued.key.as_str())),
+            test::TestRequest::get()
+                .uri("/admin/api-keys")
+                .insert_header(("Authorization", format!("Bearer {}", issued.key))),
+        ] {
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+        }
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/admin/api-keys/{}", issued.api_key.id))
+            .insert_header(basic_auth.clone())
+            .to_request();
+        let revoked: ApiKey = test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(revoked.revoked_at, Some(1683000000));
+
+        let req = test::TestRequest::get()
+            .uri("/admin/api-keys")
+            .insert_header((API_KEY_HEADER, issued.key.as_str()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/api-keys")
+            .insert_header(basic_auth)
+            .to_request();
+        let listed: ApiKeysResponse =
+            test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(listed.api_keys, vec![revoked]);
+    }
+
+    #[actix_web::test]
+    async fn test_rejects_invalid_issue_requests() {
+        let app_state = Data::new(AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            Box::new(MockTimeProvider::new(SystemTime::now())),
+            Box::new(DummyAirportsRepository::new(vec![])),
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state)
+                .app_data(Data::new(
+                    ApiKeyStore::open("sqlite::memory:").await.unwrap(),
+                ))
+                .app_data(Data::new(ApiCredentials::new("admin", "secret")))
+                .service(
+                    web::scope("/admin")
+                        .wrap(RequireRole::new(ADMIN_ROLE))
+                        .service(issue_api_key_handler),
+                )
+                .wrap(HttpAuthentication::with_fn(api_auth_validator)),
+        )
+        .await;
+
+        for body in [
+            json!({"label": ""}),
+            json!({"label": "old", "expires_at": 1}),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/admin/api-keys")
+                .insert_header(Authorization::<Basic>::from(Basic::new(
+                    "admin",
+                    Some("secret"),
+                )))
+                .set_json(&body)
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST, "{body}");
+        }
+    }
+}
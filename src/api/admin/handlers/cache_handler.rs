@@ -0,0 +1,79 @@
+#![allow(non_camel_case_types)]
+
+use actix_web::web::Data;
+use paperclip::actix::web::Json;
+use paperclip::actix::{api_v2_operation, get};
+
+use crate::api::admin::schemas::CacheStatsResponse;
+use crate::auth::{AdminScope, RequireScope};
+use crate::services::app_state::AppState;
+
+#[api_v2_operation]
+#[get("/airports/cache")]
+#[tracing::instrument(skip_all)]
+pub async fn cache_stats_handler(
+    _scope: RequireScope<AdminScope>,
+    app_state: Data<AppState>,
+) -> Result<Json<CacheStatsResponse>, actix_web::Error> {
+    Ok(Json(CacheStatsResponse {
+        caches: app_state.airports_repository.snapshot().cache_stats(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use actix_web::{http, test, App};
+
+    use crate::services::airports::GlobalAirportsRepository;
+    use crate::services::healthcheck::{
+        hostname_provider::MockSuccessfulHostnameProvider, time_provider::MockTimeProvider,
+    };
+    use crate::DATABASE_URL;
+
+    use crate::auth::AuthenticateAs;
+    use actix_web::web;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_cache_stats_handler() {
+        let repository = GlobalAirportsRepository::open(DATABASE_URL).await.unwrap();
+        let state = Data::new(AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            Box::new(MockTimeProvider::new(SystemTime::now())),
+            Box::new(repository),
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/admin").service(cache_stats_handler))
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let repository = state.airports_repository.snapshot();
+        for iata_code in ["WAW", "WAW", "XXX", "XXX"] {
+            repository.fetch_airport_by_iata(iata_code).await.unwrap();
+        }
+        repository.dataset_metadata().await.unwrap();
+        repository.dataset_metadata().await.unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/admin/airports/cache")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body: CacheStatsResponse = test::read_body_json(resp).await;
+        let airports = &body.caches[0];
+        assert_eq!(airports.name, "airports");
+        assert_eq!((airports.hits, airports.misses), (2, 2));
+
+        let dataset_metadata = &body.caches[2];
+        assert_eq!(dataset_metadata.name, "dataset_metadata");
+        assert_eq!((dataset_metadata.hits, dataset_metadata.misses), (1, 1));
+    }
+}
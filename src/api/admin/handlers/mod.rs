@@ -0,0 +1,16 @@
+mod airports_handler;
+mod api_keys_handler;
+mod cache_handler;
+mod reload_handler;
+mod usage_handler;
+
+pub(crate) use airports_handler::{
+    create_airport_handler, delete_airport_handler, overlay_audit_log_handler,
+    update_airport_handler,
+};
+pub(crate) use api_keys_handler::{
+    issue_api_key_handler, list_api_keys_handler, revoke_api_key_handler,
+};
+pub(crate) use cache_handler::cache_stats_handler;
+pub(crate) use reload_handler::reload_airports_handler;
+pub(crate) use usage_handler::usage_summary_handler;
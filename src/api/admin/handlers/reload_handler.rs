@@ -0,0 +1,160 @@
+#![allow(non_camel_case_types)]
+
+use actix_web::error::{ErrorForbidden, ErrorUnprocessableEntity};
+use actix_web::web::Data;
+use paperclip::actix::web::Json;
+use paperclip::actix::{api_v2_operation, post};
+use serde_json::json;
+use tracing::log;
+
+use crate::api::admin::schemas::{ReloadAirportsRequest, ReloadAirportsResponse};
+use crate::auth::{AdminScope, RequireScope};
+use crate::services::airports::{AirportsDatabaseReloader, ReloadError};
+use crate::services::app_state::AppState;
+
+#[api_v2_operation]
+#[post("/airports/reload")]
+#[tracing::instrument(skip_all)]
+pub async fn reload_airports_handler(
+    _scope: RequireScope<AdminScope>,
+    request: Json<ReloadAirportsRequest>,
+    app_state: Data<AppState>,
+    reloader: Data<AirportsDatabaseReloader>,
+) -> Result<Json<ReloadAirportsResponse>, actix_web::Error> {
+    let request = request.into_inner();
+
+    match reloader
+        .reload(
+            &app_state.airports_repository,
+            request.database_path.as_deref(),
+        )
+        .await
+    {
+        Ok(summary) => Ok(Json(ReloadAirportsResponse {
+            database_path: summary.database_url,
+            airports_count: summary.airports_count,
+            dataset: summary.dataset,
+        })),
+        Err(e @ ReloadError::ForbiddenPath(_)) => {
+            log::warn!("Refused to reload airports database: {e}");
+            Err(ErrorForbidden(json!({
+                "error": "Database path not allowed",
+                "details": e.to_string(),
+            })))
+        }
+        Err(e) => {
+            log::error!("Failed to reload airports database: {e}");
+            Err(ErrorUnprocessableEntity(json!({
+                "error": "Failed to reload airports database",
+                "details": e.to_string(),
+            })))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use actix_web::{http, test, App};
+
+    use crate::{
+        services::{
+            airports::DummyAirportsRepository,
+            healthcheck::{
+                hostname_provider::MockSuccessfulHostnameProvider, time_provider::MockTimeProvider,
+            },
+        },
+        DATABASE_URL,
+    };
+
+    use crate::auth::AuthenticateAs;
+    use actix_web::web;
+
+    use super::*;
+
+    fn app_state() -> Data<AppState> {
+        Data::new(AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            Box::new(MockTimeProvider::new(SystemTime::now())),
+            Box::new(DummyAirportsRepository::new(vec![])),
+        ))
+    }
+
+    #[actix_web::test]
+    async fn test_reload_airports_handler() {
+        let state = app_state();
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .app_data(Data::new(AirportsDatabaseReloader::new(DATABASE_URL)))
+                .service(web::scope("/admin").service(reload_airports_handler))
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/airports/reload")
+            .set_json(ReloadAirportsRequest::default())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body: ReloadAirportsResponse = test::read_body_json(resp).await;
+        assert_eq!(body.database_path, DATABASE_URL);
+        assert!(body.airports_count > 0);
+
+        let airport = state
+            .airports_repository
+            .snapshot()
+            .fetch_airport_by_iata("JFK")
+            .await
+            .unwrap();
+        assert!(airport.is_some());
+    }
+
+    #[actix_web::test]
+    async fn test_reload_airports_handler_invalid_database() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state())
+                .app_data(Data::new(AirportsDatabaseReloader::new(DATABASE_URL)))
+                .service(web::scope("/admin").service(reload_airports_handler))
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/airports/reload")
+            .set_json(ReloadAirportsRequest {
+                database_path: Some("./does_not_exist.sqlite".to_owned()),
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[actix_web::test]
+    async fn test_reload_airports_handler_forbidden_path() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state())
+                .app_data(Data::new(AirportsDatabaseReloader::new(DATABASE_URL)))
+                .service(web::scope("/admin").service(reload_airports_handler))
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/airports/reload")
+            .set_json(ReloadAirportsRequest {
+                database_path: Some("/etc/passwd".to_owned()),
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+    }
+}
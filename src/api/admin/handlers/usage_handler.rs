@@ -0,0 +1,155 @@
+#![allow(non_camel_case_types)]
+
+use std::time::UNIX_EPOCH;
+
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound};
+use actix_web::web::Data;
+use paperclip::actix::web::{Json, Query};
+use paperclip::actix::{api_v2_operation, get};
+use serde_json::json;
+use serde_valid::Validate;
+use tracing::log;
+
+use crate::api::admin::schemas::{UsageSummaryRequest, UsageSummaryResponse};
+use crate::auth::{AdminScope, RequireScope};
+use crate::models::UsageCounts;
+use crate::services::app_state::AppState;
+use crate::usage::{UsagePeriod, UsageTracker};
+
+#[api_v2_operation]
+#[get("/usage")]
+#[tracing::instrument(skip_all)]
+pub async fn usage_summary_handler(
+    _scope: RequireScope<AdminScope>,
+    request: Query<UsageSummaryRequest>,
+    app_state: Data<AppState>,
+    usage_tracker: Option<Data<UsageTracker>>,
+) -> Result<Json<UsageSummaryResponse>, actix_web::Error> {
+    let usage_tracker = usage_tracker
+        .ok_or_else(|| ErrorNotFound(json!({"error": "Usage tracking is not configured"})))?;
+    let request = request.into_inner();
+
+    if let Err(validation_errors) = request.validate() {
+        log::warn!("Failed to validate request: {validation_errors:?}");
+        return Err(ErrorBadRequest(validation_errors));
+    }
+
+    let month = match request.month {
+        Some(month) => month,
+        None => {
+            let now = app_state
+                .time_provider
+                .now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs() as i64);
+            UsagePeriod::at(now).month
+        }
+    };
+
+    let clients = match usage_tracker.summary(&month).await {
+        Ok(clients) => clients,
+        Err(e) => {
+            log::error!("Failed to read usage summary: {e}");
+            return Err(ErrorInternalServerError(json!({"error": "Database fail"})));
+        }
+    };
+
+    let mut total = UsageCounts::default();
+    for client in &clients {
+        total += client.usage;
+    }
+
+    Ok(Json(UsageSummaryResponse {
+        month,
+        clients,
+        total,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::{http, test, App};
+
+    use crate::services::{
+        airports::DummyAirportsRepository,
+        healthcheck::{
+            hostname_provider::MockSuccessfulHostnameProvider, time_provider::MockTimeProvider,
+        },
+    };
+    use crate::usage::{QuotaConfig, UsageStore};
+
+    use crate::auth::AuthenticateAs;
+    use actix_web::web;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_summarises_usage_of_all_clients() {
+        let app_state = Data::new(AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            // 2023-05-02T04:00:00Z
+            Box::new(MockTimeProvider::new(
+                UNIX_EPOCH + Duration::from_secs(1683000000),
+            )),
+            Box::new(DummyAirportsRepository::new(vec![])),
+        ));
+        let usage_tracker = UsageTracker::new(
+            UsageStore::open("sqlite::memory:").await.unwrap(),
+            QuotaConfig::default(),
+        );
+        for (client, timestamp, legs) in [
+            ("alice", 1683000000, 2),
+            ("api-key:1", 1683000000, 5),
+            ("api-key:1", 1683000001, 1),
+            // 2023-04-30
+            ("alice", 1682899199, 7),
+        ] {
+            let usage = UsageCounts {
+                requests: 1,
+                legs,
+                airport_lookups: 0,
+            };
+            usage_tracker
+                .record(client, timestamp, &usage)
+                .await
+                .unwrap();
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state)
+                .app_data(Data::new(usage_tracker))
+                .service(web::scope("/admin").service(usage_summary_handler))
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/admin/usage").to_request();
+        let body: UsageSummaryResponse =
+            test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(body.month, "2023-05");
+        assert_eq!(
+            body.clients
+                .iter()
+                .map(|client| (client.client.as_str(), client.usage.legs))
+                .collect::<Vec<(&str, i64)>>(),
+            vec![("api-key:1", 6), ("alice", 2)]
+        );
+        assert_eq!(body.total.requests, 3);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/usage?month=2023-04")
+            .to_request();
+        let body: UsageSummaryResponse =
+            test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(body.total.legs, 7);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/usage?month=2023-13")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,2 @@
+pub(crate) mod handlers;
+mod schemas;
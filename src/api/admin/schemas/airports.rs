@@ -0,0 +1,59 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+
+use crate::models::{Airport, Dms};
+
+#[derive(Debug, Deserialize, Serialize, Validate, Apiv2Schema)]
+pub struct AirportRequest {
+    #[serde(default)]
+    pub icao_code: Option<String>,
+    #[validate(min_length = 1)]
+    pub name: String,
+    #[serde(default)]
+    pub city: String,
+    #[serde(default)]
+    pub country: String,
+    /// Altitude in meters.
+    #[serde(default)]
+    pub altitude: i64,
+    #[validate(minimum = -90.0)]
+    #[validate(maximum = 90.0)]
+    pub latitude: f64,
+    #[validate(minimum = -180.0)]
+    #[validate(maximum = 180.0)]
+    pub longitude: f64,
+}
+
+impl AirportRequest {
+    /// Builds the airport record, deriving DMS fields from the decimal coordinates.
+    pub fn into_airport(self, id: u32, iata_code: &str) -> Airport {
+        let lat = Dms::latitude(self.latitude);
+        let lon = Dms::longitude(self.longitude);
+
+        Airport {
+            id,
+            icao_code: self.icao_code.unwrap_or_else(|| "N/A".to_owned()),
+            iata_code: iata_code.to_owned(),
+            name: self.name,
+            city: self.city,
+            country: self.country,
+            lat_deg: lat.degrees,
+            lat_min: lat.minutes,
+            lat_sec: lat.seconds,
+            lat_dir: lat.direction,
+            lon_deg: lon.degrees,
+            lon_min: lon.minutes,
+            lon_sec: lon.seconds,
+            lon_dir: lon.direction,
+            altitude: self.altitude,
+            lat_decimal: self.latitude,
+            lon_decimal: self.longitude,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
+pub struct OverlayAuditLogResponse {
+    pub entries: Vec<crate::services::airports::OverlayAuditEntry>,
+}
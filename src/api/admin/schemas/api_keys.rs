@@ -0,0 +1,28 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+
+use crate::auth::ApiKey;
+
+#[derive(Debug, Deserialize, Serialize, Validate, Apiv2Schema)]
+pub struct IssueApiKeyRequest {
+    /// Name of the client the key is issued for.
+    #[validate(min_length = 1)]
+    #[validate(max_length = 100)]
+    pub label: String,
+    /// Unix timestamp after which the key is rejected, keys without it don't expire.
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
+pub struct IssuedApiKeyResponse {
+    /// Key to send in the `X-API-Key` header or as a bearer token, it can't be retrieved later.
+    pub key: String,
+    pub api_key: ApiKey,
+}
+
+#[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
+pub struct ApiKeysResponse {
+    pub api_keys: Vec<ApiKey>,
+}
@@ -0,0 +1,10 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::CacheStats;
+
+#[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
+pub struct CacheStatsResponse {
+    /// Counters since the repository was opened, empty for repositories without caches.
+    pub caches: Vec<CacheStats>,
+}
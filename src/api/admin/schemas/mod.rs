@@ -0,0 +1,11 @@
+mod airports;
+mod api_keys;
+mod cache;
+mod reload;
+mod usage;
+
+pub use self::airports::{AirportRequest, OverlayAuditLogResponse};
+pub use self::api_keys::{ApiKeysResponse, IssueApiKeyRequest, IssuedApiKeyResponse};
+pub use self::cache::CacheStatsResponse;
+pub use self::reload::{ReloadAirportsRequest, ReloadAirportsResponse};
+pub use self::usage::{UsageSummaryRequest, UsageSummaryResponse};
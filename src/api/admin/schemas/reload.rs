@@ -0,0 +1,18 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::DatasetMetadata;
+
+#[derive(Debug, Default, Deserialize, Serialize, Apiv2Schema)]
+pub struct ReloadAirportsRequest {
+    /// Database to load, defaults to the one the service was started with.
+    #[serde(default)]
+    pub database_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
+pub struct ReloadAirportsResponse {
+    pub database_path: String,
+    pub airports_count: usize,
+    pub dataset: Option<DatasetMetadata>,
+}
@@ -0,0 +1,45 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+
+use crate::models::{ClientUsage, UsageCounts};
+
+#[derive(Debug, Deserialize, Serialize, Validate, Apiv2Schema)]
+pub struct UsageSummaryRequest {
+    /// Month in the `YYYY-MM` format, the current one by default.
+    #[validate(custom(validate_month))]
+    pub month: Option<String>,
+}
+
+fn validate_month(month: &Option<String>) -> Result<(), serde_valid::validation::Error> {
+    let Some(month) = month else {
+        return Ok(());
+    };
+
+    let valid = match month.split_once('-') {
+        Some((year, month)) => {
+            year.len() == 4
+                && year.bytes().all(|c| c.is_ascii_digit())
+                && month.len() == 2
+                && month
+                    .parse::<u32>()
+                    .is_ok_and(|month| (1..=12).contains(&month))
+        }
+        None => false,
+    };
+
+    match valid {
+        true => Ok(()),
+        false => Err(serde_valid::validation::Error::Custom(
+            "month has to be in the YYYY-MM format".to_owned(),
+        )),
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
+pub struct UsageSummaryResponse {
+    pub month: String,
+    /// Usage of clients with any requests in the month, heaviest users first.
+    pub clients: Vec<ClientUsage>,
+    pub total: UsageCounts,
+}
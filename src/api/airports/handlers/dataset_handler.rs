@@ -0,0 +1,109 @@
+#![allow(non_camel_case_types)]
+
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
+use actix_web::web::Data;
+use paperclip::actix::web::Json;
+use paperclip::actix::{api_v2_operation, get};
+use serde_json::json;
+use tracing::log;
+
+use crate::auth::{AirportsScope, RequireScope};
+use crate::models::DatasetMetadata;
+use crate::services::app_state::AppState;
+
+#[api_v2_operation]
+#[get("/airports/dataset")]
+#[tracing::instrument(skip_all)]
+pub async fn dataset_metadata_handler(
+    _scope: RequireScope<AirportsScope>,
+    data: Data<AppState>,
+) -> Result<Json<DatasetMetadata>, actix_web::Error> {
+    match data.airports_repository.snapshot().dataset_metadata().await {
+        Ok(Some(metadata)) => Ok(Json(metadata)),
+        Ok(None) => Err(ErrorNotFound(
+            json!({"error": "Dataset metadata is not available"}),
+        )),
+        Err(e) => {
+            log::error!("Failed to fetch dataset metadata from database: {e}");
+            Err(ErrorInternalServerError(json!({"error": "Database fail"})))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use actix_web::{http, test, App};
+
+    use crate::services::{
+        airports::DummyAirportsRepository,
+        healthcheck::{
+            hostname_provider::MockSuccessfulHostnameProvider, time_provider::MockTimeProvider,
+        },
+    };
+
+    use crate::auth::AuthenticateAs;
+
+    use super::*;
+
+    fn metadata() -> DatasetMetadata {
+        DatasetMetadata {
+            source: "ourairports".to_owned(),
+            version: "20230501".to_owned(),
+            imported_at: 1683000000,
+            rows_read: 10,
+            airports_count: 8,
+            rejected_count: 2,
+            checksum: "abc".to_owned(),
+        }
+    }
+
+    fn app_state(repository: DummyAirportsRepository) -> Data<AppState> {
+        Data::new(AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            Box::new(MockTimeProvider::new(SystemTime::now())),
+            Box::new(repository),
+        ))
+    }
+
+    #[actix_web::test]
+    async fn test_dataset_handler() {
+        let repository = DummyAirportsRepository::new(vec![]).with_dataset_metadata(metadata());
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state(repository))
+                .service(dataset_metadata_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/airports/dataset")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body: DatasetMetadata = test::read_body_json(resp).await;
+        assert_eq!(body, metadata());
+    }
+
+    #[actix_web::test]
+    async fn test_dataset_handler_without_metadata() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state(DummyAirportsRepository::new(vec![])))
+                .service(dataset_metadata_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/airports/dataset")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+    }
+}
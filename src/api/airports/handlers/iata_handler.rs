@@ -1,6 +1,7 @@
 #![allow(non_camel_case_types)]
 
 use crate::api::airports::schemas::UniqueIatasResponse;
+use crate::auth::{AirportsScope, RequireScope};
 use actix_web::error::ErrorInternalServerError;
 use actix_web::web::Data;
 use paperclip::actix::web::Json;
@@ -11,10 +12,16 @@ use crate::services::app_state::AppState;
 
 #[api_v2_operation]
 #[get("/airports/iatas")]
+#[tracing::instrument(skip_all)]
 pub async fn unique_iatas_handler(
+    _scope: RequireScope<AirportsScope>,
     data: Data<AppState>,
 ) -> Result<Json<UniqueIatasResponse>, actix_web::Error> {
-    let iatas = data.airports_repository.unique_airport_iatas().await;
+    let iatas = data
+        .airports_repository
+        .snapshot()
+        .unique_airport_iatas()
+        .await;
 
     match iatas {
         Ok(iatas) => Ok(Json(UniqueIatasResponse { iatas })),
@@ -40,6 +47,8 @@ mod tests {
         DATABASE_URL,
     };
 
+    use crate::auth::AuthenticateAs;
+
     use super::*;
 
     #[actix_web::test]
@@ -51,19 +60,20 @@ mod tests {
             Box::new(GlobalAirportsRepository::new(pool).await),
         );
 
-        let mut app = test::init_service(
+        let app = test::init_service(
             App::new()
                 .app_data(Data::new(state))
-                .service(unique_iatas_handler),
+                .service(unique_iatas_handler)
+                .wrap(AuthenticateAs::admin()),
         )
         .await;
 
         let req = test::TestRequest::get().uri("/airports/iatas").to_request();
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert_eq!(resp.status(), http::StatusCode::OK);
 
         let body: UniqueIatasResponse = test::read_body_json(resp).await;
-        assert!(body.iatas.len() > 0);
+        assert!(!body.iatas.is_empty());
     }
 }
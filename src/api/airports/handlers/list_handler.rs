@@ -0,0 +1,364 @@
+#![allow(non_camel_case_types)]
+
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::web::Data;
+use paperclip::actix::web::{Json, Query};
+use paperclip::actix::{api_v2_operation, get};
+use serde_json::json;
+use serde_valid::Validate;
+use tracing::log;
+
+use crate::api::airports::schemas::{
+    AirportListItem, ListAirportsRequest, ListAirportsResponse, ListCursor, SortOrder,
+    AIRPORT_FIELDS,
+};
+use crate::auth::{AirportsScope, RequireScope};
+use crate::services::airports::{AirportFilter, AirportsQuery, BoundingBox};
+use crate::services::app_state::AppState;
+
+#[api_v2_operation]
+#[get("/airports")]
+#[tracing::instrument(skip_all)]
+pub async fn list_airports_handler(
+    _scope: RequireScope<AirportsScope>,
+    request: Query<ListAirportsRequest>,
+    app_state: Data<AppState>,
+) -> Result<Json<ListAirportsResponse>, actix_web::Error> {
+    let request = request.into_inner();
+
+    if let Err(validation_errors) = request.validate() {
+        log::warn!("Failed to validate request: {validation_errors:?}");
+        return Err(ErrorBadRequest(validation_errors));
+    }
+
+    let fields = parse_fields(request.fields.as_deref())?;
+    let mut query = build_query(&request)?;
+
+    // one airport more than requested tells whether there's a next page
+    query.limit += 1;
+    let mut airports = match app_state
+        .airports_repository
+        .snapshot()
+        .list_airports(&query)
+        .await
+    {
+        Ok(airports) => airports,
+        Err(e) => {
+            log::error!("Failed to list airports from database: {e}");
+            return Err(ErrorInternalServerError(json!({"error": "Database fail"})));
+        }
+    };
+
+    let next_cursor = match airports.len() > request.limit {
+        true => {
+            airports.truncate(request.limit);
+            airports.last().map(|airport| {
+                ListCursor {
+                    sort: request.sort,
+                    order: request.order,
+                    after: query.cursor(airport),
+                }
+                .encode()
+            })
+        }
+        false => None,
+    };
+
+    Ok(Json(ListAirportsResponse {
+        airports: airports
+            .into_iter()
+            .map(|airport| AirportListItem::new(airport, fields.as_deref()))
+            .collect(),
+        next_cursor,
+    }))
+}
+
+fn build_query(request: &ListAirportsRequest) -> Result<AirportsQuery, actix_web::Error> {
+    let bounding_box = match (
+        request.min_latitude,
+        request.max_latitude,
+        request.min_longitude,
+        request.max_longitude,
+    ) {
+        (Some(min_latitude), Some(max_latitude), Some(min_longitude), Some(max_longitude)) => {
+            if min_latitude > max_latitude {
+                return Err(ErrorBadRequest(json!({
+                    "error": "min_latitude can't be greater than max_latitude"
+                })));
+            }
+
+            Some(BoundingBox {
+                min_latitude,
+                max_latitude,
+                min_longitude,
+                max_longitude,
+            })
+        }
+        (None, None, None, None) => None,
+        _ => {
+            return Err(ErrorBadRequest(json!({
+                "error": "Bounding box requires min_latitude, max_latitude, min_longitude and max_longitude"
+            })))
+        }
+    };
+
+    let after = match &request.cursor {
+        Some(cursor) => match ListCursor::decode(cursor) {
+            Some(cursor) if cursor.sort == request.sort && cursor.order == request.order => {
+                Some(cursor.after)
+            }
+            _ => {
+                log::warn!("Invalid airports listing cursor: {cursor}");
+                return Err(ErrorBadRequest(json!({"error": "Invalid cursor"})));
+            }
+        },
+        None => None,
+    };
+
+    Ok(AirportsQuery {
+        filter: AirportFilter {
+            icao_code: request.icao_code.clone(),
+            country: request.country.clone(),
+            city: request.city.clone(),
+            has_iata: request.has_iata,
+            has_icao: request.has_icao,
+            bounding_box,
+            min_altitude: request.min_altitude,
+            max_altitude: request.max_altitude,
+        },
+        sort: request.sort,
+        descending: request.order == SortOrder::Desc,
+        after,
+        limit: request.limit,
+    })
+}
+
+fn parse_fields(fields: Option<&str>) -> Result<Option<Vec<String>>, actix_web::Error> {
+    let Some(fields) = fields else {
+        return Ok(None);
+    };
+
+    let fields = fields
+        .split(',')
+        .map(|field| field.trim().to_owned())
+        .filter(|field| !field.is_empty())
+        .collect::<Vec<String>>();
+
+    let unknown_fields = fields
+        .iter()
+        .filter(|field| !AIRPORT_FIELDS.contains(&field.as_str()))
+        .collect::<Vec<&String>>();
+
+    if !unknown_fields.is_empty() {
+        return Err(ErrorBadRequest(json!({
+            "error": "Unknown fields requested",
+            "details": {"unknown_fields": unknown_fields}
+        })));
+    }
+
+    Ok(Some(fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use actix_web::{http, test, App};
+
+    use crate::models::Airport;
+    use crate::services::airports::{
+        AirportsRepository, DummyAirportsRepository, GlobalAirportsRepository,
+    };
+    use crate::services::healthcheck::{
+        hostname_provider::MockSuccessfulHostnameProvider, time_provider::MockTimeProvider,
+    };
+    use crate::DATABASE_URL;
+
+    use crate::auth::AuthenticateAs;
+
+    use super::*;
+
+    fn airport(id: u32, iata_code: &str, country: &str, altitude: i64) -> Airport {
+        Airport {
+            id,
+            icao_code: "N/A".to_owned(),
+            iata_code: iata_code.to_owned(),
+            name: iata_code.to_owned(),
+            city: String::new(),
+            country: country.to_owned(),
+            lat_deg: 0,
+            lat_min: 0,
+            lat_sec: 0,
+            lat_dir: "N".to_owned(),
+            lon_deg: 0,
+            lon_min: 0,
+            lon_sec: 0,
+            lon_dir: "E".to_owned(),
+            altitude,
+            lat_decimal: 1.,
+            lon_decimal: 1.,
+        }
+    }
+
+    fn app_state(repository: Box<dyn AirportsRepository>) -> Data<AppState> {
+        Data::new(AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            Box::new(MockTimeProvider::new(SystemTime::now())),
+            repository,
+        ))
+    }
+
+    fn dummy_repository() -> Box<DummyAirportsRepository> {
+        Box::new(DummyAirportsRepository::new(vec![
+            airport(1, "WAW", "POLAND", 110),
+            airport(2, "CDG", "FRANCE", 119),
+            airport(3, "KRK", "POLAND", 241),
+            airport(4, "ORY", "FRANCE", 89),
+            airport(5, "KTW", "POLAND", 303),
+        ]))
+    }
+
+    fn iatas(response: &ListAirportsResponse) -> Vec<&str> {
+        response
+            .airports
+            .iter()
+            .map(|airport| airport.iata_code.as_deref().unwrap())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_list_airports_handler_pages_results() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state(dummy_repository()))
+                .service(list_airports_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/airports?country=poland&sort=altitude&order=desc&limit=2")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let first_page: ListAirportsResponse = test::read_body_json(resp).await;
+        assert_eq!(iatas(&first_page), vec!["KTW", "KRK"]);
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/airports?country=poland&sort=altitude&order=desc&limit=2&cursor={}",
+                first_page.next_cursor.unwrap()
+            ))
+            .to_request();
+        let second_page: ListAirportsResponse =
+            test::read_body_json(test::call_service(&app, req).await).await;
+
+        assert_eq!(iatas(&second_page), vec!["WAW"]);
+        assert_eq!(second_page.next_cursor, None);
+    }
+
+    #[actix_web::test]
+    async fn test_list_airports_handler_selects_fields() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state(dummy_repository()))
+                .service(list_airports_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/airports?max_altitude=100&fields=iata_code,altitude")
+            .to_request();
+        let body: ListAirportsResponse =
+            test::read_body_json(test::call_service(&app, req).await).await;
+
+        assert_eq!(
+            body.airports,
+            vec![AirportListItem {
+                iata_code: Some("ORY".to_owned()),
+                altitude: Some(89),
+                ..AirportListItem::default()
+            }]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_list_airports_handler_rejects_invalid_requests() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state(dummy_repository()))
+                .service(list_airports_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        for uri in [
+            "/airports?fields=iata_code,runways",
+            "/airports?min_latitude=10&max_latitude=20",
+            "/airports?limit=0",
+            "/airports?cursor=not-a-cursor",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST, "{uri}");
+        }
+
+        // cursors are bound to the sort they were issued for
+        let req = test::TestRequest::get()
+            .uri("/airports?limit=1")
+            .to_request();
+        let body: ListAirportsResponse =
+            test::read_body_json(test::call_service(&app, req).await).await;
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/airports?limit=1&sort=name&cursor={}",
+                body.next_cursor.unwrap()
+            ))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_list_airports_handler_with_global_database() {
+        let repository = GlobalAirportsRepository::open(DATABASE_URL).await.unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state(Box::new(repository)))
+                .service(list_airports_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/airports?min_latitude=49&max_latitude=55&min_longitude=14&max_longitude=24&has_iata=true&sort=iata_code&limit=3")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let first_page: ListAirportsResponse = test::read_body_json(resp).await;
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/airports?min_latitude=49&max_latitude=55&min_longitude=14&max_longitude=24&has_iata=true&sort=iata_code&limit=3&cursor={}",
+                first_page.next_cursor.as_ref().unwrap()
+            ))
+            .to_request();
+        let second_page: ListAirportsResponse =
+            test::read_body_json(test::call_service(&app, req).await).await;
+
+        let mut iatas = iatas(&first_page);
+        iatas.extend(self::iatas(&second_page));
+
+        let mut sorted = iatas.clone();
+        sorted.sort();
+        sorted.dedup();
+
+        assert_eq!(iatas.len(), 6);
+        assert_eq!(iatas, sorted);
+        assert!(!iatas.contains(&"N/A"));
+    }
+}
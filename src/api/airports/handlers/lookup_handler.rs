@@ -0,0 +1,139 @@
+#![allow(non_camel_case_types)]
+
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::web::Data;
+use actix_web::HttpRequest;
+use itertools::Itertools;
+use paperclip::actix::web::Json;
+use paperclip::actix::{api_v2_operation, post};
+use serde_json::json;
+use serde_valid::Validate;
+use tracing::log;
+
+use crate::api::airports::schemas::{AirportLookupRequest, AirportLookupResponse};
+use crate::auth::{AirportsScope, RequireScope};
+use crate::services::app_state::AppState;
+use crate::usage::record_usage;
+
+#[api_v2_operation]
+#[post("/airports/lookup")]
+#[tracing::instrument(skip_all)]
+pub async fn lookup_airports_handler(
+    _scope: RequireScope<AirportsScope>,
+    http_request: HttpRequest,
+    request: Json<AirportLookupRequest>,
+    app_state: Data<AppState>,
+) -> Result<Json<AirportLookupResponse>, actix_web::Error> {
+    let request = request.into_inner();
+
+    if let Err(validation_errors) = request.validate() {
+        log::warn!("Failed to validate request: {validation_errors:?}");
+        return Err(ErrorBadRequest(validation_errors));
+    }
+
+    let codes = request.codes.into_iter().unique().collect::<Vec<String>>();
+
+    let mut airports = match app_state
+        .airports_repository
+        .snapshot()
+        .fetch_airports_by_iata(&codes)
+        .await
+    {
+        Ok(airports) => airports,
+        Err(e) => {
+            log::error!("Failed to fetch airports from database: {e}");
+            return Err(ErrorInternalServerError(json!({"error": "Database fail"})));
+        }
+    };
+
+    record_usage(&http_request, 0, codes.len());
+
+    let mut response = AirportLookupResponse {
+        airports: Vec::with_capacity(airports.len()),
+        unresolved: vec![],
+    };
+
+    for code in codes {
+        match airports.remove(&code) {
+            Some(airport) => response.airports.push(airport),
+            None => response.unresolved.push(code),
+        }
+    }
+
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use actix_web::{http, test, App};
+
+    use crate::services::airports::GlobalAirportsRepository;
+    use crate::services::healthcheck::{
+        hostname_provider::MockSuccessfulHostnameProvider, time_provider::MockTimeProvider,
+    };
+    use crate::DATABASE_URL;
+
+    use crate::auth::AuthenticateAs;
+
+    use super::*;
+
+    async fn app_state() -> Data<AppState> {
+        let repository = GlobalAirportsRepository::open(DATABASE_URL).await.unwrap();
+
+        Data::new(AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            Box::new(MockTimeProvider::new(SystemTime::now())),
+            Box::new(repository),
+        ))
+    }
+
+    #[actix_web::test]
+    async fn test_lookup_airports_handler() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state().await)
+                .service(lookup_airports_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/airports/lookup")
+            .set_json(json!({"codes": ["WAW", "XXX", "LHR", "WAW", "KRK"]}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body: AirportLookupResponse = test::read_body_json(resp).await;
+        assert_eq!(
+            body.airports
+                .iter()
+                .map(|airport| airport.iata_code.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["WAW", "LHR", "KRK"]
+        );
+        assert_eq!(body.unresolved, vec!["XXX".to_owned()]);
+    }
+
+    #[actix_web::test]
+    async fn test_lookup_airports_handler_without_codes() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state().await)
+                .service(lookup_airports_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/airports/lookup")
+            .set_json(json!({"codes": []}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+    }
+}
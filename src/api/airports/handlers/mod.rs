@@ -1,3 +1,9 @@
+mod dataset_handler;
 mod iata_handler;
+mod list_handler;
+mod lookup_handler;
 
+pub(crate) use dataset_handler::dataset_metadata_handler;
 pub(crate) use iata_handler::unique_iatas_handler;
+pub(crate) use list_handler::list_airports_handler;
+pub(crate) use lookup_handler::lookup_airports_handler;
@@ -1,2 +1,2 @@
 pub(crate) mod handlers;
-pub(self) mod schemas;
+mod schemas;
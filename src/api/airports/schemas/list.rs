@@ -0,0 +1,187 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+
+use crate::models::Airport;
+use crate::services::airports::{AirportCursor, AirportSort};
+
+/// Fields of `Airport` which can be selected with the `fields` parameter.
+pub const AIRPORT_FIELDS: [&str; 17] = [
+    "id",
+    "icao_code",
+    "iata_code",
+    "name",
+    "city",
+    "country",
+    "lat_deg",
+    "lat_min",
+    "lat_sec",
+    "lat_dir",
+    "lon_deg",
+    "lon_min",
+    "lon_sec",
+    "lon_dir",
+    "altitude",
+    "lat_decimal",
+    "lon_decimal",
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Apiv2Schema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate, Apiv2Schema)]
+pub struct ListAirportsRequest {
+    /// ICAO code, case-insensitive.
+    pub icao_code: Option<String>,
+    /// Country name, ignoring the case of ASCII letters only.
+    pub country: Option<String>,
+    /// City name, ignoring the case of ASCII letters only.
+    pub city: Option<String>,
+    pub has_iata: Option<bool>,
+    pub has_icao: Option<bool>,
+    /// Southern edge of the bounding box, all four edges have to be given together.
+    #[validate(minimum = -90.0)]
+    #[validate(maximum = 90.0)]
+    pub min_latitude: Option<f64>,
+    /// Northern edge of the bounding box.
+    #[validate(minimum = -90.0)]
+    #[validate(maximum = 90.0)]
+    pub max_latitude: Option<f64>,
+    /// Western edge of the bounding box, greater than `max_longitude` for boxes crossing
+    /// the antimeridian.
+    #[validate(minimum = -180.0)]
+    #[validate(maximum = 180.0)]
+    pub min_longitude: Option<f64>,
+    /// Eastern edge of the bounding box.
+    #[validate(minimum = -180.0)]
+    #[validate(maximum = 180.0)]
+    pub max_longitude: Option<f64>,
+    /// Minimum altitude in meters.
+    pub min_altitude: Option<i64>,
+    /// Maximum altitude in meters.
+    pub max_altitude: Option<i64>,
+    #[serde(default)]
+    pub sort: AirportSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    #[serde(default = "default_limit")]
+    #[validate(minimum = 1)]
+    #[validate(maximum = 1000)]
+    pub limit: usize,
+    /// Comma separated fields to return, e.g. `iata_code,name,country`, all by default.
+    pub fields: Option<String>,
+}
+
+/// Airport with the selected fields only.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Apiv2Schema)]
+pub struct AirportListItem {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icao_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iata_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_deg: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_min: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_sec: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_deg: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_min: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_sec: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat_decimal: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon_decimal: Option<f64>,
+}
+
+impl AirportListItem {
+    /// Keeps fields of `airport` listed in `fields`, or all of them if it's `None`.
+    pub fn new(airport: Airport, fields: Option<&[String]>) -> Self {
+        let selected = |field: &str| fields.is_none_or(|fields| fields.iter().any(|f| f == field));
+
+        Self {
+            id: selected("id").then_some(airport.id),
+            icao_code: selected("icao_code").then_some(airport.icao_code),
+            iata_code: selected("iata_code").then_some(airport.iata_code),
+            name: selected("name").then_some(airport.name),
+            city: selected("city").then_some(airport.city),
+            country: selected("country").then_some(airport.country),
+            lat_deg: selected("lat_deg").then_some(airport.lat_deg),
+            lat_min: selected("lat_min").then_some(airport.lat_min),
+            lat_sec: selected("lat_sec").then_some(airport.lat_sec),
+            lat_dir: selected("lat_dir").then_some(airport.lat_dir),
+            lon_deg: selected("lon_deg").then_some(airport.lon_deg),
+            lon_min: selected("lon_min").then_some(airport.lon_min),
+            lon_sec: selected("lon_sec").then_some(airport.lon_sec),
+            lon_dir: selected("lon_dir").then_some(airport.lon_dir),
+            altitude: selected("altitude").then_some(airport.altitude),
+            lat_decimal: selected("lat_decimal").then_some(airport.lat_decimal),
+            lon_decimal: selected("lon_decimal").then_some(airport.lon_decimal),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
+pub struct ListAirportsResponse {
+    pub airports: Vec<AirportListItem>,
+    /// Cursor of the next page, missing on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// Position of the last listed airport, handed out to clients as an opaque hex string.
+/// The sort is part of the cursor, so it can't be reused for a differently ordered listing.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct ListCursor {
+    pub sort: AirportSort,
+    pub order: SortOrder,
+    pub after: AirportCursor,
+}
+
+impl ListCursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_string(self).expect("cursor is always serializable");
+
+        json.bytes().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        if !cursor.len().is_multiple_of(2) {
+            return None;
+        }
+
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+
+        serde_json::from_slice(&bytes).ok()
+    }
+}
@@ -0,0 +1,21 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+
+use crate::models::Airport;
+
+#[derive(Debug, Deserialize, Serialize, Validate, Apiv2Schema)]
+pub struct AirportLookupRequest {
+    /// IATA codes to look up.
+    #[validate(min_items = 1)]
+    #[validate(max_items = 1000)]
+    pub codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
+pub struct AirportLookupResponse {
+    /// Found airports, in the order of requested codes.
+    pub airports: Vec<Airport>,
+    /// Requested codes which aren't known airports.
+    pub unresolved: Vec<String>,
+}
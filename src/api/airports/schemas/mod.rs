@@ -1,3 +1,10 @@
 mod iata;
+mod list;
+mod lookup;
 
 pub use self::iata::UniqueIatasResponse;
+pub use self::list::{
+    AirportListItem, ListAirportsRequest, ListAirportsResponse, ListCursor, SortOrder,
+    AIRPORT_FIELDS,
+};
+pub use self::lookup::{AirportLookupRequest, AirportLookupResponse};
@@ -2,7 +2,7 @@
 
 use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorUnprocessableEntity};
 use actix_web::web::Data;
-use futures::future::join_all;
+use actix_web::HttpRequest;
 use itertools::Itertools;
 use paperclip::actix::web::Json;
 use paperclip::actix::{api_v2_operation, post};
@@ -13,13 +13,19 @@ use tracing::log;
 use crate::api::distance::schemas::{
     AirportCoordinates, AirportDistanceRequest, AirportDistanceResponse, AirportRoutePart,
 };
-use crate::models::{Airport, Coordinates};
+use crate::auth::{DistanceScope, RequireScope};
+use crate::models::Coordinates;
+use crate::services::airports::{resolve_route, ResolvedStop};
 use crate::services::app_state::AppState;
 use crate::services::distance::{DistanceCalculator, DistanceCalculatorFactory};
+use crate::usage::record_usage;
 
 #[api_v2_operation]
 #[post("/calculate_distance/airports")]
+#[tracing::instrument(skip_all)]
 pub async fn airports_handler(
+    _scope: RequireScope<DistanceScope>,
+    http_request: HttpRequest,
     request: Json<AirportDistanceRequest>,
     app_state: Data<AppState>,
 ) -> Result<Json<AirportDistanceResponse>, actix_web::Error> {
@@ -30,31 +36,31 @@ pub async fn airports_handler(
         return Err(ErrorBadRequest(validation_errors));
     }
 
-    let airports_results = join_all(
-        request
-            .route
-            .iter()
-            .map(|iata| app_state.airports_repository.fetch_airport_by_iata(iata)),
+    let calculator = DistanceCalculatorFactory::create(&request.formula, &request.datum);
+
+    let airports_repository = app_state.airports_repository.snapshot();
+
+    let stops = match resolve_route(
+        airports_repository.as_ref(),
+        &request.route,
+        calculator.as_ref(),
     )
-    .await;
+    .await
+    {
+        Ok(stops) => stops,
+        Err(e) => {
+            log::error!("Failed to fetch airport from database: {e}");
+            return Err(ErrorInternalServerError(json!({"error": "Database fail"})));
+        }
+    };
 
-    let mut airports = vec![];
     let mut missing_airports = vec![];
 
-    for (iata, airport_result) in request.route.iter().zip(airports_results.into_iter()) {
-        match airport_result {
-            Ok(airport) => match airport {
-                Some(airport) => airports.push(airport),
-                None => {
-                    log::warn!("Missing airport: {iata}");
-                    missing_airports.push(iata.as_str())
-                }
-            },
-            Err(e) => {
-                log::error!("Failed to fetch airport from database: {e}");
-                return Err(ErrorInternalServerError(json!({"error": "Database fail"})));
-            }
-        };
+    for stop in &stops {
+        if stop.airport.is_none() {
+            log::warn!("Missing airport: {}", stop.requested_code);
+            missing_airports.push(stop.requested_code.as_str());
+        }
     }
 
     if !missing_airports.is_empty() {
@@ -66,13 +72,13 @@ pub async fn airports_handler(
         })));
     }
 
-    let calculator = DistanceCalculatorFactory::create(&request.formula, &request.datum);
-    let distances = match calculate_distances(&airports, calculator.as_ref()) {
+    let distances = match calculate_distances(&stops, calculator.as_ref()) {
         Ok(distances) => distances,
         Err(e) => return Err(e),
     };
 
     let total_distance = distances.iter().map(|part| part.distance).sum();
+    record_usage(&http_request, distances.len(), stops.len());
 
     Ok(Json(AirportDistanceResponse {
         distances,
@@ -83,12 +89,16 @@ pub async fn airports_handler(
 }
 
 fn calculate_distances(
-    route: &[Airport],
+    route: &[ResolvedStop],
     calculator: &dyn DistanceCalculator,
 ) -> Result<Vec<AirportRoutePart>, actix_web::Error> {
     let mut distances: Vec<AirportRoutePart> = Vec::new();
 
-    for (from, to) in route.iter().tuple_windows() {
+    for (from_stop, to_stop) in route.iter().tuple_windows() {
+        let (Some(from), Some(to)) = (&from_stop.airport, &to_stop.airport) else {
+            continue;
+        };
+
         let from_coords = Coordinates::new(from.lat_decimal, from.lon_decimal);
         let to_coords = Coordinates::new(to.lat_decimal, to.lon_decimal);
 
@@ -104,10 +114,12 @@ fn calculate_distances(
             from: AirportCoordinates {
                 iata_code: from.iata_code.to_owned(),
                 coordinates: from_coords,
+                metro_code: from_stop.metro_code.clone(),
             },
             to: AirportCoordinates {
                 iata_code: to.iata_code.to_owned(),
                 coordinates: to_coords,
+                metro_code: to_stop.metro_code.clone(),
             },
             distance,
         })
@@ -123,6 +135,8 @@ mod tests {
     use actix_web::http::StatusCode;
     use sqlx::SqlitePool;
 
+    use crate::auth::AuthenticateAs;
+
     use super::*;
     use crate::{
         models::{Datum, Formula},
@@ -151,7 +165,8 @@ mod tests {
         let app = actix_web::test::init_service(
             actix_web::App::new()
                 .app_data(app_state().await)
-                .service(airports_handler),
+                .service(airports_handler)
+                .wrap(AuthenticateAs::admin()),
         )
         .await;
 
@@ -176,7 +191,8 @@ mod tests {
         let app = actix_web::test::init_service(
             actix_web::App::new()
                 .app_data(app_state().await)
-                .service(airports_handler),
+                .service(airports_handler)
+                .wrap(AuthenticateAs::admin()),
         )
         .await;
 
@@ -200,7 +216,8 @@ mod tests {
         let app = actix_web::test::init_service(
             actix_web::App::new()
                 .app_data(app_state().await)
-                .service(airports_handler),
+                .service(airports_handler)
+                .wrap(AuthenticateAs::admin()),
         )
         .await;
 
@@ -218,4 +235,36 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
     }
+
+    #[actix_web::test]
+    async fn test_airports_handler_resolves_metro_codes() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(app_state().await)
+                .service(airports_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
+
+        let request = AirportDistanceRequest {
+            route: vec!["LON".to_owned(), "NYC".to_owned()],
+            formula: Formula::Haversine,
+            datum: Datum::WGS84,
+        };
+
+        let request = actix_web::test::TestRequest::post()
+            .uri("/calculate_distance/airports")
+            .set_json(&request);
+
+        let response = actix_web::test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response: AirportDistanceResponse = actix_web::test::read_body_json(response).await;
+        let leg = &response.distances[0];
+
+        assert_eq!(leg.from.metro_code.as_deref(), Some("LON"));
+        assert!(["LHR", "LGW", "STN", "LTN", "LCY", "SEN"].contains(&leg.from.iata_code.as_str()));
+        assert_eq!(leg.to.metro_code.as_deref(), Some("NYC"));
+        assert!(["JFK", "LGA", "EWR"].contains(&leg.to.iata_code.as_str()));
+    }
 }
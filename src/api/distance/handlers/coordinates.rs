@@ -1,6 +1,7 @@
 #![allow(non_camel_case_types)]
 
 use actix_web::error::{ErrorBadRequest, ErrorUnprocessableEntity};
+use actix_web::HttpRequest;
 use itertools::Itertools;
 use paperclip::actix::web::Json;
 use paperclip::actix::{api_v2_operation, post};
@@ -10,11 +11,16 @@ use tracing::log;
 use crate::api::distance::schemas::{
     CoordinatesDistanceRequest, CoordinatesDistanceResponse, CoordinatesRoutePart,
 };
+use crate::auth::{DistanceScope, RequireScope};
 use crate::services::distance::DistanceCalculatorFactory;
+use crate::usage::record_usage;
 
 #[api_v2_operation]
 #[post("/calculate_distance/coordinates")]
+#[tracing::instrument(skip_all)]
 pub async fn coordinates_handler(
+    _scope: RequireScope<DistanceScope>,
+    http_request: HttpRequest,
     request: Json<CoordinatesDistanceRequest>,
 ) -> Result<Json<CoordinatesDistanceResponse>, actix_web::Error> {
     let request = request.into_inner();
@@ -46,6 +52,8 @@ pub async fn coordinates_handler(
         }
     }
 
+    record_usage(&http_request, distances.len(), 0);
+
     Ok(Json(CoordinatesDistanceResponse {
         distances,
         total_distance,
@@ -60,11 +68,18 @@ mod tests {
 
     use crate::models::{Coordinates, Datum, Formula};
 
+    use crate::auth::AuthenticateAs;
+
     use super::*;
 
     #[actix_web::test]
     async fn test_distance_handler_full_request() {
-        let mut app = test::init_service(App::new().service(coordinates_handler)).await;
+        let app = test::init_service(
+            App::new()
+                .service(coordinates_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
 
         let req = test::TestRequest::post()
             .uri("/calculate_distance/coordinates")
@@ -80,7 +95,7 @@ mod tests {
             })
             .to_request();
 
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert_eq!(resp.status(), http::StatusCode::OK);
 
@@ -95,7 +110,12 @@ mod tests {
 
     #[actix_web::test]
     async fn test_distance_handler_defaults() {
-        let mut app = test::init_service(App::new().service(coordinates_handler)).await;
+        let app = test::init_service(
+            App::new()
+                .service(coordinates_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
 
         let req = test::TestRequest::post()
             .uri("/calculate_distance/coordinates")
@@ -116,7 +136,7 @@ mod tests {
             .insert_header(("Content-Type", "application/json"))
             .to_request();
 
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert_eq!(resp.status(), http::StatusCode::OK);
 
@@ -128,7 +148,12 @@ mod tests {
 
     #[actix_web::test]
     async fn test_distance_handler_incorrect_request_body() {
-        let mut app = test::init_service(App::new().service(coordinates_handler)).await;
+        let app = test::init_service(
+            App::new()
+                .service(coordinates_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
 
         let req = test::TestRequest::post()
             .uri("/calculate_distance/coordinates")
@@ -145,14 +170,19 @@ mod tests {
             .insert_header(("Content-Type", "application/json"))
             .to_request();
 
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
     }
 
     #[actix_web::test]
     async fn test_distance_handler_no_content_type() {
-        let mut app = test::init_service(App::new().service(coordinates_handler)).await;
+        let app = test::init_service(
+            App::new()
+                .service(coordinates_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
 
         let req = test::TestRequest::post()
             .uri("/calculate_distance/coordinates")
@@ -168,14 +198,19 @@ mod tests {
             )
             .to_request();
 
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
     }
 
     #[actix_web::test]
     async fn test_distance_handler_vincenty() {
-        let mut app = test::init_service(App::new().service(coordinates_handler)).await;
+        let app = test::init_service(
+            App::new()
+                .service(coordinates_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
 
         let req = test::TestRequest::post()
             .uri("/calculate_distance/coordinates")
@@ -205,7 +240,7 @@ mod tests {
             .insert_header(("Content-Type", "application/json"))
             .to_request();
 
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert_eq!(resp.status(), http::StatusCode::OK);
 
@@ -219,7 +254,12 @@ mod tests {
 
     #[actix_web::test]
     async fn test_handles_distance_calculation_error() {
-        let mut app = test::init_service(App::new().service(coordinates_handler)).await;
+        let app = test::init_service(
+            App::new()
+                .service(coordinates_handler)
+                .wrap(AuthenticateAs::admin()),
+        )
+        .await;
 
         let req = test::TestRequest::post()
             .uri("/calculate_distance/coordinates")
@@ -242,7 +282,7 @@ mod tests {
             .insert_header(("Content-Type", "application/json"))
             .to_request();
 
-        let resp = test::call_service(&mut app, req).await;
+        let resp = test::call_service(&app, req).await;
 
         assert_eq!(resp.status(), http::StatusCode::UNPROCESSABLE_ENTITY);
     }
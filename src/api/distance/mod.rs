@@ -1,2 +1,2 @@
 pub mod handlers;
-pub(self) mod schemas;
+mod schemas;
@@ -18,6 +18,9 @@ pub struct AirportDistanceRequest {
 pub struct AirportCoordinates {
     pub iata_code: String,
     pub coordinates: Coordinates,
+    /// Metropolitan area code requested in the route which resolved to this airport.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metro_code: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
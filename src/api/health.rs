@@ -1,10 +1,16 @@
-use std::time::UNIX_EPOCH;
+use std::future::Future;
+use std::time::{Instant, UNIX_EPOCH};
 
+use actix_web::http::StatusCode;
 use actix_web::{get, web::Data, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 use tracing::log::error;
 
+use crate::auth::ApiKeyStore;
+use crate::models::{BuildInfo, DatasetMetadata};
+use crate::services::airports::AirportsOverlayStore;
 use crate::services::app_state::AppState;
+use crate::usage::UsageTracker;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct HealthResponse {
@@ -12,10 +18,192 @@ pub struct HealthResponse {
     pub timestamp: u64,
     pub hostname: String,
     pub message: String,
+    #[serde(default)]
+    pub dataset: Option<DatasetMetadata>,
+    /// Dependencies checked by readiness probes, empty for liveness probes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<DependencyCheck>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyStatus {
+    Up,
+    Down,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DependencyCheck {
+    pub name: String,
+    pub status: DependencyStatus,
+    pub latency_ms: f64,
+    /// Version of the dataset served from the dependency, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DependencyCheck {
+    /// Runs `ping`, timing how long the dependency takes to answer.
+    async fn run(name: &str, ping: impl Future<Output = Result<(), sqlx::Error>>) -> Self {
+        let started_at = Instant::now();
+        let result = ping.await;
+        let latency_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+
+        match result {
+            Ok(()) => Self {
+                name: name.to_owned(),
+                status: DependencyStatus::Up,
+                latency_ms,
+                version: None,
+                error: None,
+            },
+            Err(e) => {
+                error!("Health check of {name} failed: {e}");
+                Self {
+                    name: name.to_owned(),
+                    status: DependencyStatus::Down,
+                    latency_ms,
+                    version: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Build of the running service and how long it has been running.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InfoResponse {
+    pub build: BuildInfo,
+    pub hostname: String,
+    /// Unix timestamp of the service start.
+    pub started_at: u64,
+    pub uptime_seconds: u64,
+    pub workers: usize,
+}
+
+/// Liveness probe, checking only the process itself, so that it isn't restarted when a
+/// database is unavailable.
+#[get("/health/live")]
+pub async fn check_liveness(app_state: Data<AppState>) -> impl Responder {
+    let (timestamp, hostname, errors) = check_process(&app_state);
+
+    health_response(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        HealthResponse {
+            healthy: errors.is_empty(),
+            timestamp,
+            hostname,
+            message: message(&errors),
+            dataset: None,
+            checks: vec![],
+        },
+    )
+}
+
+#[get("/health/info")]
+pub async fn build_info(app_state: Data<AppState>) -> impl Responder {
+    let (_, hostname, _) = check_process(&app_state);
+    let uptime = app_state
+        .time_provider
+        .now()
+        .duration_since(app_state.started_at)
+        .unwrap_or_default();
+    let started_at = app_state
+        .started_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    HttpResponse::Ok().json(InfoResponse {
+        build: BuildInfo::current(),
+        hostname,
+        started_at: started_at.as_secs(),
+        uptime_seconds: uptime.as_secs(),
+        workers: app_state.workers,
+    })
+}
+
+/// Readiness probe, checking the process and every database requests depend on.
+#[get("/health/ready")]
+pub async fn check_readiness(
+    app_state: Data<AppState>,
+    overlay_store: Option<Data<AirportsOverlayStore>>,
+    api_key_store: Option<Data<ApiKeyStore>>,
+    usage_tracker: Option<Data<UsageTracker>>,
+) -> impl Responder {
+    readiness(app_state, overlay_store, api_key_store, usage_tracker).await
+}
+
+/// Same as `/health/ready`, kept for probes configured before it was split.
 #[get("/health")]
-pub async fn check_health(app_state: Data<AppState>) -> impl Responder {
+pub async fn check_health(
+    app_state: Data<AppState>,
+    overlay_store: Option<Data<AirportsOverlayStore>>,
+    api_key_store: Option<Data<ApiKeyStore>>,
+    usage_tracker: Option<Data<UsageTracker>>,
+) -> impl Responder {
+    readiness(app_state, overlay_store, api_key_store, usage_tracker).await
+}
+
+async fn readiness(
+    app_state: Data<AppState>,
+    overlay_store: Option<Data<AirportsOverlayStore>>,
+    api_key_store: Option<Data<ApiKeyStore>>,
+    usage_tracker: Option<Data<UsageTracker>>,
+) -> HttpResponse {
+    let (timestamp, hostname, mut errors) = check_process(&app_state);
+    if app_state.is_shutting_down() {
+        errors.push("shutting down".to_owned());
+    }
+
+    let repository = app_state.airports_repository.snapshot();
+    let mut airports_check = DependencyCheck::run("airports_database", repository.ping()).await;
+
+    // dataset metadata is informational only, so failing to fetch it doesn't make the
+    // service unready
+    let dataset = match repository.dataset_metadata().await {
+        Ok(dataset) => dataset,
+        Err(e) => {
+            error!("Failed to get dataset metadata: {}", e);
+            None
+        }
+    };
+    airports_check.version = dataset.as_ref().map(|dataset| dataset.version.clone());
+
+    let mut checks = vec![airports_check];
+    if let Some(overlay_store) = &overlay_store {
+        checks.push(DependencyCheck::run("overlay_database", overlay_store.ping()).await);
+    }
+    if let Some(api_key_store) = &api_key_store {
+        checks.push(DependencyCheck::run("api_keys_database", api_key_store.ping()).await);
+    }
+    if let Some(usage_tracker) = &usage_tracker {
+        checks.push(DependencyCheck::run("usage_database", usage_tracker.ping()).await);
+    }
+
+    for check in &checks {
+        if let Some(error) = &check.error {
+            errors.push(format!("{}: {error}", check.name));
+        }
+    }
+
+    health_response(
+        StatusCode::SERVICE_UNAVAILABLE,
+        HealthResponse {
+            healthy: errors.is_empty(),
+            timestamp,
+            hostname,
+            message: message(&errors),
+            dataset,
+            checks,
+        },
+    )
+}
+
+/// Current timestamp and hostname, together with errors of getting them.
+fn check_process(app_state: &AppState) -> (u64, String, Vec<String>) {
     // we're going to store our error messages in a vector to provide better `unhealthy` message
     let mut errors = vec![];
 
@@ -39,19 +227,20 @@ pub async fn check_health(app_state: Data<AppState>) -> impl Responder {
         }
     };
 
+    (timestamp, hostname, errors)
+}
+
+fn message(errors: &[String]) -> String {
     match errors.len() {
-        0 => HttpResponse::Ok().json(HealthResponse {
-            healthy: true,
-            timestamp,
-            hostname,
-            message: "ok".to_string(),
-        }),
-        _ => HttpResponse::InternalServerError().json(HealthResponse {
-            healthy: false,
-            timestamp,
-            hostname,
-            message: errors.join(";"),
-        }),
+        0 => "ok".to_string(),
+        _ => errors.join(";"),
+    }
+}
+
+fn health_response(unhealthy_status: StatusCode, response: HealthResponse) -> HttpResponse {
+    match response.healthy {
+        true => HttpResponse::Ok().json(response),
+        false => HttpResponse::build(unhealthy_status).json(response),
     }
 }
 
@@ -69,19 +258,22 @@ mod tests {
     use actix_web::{test, App};
 
     async fn test_health_check(
+        uri: &str,
         app_state: AppState,
         expected_response: HealthResponse,
         expected_status_code: u16,
-    ) {
-        let mut app = test::init_service(
+    ) -> HealthResponse {
+        let app = test::init_service(
             App::new()
                 .app_data(Data::new(app_state))
+                .service(check_liveness)
+                .service(check_readiness)
                 .service(check_health),
         )
         .await;
 
-        let req = test::TestRequest::get().uri("/health").to_request();
-        let resp = test::call_service(&mut app, req).await;
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
 
         assert_eq!(resp.status(), expected_status_code);
 
@@ -90,27 +282,47 @@ mod tests {
         assert_eq!(resp.message, expected_response.message);
         assert_eq!(resp.timestamp, expected_response.timestamp);
         assert_eq!(resp.hostname, expected_response.hostname);
+        assert_eq!(resp.dataset, expected_response.dataset);
+
+        resp
+    }
+
+    fn successful_app_state(airports_repository: DummyAirportsRepository) -> AppState {
+        AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test-hostname".into())),
+            Box::new(MockTimeProvider::new(
+                UNIX_EPOCH + Duration::from_secs(123456789),
+            )),
+            Box::new(airports_repository),
+        )
     }
 
     #[actix_web::test]
     async fn test_successful_health_check() {
-        let mock_time_provider = MockTimeProvider::new(UNIX_EPOCH + Duration::from_secs(123456789));
-        let mock_hostname_provider = MockSuccessfulHostnameProvider::new("test-hostname".into());
-        let app_state = AppState::new(
-            Box::new(mock_hostname_provider),
-            Box::new(mock_time_provider),
-            Box::new(DummyAirportsRepository::new(vec![])),
-        );
+        for uri in ["/health/live", "/health/ready", "/health"] {
+            let app_state = successful_app_state(DummyAirportsRepository::new(vec![]));
 
-        let expected = HealthResponse {
-            healthy: true,
-            timestamp: 123456789,
-            hostname: "test-hostname".to_string(),
-            message: "ok".to_string(),
-        };
-        let expected_status_code = 200;
+            let expected = HealthResponse {
+                healthy: true,
+                timestamp: 123456789,
+                hostname: "test-hostname".to_string(),
+                message: "ok".to_string(),
+                dataset: None,
+                checks: vec![],
+            };
+            let expected_status_code = 200;
 
-        test_health_check(app_state, expected, expected_status_code).await;
+            let resp = test_health_check(uri, app_state, expected, expected_status_code).await;
+            match uri {
+                "/health/live" => assert!(resp.checks.is_empty()),
+                _ => {
+                    assert_eq!(resp.checks.len(), 1);
+                    assert_eq!(resp.checks[0].name, "airports_database");
+                    assert_eq!(resp.checks[0].status, DependencyStatus::Up);
+                    assert_eq!(resp.checks[0].error, None);
+                }
+            }
+        }
     }
 
     #[actix_web::test]
@@ -128,9 +340,11 @@ mod tests {
             timestamp: 0,
             hostname: "test-hostname".to_string(),
             message: "second time provided was later than self".to_string(),
+            dataset: None,
+            checks: vec![],
         };
 
-        test_health_check(app_state, expected, 500).await;
+        test_health_check("/health/live", app_state, expected, 500).await;
     }
 
     #[actix_web::test]
@@ -148,10 +362,12 @@ mod tests {
             timestamp: 123456789,
             hostname: "unknown".to_string(),
             message: "mock error".to_string(),
+            dataset: None,
+            checks: vec![],
         };
         let expected_status_code = 500;
 
-        test_health_check(app_state, expected, expected_status_code).await;
+        test_health_check("/health/live", app_state, expected, expected_status_code).await;
     }
 
     #[actix_web::test]
@@ -169,9 +385,141 @@ mod tests {
             timestamp: 0,
             hostname: "unknown".to_string(),
             message: "second time provided was later than self;mock error".into(),
+            dataset: None,
+            checks: vec![],
         };
         let expected_status_code = 500;
 
-        test_health_check(app_state, expected, expected_status_code).await;
+        test_health_check("/health/live", app_state, expected, expected_status_code).await;
+    }
+
+    #[actix_web::test]
+    async fn test_health_check_includes_dataset_metadata() {
+        let dataset = DatasetMetadata {
+            source: "partow".to_string(),
+            version: "0.0.2 - 20170321".to_string(),
+            imported_at: 1683000000,
+            rows_read: 9300,
+            airports_count: 4174,
+            rejected_count: 5126,
+            checksum: "01bdb91f".to_string(),
+        };
+        let app_state = successful_app_state(
+            DummyAirportsRepository::new(vec![]).with_dataset_metadata(dataset.clone()),
+        );
+
+        let expected = HealthResponse {
+            healthy: true,
+            timestamp: 123456789,
+            hostname: "test-hostname".to_string(),
+            message: "ok".to_string(),
+            dataset: Some(dataset),
+            checks: vec![],
+        };
+
+        let resp = test_health_check("/health/ready", app_state, expected, 200).await;
+        assert_eq!(resp.checks[0].version.as_deref(), Some("0.0.2 - 20170321"));
+    }
+
+    #[actix_web::test]
+    async fn test_readiness_fails_without_airports_database() {
+        let expected = HealthResponse {
+            healthy: false,
+            timestamp: 123456789,
+            hostname: "test-hostname".to_string(),
+            message: "airports_database: encountered unexpected or invalid data: \
+                      no such table: airports"
+                .to_string(),
+            dataset: None,
+            checks: vec![],
+        };
+
+        let app_state = successful_app_state(
+            DummyAirportsRepository::new(vec![]).with_ping_error("no such table: airports"),
+        );
+        let resp = test_health_check("/health/ready", app_state, expected, 503).await;
+        assert_eq!(resp.checks[0].status, DependencyStatus::Down);
+
+        // the process is still alive, so it mustn't be restarted
+        let app_state = successful_app_state(
+            DummyAirportsRepository::new(vec![]).with_ping_error("no such table: airports"),
+        );
+        let expected = HealthResponse {
+            healthy: true,
+            timestamp: 123456789,
+            hostname: "test-hostname".to_string(),
+            message: "ok".to_string(),
+            dataset: None,
+            checks: vec![],
+        };
+        test_health_check("/health/live", app_state, expected, 200).await;
+    }
+
+    #[actix_web::test]
+    async fn test_readiness_fails_while_shutting_down() {
+        let app_state = successful_app_state(DummyAirportsRepository::new(vec![]));
+        app_state.begin_shutdown();
+
+        let expected = HealthResponse {
+            healthy: false,
+            timestamp: 123456789,
+            hostname: "test-hostname".to_string(),
+            message: "shutting down".to_string(),
+            dataset: None,
+            checks: vec![],
+        };
+        test_health_check("/health/ready", app_state, expected, 503).await;
+    }
+
+    #[actix_web::test]
+    async fn test_readiness_checks_configured_databases() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(successful_app_state(
+                    DummyAirportsRepository::new(vec![]),
+                )))
+                .app_data(Data::new(
+                    ApiKeyStore::open("sqlite::memory:").await.unwrap(),
+                ))
+                .service(check_readiness),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/health/ready").to_request();
+        let resp: HealthResponse = test::call_and_read_body_json(&app, req).await;
+        assert!(resp.healthy);
+        assert_eq!(
+            resp.checks
+                .iter()
+                .map(|check| (check.name.as_str(), check.status))
+                .collect::<Vec<_>>(),
+            [
+                ("airports_database", DependencyStatus::Up),
+                ("api_keys_database", DependencyStatus::Up),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_build_info() {
+        let mut app_state =
+            successful_app_state(DummyAirportsRepository::new(vec![])).with_workers(3);
+        app_state.started_at = UNIX_EPOCH + Duration::from_secs(123456789 - 90);
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(app_state))
+                .service(build_info),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/health/info").to_request();
+        let resp: InfoResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp.build, BuildInfo::current());
+        assert_eq!(resp.build.version, env!("CARGO_PKG_VERSION"));
+        assert!(resp.build.rustc_version.starts_with("rustc "));
+        assert_eq!(resp.hostname, "test-hostname");
+        assert_eq!(resp.started_at, 123456789 - 90);
+        assert_eq!(resp.uptime_seconds, 90);
+        assert_eq!(resp.workers, 3);
     }
 }
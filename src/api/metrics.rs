@@ -0,0 +1,22 @@
+use actix_web::http::header::ContentType;
+use actix_web::{get, web::Data, HttpResponse};
+use tracing::log::error;
+
+use crate::metrics::metrics;
+use crate::services::app_state::AppState;
+
+/// Metrics in the Prometheus text format, left out of the API docs like `/health`.
+#[get("/metrics")]
+pub async fn metrics_handler(app_state: Data<AppState>) -> HttpResponse {
+    let repository = app_state.airports_repository.snapshot();
+
+    match metrics().render(repository.as_ref()) {
+        Ok(rendered) => HttpResponse::Ok()
+            .content_type(ContentType(prometheus::TEXT_FORMAT.parse().unwrap()))
+            .body(rendered),
+        Err(e) => {
+            error!("Failed to render metrics: {e}");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
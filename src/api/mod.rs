@@ -1,3 +1,6 @@
+pub(crate) mod admin;
 pub(crate) mod airports;
 pub(crate) mod distance;
 pub(crate) mod health;
+pub(crate) mod metrics;
+pub(crate) mod usage;
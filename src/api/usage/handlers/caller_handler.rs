@@ -0,0 +1,165 @@
+#![allow(non_camel_case_types)]
+
+use std::time::UNIX_EPOCH;
+
+use actix_web::error::{ErrorInternalServerError, ErrorNotFound, ErrorUnauthorized};
+use actix_web::web::Data;
+use actix_web::HttpRequest;
+use paperclip::actix::web::Json;
+use paperclip::actix::{api_v2_operation, get};
+use serde_json::json;
+use tracing::log;
+
+use crate::api::usage::schemas::UsageResponse;
+use crate::auth::request_principal;
+use crate::models::UsageCounts;
+use crate::services::app_state::AppState;
+use crate::usage::{UsagePeriod, UsageTracker};
+
+#[api_v2_operation]
+#[get("/usage")]
+#[tracing::instrument(skip_all)]
+pub async fn caller_usage_handler(
+    http_request: HttpRequest,
+    app_state: Data<AppState>,
+    usage_tracker: Option<Data<UsageTracker>>,
+) -> Result<Json<UsageResponse>, actix_web::Error> {
+    let usage_tracker = usage_tracker
+        .ok_or_else(|| ErrorNotFound(json!({"error": "Usage tracking is not configured"})))?;
+    let client = request_principal(&http_request)
+        .map(|principal| principal.client_id())
+        .ok_or_else(|| ErrorUnauthorized(json!({"error": "Unknown client"})))?;
+
+    let now = app_state
+        .time_provider
+        .now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let period = UsagePeriod::at(now);
+
+    let days = match usage_tracker.month_usage(&client, &period).await {
+        Ok(days) => days,
+        Err(e) => {
+            log::error!("Failed to read usage of {client}: {e}");
+            return Err(ErrorInternalServerError(json!({"error": "Database fail"})));
+        }
+    };
+
+    let mut today = UsageCounts::default();
+    let mut this_month = UsageCounts::default();
+    for daily in &days {
+        if daily.day == period.day {
+            today += daily.usage;
+        }
+        this_month += daily.usage;
+    }
+
+    Ok(Json(UsageResponse {
+        quota: usage_tracker.quota(&client),
+        client,
+        day: period.day,
+        month: period.month,
+        today,
+        this_month,
+        days,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::{http, test, App};
+    use actix_web_httpauth::headers::authorization::{Authorization, Basic};
+    use actix_web_httpauth::middleware::HttpAuthentication;
+
+    use crate::auth::{api_auth_validator, ApiCredentials};
+    use crate::models::DailyUsage;
+    use crate::services::{
+        airports::DummyAirportsRepository,
+        healthcheck::{
+            hostname_provider::MockSuccessfulHostnameProvider, time_provider::MockTimeProvider,
+        },
+    };
+    use crate::usage::{QuotaConfig, UsageStore};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_returns_usage_of_the_caller() {
+        let app_state = Data::new(AppState::new(
+            Box::new(MockSuccessfulHostnameProvider::new("test".into())),
+            // 2023-05-02T04:00:00Z
+            Box::new(MockTimeProvider::new(
+                UNIX_EPOCH + Duration::from_secs(1683000000),
+            )),
+            Box::new(DummyAirportsRepository::new(vec![])),
+        ));
+        let usage_tracker = UsageTracker::new(
+            UsageStore::open("sqlite::memory:").await.unwrap(),
+            QuotaConfig::default(),
+        );
+        let usage = UsageCounts {
+            requests: 1,
+            legs: 2,
+            airport_lookups: 3,
+        };
+        for (client, timestamp) in [
+            ("user:alice", 1682900000),
+            ("user:alice", 1683000000),
+            ("user:alice", 1683000001),
+            ("user:bob", 1683000000),
+        ] {
+            usage_tracker
+                .record(client, timestamp, &usage)
+                .await
+                .unwrap();
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state)
+                .app_data(Data::new(usage_tracker))
+                .app_data(Data::new(ApiCredentials::new("alice", "secret")))
+                .service(caller_usage_handler)
+                .wrap(HttpAuthentication::with_fn(api_auth_validator)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/usage")
+            .insert_header(Authorization::<Basic>::from(Basic::new(
+                "alice",
+                Some("secret"),
+            )))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body: UsageResponse = test::read_body_json(resp).await;
+        assert_eq!(body.client, "user:alice");
+        assert_eq!(body.day, "2023-05-02");
+        assert_eq!(body.month, "2023-05");
+        assert_eq!(body.today.requests, 2);
+        assert_eq!(
+            body.this_month,
+            UsageCounts {
+                requests: 3,
+                legs: 6,
+                airport_lookups: 9,
+            }
+        );
+        assert_eq!(
+            body.days
+                .iter()
+                .map(|daily| daily.day.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["2023-05-01", "2023-05-02"]
+        );
+        assert_eq!(body.quota, None);
+        assert!(body.days.contains(&DailyUsage {
+            day: "2023-05-01".to_owned(),
+            usage,
+        }));
+    }
+}
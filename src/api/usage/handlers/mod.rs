@@ -0,0 +1,3 @@
+mod caller_handler;
+
+pub(crate) use caller_handler::caller_usage_handler;
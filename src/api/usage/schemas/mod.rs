@@ -0,0 +1,3 @@
+mod usage;
+
+pub use self::usage::UsageResponse;
@@ -0,0 +1,21 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{DailyUsage, UsageCounts};
+use crate::usage::Quota;
+
+#[derive(Debug, Deserialize, Serialize, Apiv2Schema)]
+pub struct UsageResponse {
+    /// Client the usage is counted for, the user name, `api-key:<id>` or `jwt:<subject>`.
+    pub client: String,
+    /// Current UTC day in the `YYYY-MM-DD` format.
+    pub day: String,
+    /// Current month in the `YYYY-MM` format.
+    pub month: String,
+    pub today: UsageCounts,
+    pub this_month: UsageCounts,
+    /// Usage on the days of the current month, days without requests are left out.
+    pub days: Vec<DailyUsage>,
+    /// Quotas of the client, `None` for clients whose usage isn't limited.
+    pub quota: Option<Quota>,
+}
@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use std::future::{ready, Ready};
+use std::marker::PhantomData;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::{ErrorForbidden, ErrorUnauthorized};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use futures::future::LocalBoxFuture;
+use paperclip::actix::OperationModifier;
+use paperclip::v2::models::{DefaultOperationRaw, DefaultSchemaRaw, SecurityScheme};
+use paperclip::v2::schema::Apiv2Schema;
+use serde_json::json;
+use tracing::log;
+
+use super::principal::{Principal, ADMIN_SCOPE, AIRPORTS_SCOPE, API_KEY_SCOPES, DISTANCE_SCOPE};
+use super::API_KEY_HEADER;
+
+/// Names of the security definitions of the OpenAPI spec.
+const BASIC_SECURITY: &str = "basic";
+const API_KEY_SECURITY: &str = "apiKey";
+const JWT_SECURITY: &str = "jwt";
+
+/// Scope a handler requires, see `RequireScope`.
+pub(crate) trait ApiScope {
+    const SCOPE: &'static str;
+    const DESCRIPTION: &'static str;
+}
+
+pub(crate) struct DistanceScope;
+
+impl ApiScope for DistanceScope {
+    const SCOPE: &'static str = DISTANCE_SCOPE;
+    const DESCRIPTION: &'static str = "Calculate distances";
+}
+
+pub(crate) struct AirportsScope;
+
+impl ApiScope for AirportsScope {
+    const SCOPE: &'static str = AIRPORTS_SCOPE;
+    const DESCRIPTION: &'static str = "Read airports";
+}
+
+pub(crate) struct AdminScope;
+
+impl ApiScope for AdminScope {
+    const SCOPE: &'static str = ADMIN_SCOPE;
+    const DESCRIPTION: &'static str = "Administer the service";
+}
+
+/// Extractor rejecting requests of principals without the scope `S` with `403 Forbidden`.
+/// Taken by handlers, so that the scope is also documented in the OpenAPI spec.
+pub(crate) struct RequireScope<S> {
+    principal: Principal,
+    scope: PhantomData<S>,
+}
+
+impl<S> RequireScope<S> {
+    pub fn principal(&self) -> &Principal {
+        &self.principal
+    }
+}
+
+impl<S: ApiScope> FromRequest for RequireScope<S> {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(principal) = request.extensions().get::<Principal>().cloned() else {
+            return ready(Err(ErrorUnauthorized(
+                json!({"error": "Not authenticated"}),
+            )));
+        };
+
+        if !principal.has_scope(S::SCOPE) {
+            log::warn!(
+                "{principal} lacks the {} scope for {}",
+                S::SCOPE,
+                request.path()
+            );
+            return ready(Err(ErrorForbidden(json!({
+                "error": "Missing scope",
+                "details": {"scope": S::SCOPE}
+            }))));
+        }
+
+        ready(Ok(RequireScope {
+            principal,
+            scope: PhantomData,
+        }))
+    }
+}
+
+impl<S: ApiScope> Apiv2Schema for RequireScope<S> {}
+
+impl<S: ApiScope> OperationModifier for RequireScope<S> {
+    fn update_parameter(_op: &mut DefaultOperationRaw) {}
+
+    fn update_definitions(_map: &mut BTreeMap<String, DefaultSchemaRaw>) {}
+
+    /// Any of the ways of authenticating is enough, JWTs have to carry the scope.
+    fn update_security(op: &mut DefaultOperationRaw) {
+        op.security
+            .push(BTreeMap::from([(BASIC_SECURITY.to_owned(), vec![])]));
+        if API_KEY_SCOPES.contains(&S::SCOPE) {
+            op.security
+                .push(BTreeMap::from([(API_KEY_SECURITY.to_owned(), vec![])]));
+        }
+        op.security.push(BTreeMap::from([(
+            JWT_SECURITY.to_owned(),
+            vec![S::SCOPE.to_owned()],
+        )]));
+    }
+
+    fn update_security_definitions(map: &mut BTreeMap<String, SecurityScheme>) {
+        let scheme = |type_: &str, description: &str| SecurityScheme {
+            name: None,
+            type_: type_.to_owned(),
+            in_: None,
+            flow: None,
+            auth_url: None,
+            token_url: None,
+            scopes: BTreeMap::new(),
+            description: Some(description.to_owned()),
+        };
+
+        scheme("basic", "User of the credentials file").update_definitions(BASIC_SECURITY, map);
+        SecurityScheme {
+            name: Some(API_KEY_HEADER.to_owned()),
+            in_: Some("header".to_owned()),
+            ..scheme(
+                "apiKey",
+                "API key, also accepted as `Authorization: Bearer <key>`",
+            )
+        }
+        .update_definitions(API_KEY_SECURITY, map);
+        SecurityScheme {
+            flow: Some("application".to_owned()),
+            scopes: BTreeMap::from([(S::SCOPE.to_owned(), S::DESCRIPTION.to_owned())]),
+            ..scheme(
+                "oauth2",
+                "JWT issued by the identity provider, sent as `Authorization: Bearer <token>`",
+            )
+        }
+        .update_definitions(JWT_SECURITY, map);
+    }
+}
+
+/// Middleware rejecting requests of principals without the role with `403 Forbidden`. Has to
+/// be wrapped by the authentication middleware.
+pub(crate) struct RequireRole {
+    role: &'static str,
+}
+
+impl RequireRole {
+    pub fn new(role: &'static str) -> Self {
+        Self { role }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireRole
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireRoleMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireRoleMiddleware {
+            service,
+            role: self.role,
+        }))
+    }
+}
+
+pub(crate) struct RequireRoleMiddleware<S> {
+    service: S,
+    role: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireRoleMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let rejection = match request.extensions().get::<Principal>() {
+            Some(principal) if principal.has_role(self.role) => None,
+            Some(principal) => {
+                log::warn!(
+                    "{principal} lacks the {} role for {}",
+                    self.role,
+                    request.path()
+                );
+                Some(HttpResponse::Forbidden().json(json!({
+                    "error": "Missing role",
+                    "details": {"role": self.role}
+                })))
+            }
+            None => Some(HttpResponse::Unauthorized().json(json!({"error": "Not authenticated"}))),
+        };
+
+        if let Some(response) = rejection {
+            return Box::pin(ready(Ok(request
+                .into_response(response)
+                .map_into_right_body())));
+        }
+
+        let response = self.service.call(request);
+        Box::pin(async move { Ok(response.await?.map_into_left_body()) })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::{get, web, App};
+
+    use crate::auth::principal::ADMIN_ROLE;
+
+    use super::*;
+
+    /// Middleware authenticating every request as the given principal, for testing handlers
+    /// without credentials.
+    pub(crate) struct AuthenticateAs(pub Principal);
+
+    impl AuthenticateAs {
+        /// User with the admin role.
+        pub fn admin() -> Self {
+            Self(Principal::user("test", vec![ADMIN_ROLE.to_owned()]))
+        }
+    }
+
+    impl<S, B> Transform<S, ServiceRequest> for AuthenticateAs
+    where
+        S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+        B: 'static,
+    {
+        type Response = ServiceResponse<B>;
+        type Error = Error;
+        type Transform = AuthenticateAsMiddleware<S>;
+        type InitError = ();
+        type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+        fn new_transform(&self, service: S) -> Self::Future {
+            ready(Ok(AuthenticateAsMiddleware {
+                service,
+                principal: self.0.clone(),
+            }))
+        }
+    }
+
+    pub(crate) struct AuthenticateAsMiddleware<S> {
+        service: S,
+        principal: Principal,
+    }
+
+    impl<S, B> Service<ServiceRequest> for AuthenticateAsMiddleware<S>
+    where
+        S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+        B: 'static,
+    {
+        type Response = ServiceResponse<B>;
+        type Error = Error;
+        type Future = S::Future;
+
+        forward_ready!(service);
+
+        fn call(&self, request: ServiceRequest) -> Self::Future {
+            request.extensions_mut().insert(self.principal.clone());
+            self.service.call(request)
+        }
+    }
+
+    #[get("/distance")]
+    async fn distance_handler(scope: RequireScope<DistanceScope>) -> String {
+        scope.principal().client_id()
+    }
+
+    #[get("/settings")]
+    async fn admin_handler(_scope: RequireScope<AdminScope>) -> &'static str {
+        "admin"
+    }
+
+    #[actix_web::test]
+    async fn test_requires_scopes_and_roles() {
+        let user = Principal::user("alice", vec![]);
+        let admin = Principal::user("bob", vec![ADMIN_ROLE.to_owned()]);
+        let token = Principal::token(
+            "service",
+            vec![ADMIN_ROLE.to_owned()],
+            vec![DISTANCE_SCOPE.to_owned()],
+        );
+
+        for (principal, expected) in [
+            (&user, [StatusCode::OK, StatusCode::FORBIDDEN]),
+            (&admin, [StatusCode::OK, StatusCode::OK]),
+            // the admin role doesn't grant scopes missing in a token
+            (&token, [StatusCode::OK, StatusCode::FORBIDDEN]),
+            (
+                &Principal::api_key(1, "importer"),
+                [StatusCode::OK, StatusCode::FORBIDDEN],
+            ),
+        ] {
+            let app = actix_web::test::init_service(
+                App::new()
+                    .service(distance_handler)
+                    .service(
+                        web::scope("/admin")
+                            .wrap(RequireRole::new(ADMIN_ROLE))
+                            .service(admin_handler),
+                    )
+                    .wrap(AuthenticateAs(principal.clone())),
+            )
+            .await;
+
+            let mut statuses = vec![];
+            for uri in ["/distance", "/admin/settings"] {
+                let req = actix_web::test::TestRequest::get().uri(uri).to_request();
+                statuses.push(actix_web::test::call_service(&app, req).await.status());
+            }
+
+            assert_eq!(statuses, expected, "{principal}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_rejects_unauthenticated_requests() {
+        let app = actix_web::test::init_service(
+            App::new().service(distance_handler).service(
+                web::scope("/admin")
+                    .wrap(RequireRole::new(ADMIN_ROLE))
+                    .service(admin_handler),
+            ),
+        )
+        .await;
+
+        for uri in ["/distance", "/admin/settings"] {
+            let req = actix_web::test::TestRequest::get().uri(uri).to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_documents_security_requirements() {
+        let mut op = DefaultOperationRaw::default();
+        RequireScope::<DistanceScope>::update_security(&mut op);
+        assert_eq!(
+            op.security,
+            vec![
+                BTreeMap::from([("basic".to_owned(), vec![])]),
+                BTreeMap::from([("apiKey".to_owned(), vec![])]),
+                BTreeMap::from([("jwt".to_owned(), vec!["distance".to_owned()])]),
+            ]
+        );
+
+        let mut op = DefaultOperationRaw::default();
+        RequireScope::<AdminScope>::update_security(&mut op);
+        assert_eq!(op.security.len(), 2);
+
+        let mut definitions = BTreeMap::new();
+        RequireScope::<DistanceScope>::update_security_definitions(&mut definitions);
+        RequireScope::<AdminScope>::update_security_definitions(&mut definitions);
+        assert_eq!(
+            definitions["jwt"].scopes.keys().collect::<Vec<&String>>(),
+            vec!["admin", "distance"]
+        );
+        assert_eq!(definitions["apiKey"].name.as_deref(), Some("X-API-Key"));
+    }
+}
@@ -0,0 +1,227 @@
+use std::str::FromStr;
+
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::SqlitePool;
+
+/// Prefix of every issued key, telling keys apart from other bearer tokens.
+pub const API_KEY_PREFIX: &str = "dck_";
+
+const API_KEY_SECRET_LENGTH: usize = 40;
+
+/// Characters of a key stored in plaintext, so that keys can be recognized in listings.
+const API_KEY_VISIBLE_LENGTH: usize = API_KEY_PREFIX.len() + 6;
+
+const CREATE_API_KEYS_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS api_keys
+    (
+        id         integer primary key autoincrement,
+        label      text    not null,
+        prefix     text    not null,
+        key_hash   text    not null unique,
+        created_at int     not null,
+        created_by text    not null,
+        expires_at int,
+        revoked_at int
+    )";
+
+/// API key as stored, without the key itself.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    sqlx::FromRow,
+    paperclip::actix::Apiv2Schema,
+)]
+pub struct ApiKey {
+    pub id: i64,
+    pub label: String,
+    /// First characters of the key.
+    pub prefix: String,
+    /// Unix timestamp of issuing the key.
+    pub created_at: i64,
+    pub created_by: String,
+    /// Unix timestamp after which the key is rejected, `None` for keys which don't expire.
+    pub expires_at: Option<i64>,
+    /// Unix timestamp of revoking the key.
+    pub revoked_at: Option<i64>,
+}
+
+/// Newly issued key, the only time its plaintext is available.
+#[derive(Debug, Clone)]
+pub struct IssuedApiKey {
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+/// SQLite store of API keys. Keys are random, so they're stored as SHA-256 digests, which
+/// also lets them be looked up by the digest.
+pub struct ApiKeyStore {
+    pool: SqlitePool,
+}
+
+impl ApiKeyStore {
+    /// Opens the keys database at `database_url`, creating it if it doesn't exist yet.
+    pub async fn open(database_url: &str) -> Result<Self, sqlx::Error> {
+        let db_opts = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+        let pool = SqlitePool::connect_with(db_opts).await?;
+
+        sqlx::query(CREATE_API_KEYS_TABLE).execute(&pool).await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Checks that the keys database can be queried, for readiness probes.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1 FROM api_keys LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    pub async fn issue(
+        &self,
+        label: &str,
+        expires_at: Option<i64>,
+        created_by: &str,
+        created_at: i64,
+    ) -> Result<IssuedApiKey, sqlx::Error> {
+        let key = format!(
+            "{API_KEY_PREFIX}{}",
+            Alphanumeric.sample_string(&mut rand::thread_rng(), API_KEY_SECRET_LENGTH)
+        );
+
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            "INSERT INTO api_keys (label, prefix, key_hash, created_at, created_by, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             RETURNING id, label, prefix, created_at, created_by, expires_at, revoked_at",
+        )
+        .bind(label)
+        .bind(&key[..API_KEY_VISIBLE_LENGTH])
+        .bind(key_hash(&key))
+        .bind(created_at)
+        .bind(created_by)
+        .bind(expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(IssuedApiKey { api_key, key })
+    }
+
+    /// Marks the key as revoked, returning `None` for unknown keys. Revoking a key again keeps
+    /// the original revocation time.
+    pub async fn revoke(&self, id: i64, revoked_at: i64) -> Result<Option<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(
+            "UPDATE api_keys SET revoked_at = COALESCE(revoked_at, ?) WHERE id = ?
+             RETURNING id, label, prefix, created_at, created_by, expires_at, revoked_at",
+        )
+        .bind(revoked_at)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Returns all keys, most recently issued first.
+    pub async fn list(&self) -> Result<Vec<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>(
+            "SELECT id, label, prefix, created_at, created_by, expires_at, revoked_at
+             FROM api_keys ORDER BY id DESC",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Returns the key matching `key` if it's neither revoked nor expired at `now`.
+    pub async fn authenticate(&self, key: &str, now: i64) -> Result<Option<ApiKey>, sqlx::Error> {
+        if !key.starts_with(API_KEY_PREFIX) {
+            return Ok(None);
+        }
+
+        sqlx::query_as::<_, ApiKey>(
+            "SELECT id, label, prefix, created_at, created_by, expires_at, revoked_at
+             FROM api_keys
+             WHERE key_hash = ? AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > ?)",
+        )
+        .bind(key_hash(key))
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
+
+fn key_hash(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_authenticates_issued_keys() {
+        let store = ApiKeyStore::open("sqlite::memory:").await.unwrap();
+
+        let issued = store.issue("importer", None, "alice", 100).await.unwrap();
+        let expiring = store
+            .issue("temporary", Some(200), "alice", 100)
+            .await
+            .unwrap();
+
+        assert!(issued.key.starts_with(&issued.api_key.prefix));
+        assert_eq!(
+            issued.key.len(),
+            API_KEY_PREFIX.len() + API_KEY_SECRET_LENGTH
+        );
+
+        assert_eq!(
+            store.authenticate(&issued.key, 150).await.unwrap(),
+            Some(issued.api_key.clone())
+        );
+        assert!(store
+            .authenticate(&expiring.key, 150)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(store
+            .authenticate(&expiring.key, 200)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store
+            .authenticate(&issued.key[..issued.key.len() - 1], 150)
+            .await
+            .unwrap()
+            .is_none());
+
+        let listed = store.list().await.unwrap();
+        assert_eq!(listed, vec![expiring.api_key, issued.api_key.clone()]);
+    }
+
+    #[actix_web::test]
+    async fn test_rejects_revoked_keys() {
+        let store = ApiKeyStore::open("sqlite::memory:").await.unwrap();
+        let issued = store.issue("importer", None, "alice", 100).await.unwrap();
+
+        let revoked = store.revoke(issued.api_key.id, 150).await.unwrap().unwrap();
+        assert_eq!(revoked.revoked_at, Some(150));
+        assert!(store
+            .authenticate(&issued.key, 160)
+            .await
+            .unwrap()
+            .is_none());
+
+        let revoked_again = store.revoke(issued.api_key.id, 170).await.unwrap().unwrap();
+        assert_eq!(revoked_again.revoked_at, Some(150));
+        assert_eq!(store.revoke(42, 170).await.unwrap(), None);
+    }
+}
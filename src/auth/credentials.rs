@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use argon2::{Argon2, PasswordVerifier};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+#[derive(Debug)]
+pub enum CredentialsError {
+    Io(std::io::Error),
+    Format(String),
+    InvalidUser(String),
+}
+
+impl std::error::Error for CredentialsError {}
+impl std::fmt::Display for CredentialsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CredentialsError::Io(e) => write!(f, "CredentialsError: {e}"),
+            CredentialsError::Format(reason) => write!(f, "CredentialsError: {reason}"),
+            CredentialsError::InvalidUser(reason) => write!(f, "CredentialsError: {reason}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for CredentialsError {
+    fn from(e: std::io::Error) -> Self {
+        CredentialsError::Io(e)
+    }
+}
+
+/// Password of an API user.
+#[derive(Debug, Clone)]
+pub enum StoredPassword {
+    /// Argon2 hash in the PHC string format, e.g. `$argon2id$v=19$m=19456,t=2,p=1$...`.
+    Argon2(String),
+    /// bcrypt hash, e.g. `$2b$12$...`.
+    Bcrypt(String),
+    /// Plaintext password given with `API_PASSWORD`.
+    Plain(String),
+}
+
+impl StoredPassword {
+    pub fn from_hash(hash: &str) -> Result<Self, String> {
+        if hash.starts_with("$argon2") {
+            let parsed =
+                argon2::PasswordHash::new(hash).map_err(|e| format!("invalid Argon2 hash: {e}"))?;
+            if parsed.salt.is_none() || parsed.hash.is_none() {
+                return Err("invalid Argon2 hash: missing salt or hash".to_owned());
+            }
+
+            return Ok(StoredPassword::Argon2(hash.to_owned()));
+        }
+
+        if ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| hash.starts_with(prefix))
+        {
+            hash.parse::<bcrypt::HashParts>()
+                .map_err(|e| format!("invalid bcrypt hash: {e}"))?;
+            return Ok(StoredPassword::Bcrypt(hash.to_owned()));
+        }
+
+        Err("unsupported password hash, expected Argon2 or bcrypt".to_owned())
+    }
+
+    /// Checks `password` in time independent of how much of it matches.
+    pub fn verify(&self, password: &str) -> bool {
+        match self {
+            StoredPassword::Argon2(hash) => argon2::PasswordHash::new(hash).is_ok_and(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            }),
+            StoredPassword::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            // comparing digests doesn't leak the length of the password either
+            StoredPassword::Plain(expected) => Sha256::digest(expected)
+                .ct_eq(&Sha256::digest(password))
+                .into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CredentialsFile {
+    users: Vec<UserEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserEntry {
+    username: String,
+    password_hash: String,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/// User of a credentials file.
+#[derive(Debug, Clone)]
+pub struct StoredUser {
+    pub password: StoredPassword,
+    pub roles: Vec<String>,
+}
+
+/// Reads users of a credentials file, JSON for `.json` files and TOML otherwise:
+///
+/// ```toml
+/// [[users]]
+/// username = "alice"
+/// password_hash = "$argon2id$v=19$m=19456,t=2,p=1$..."
+/// roles = ["admin"]
+/// ```
+pub fn read_credentials_file(path: &Path) -> Result<HashMap<String, StoredUser>, CredentialsError> {
+    let content = std::fs::read_to_string(path)?;
+
+    let file = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => serde_json::from_str::<CredentialsFile>(&content)
+            .map_err(|e| CredentialsError::Format(e.to_string()))?,
+        _ => toml::from_str::<CredentialsFile>(&content)
+            .map_err(|e| CredentialsError::Format(e.to_string()))?,
+    };
+
+    // an empty file would lock everyone out, most likely by mistake
+    if file.users.is_empty() {
+        return Err(CredentialsError::Format(format!(
+            "no users in {}",
+            path.display()
+        )));
+    }
+
+    let mut users = HashMap::with_capacity(file.users.len());
+    for user in file.users {
+        let password = StoredPassword::from_hash(&user.password_hash)
+            .map_err(|e| CredentialsError::InvalidUser(format!("{}: {e}", user.username)))?;
+
+        let stored_user = StoredUser {
+            password,
+            roles: user.roles,
+        };
+        if users.insert(user.username.clone(), stored_user).is_some() {
+            return Err(CredentialsError::InvalidUser(format!(
+                "{}: duplicated username",
+                user.username
+            )));
+        }
+    }
+
+    Ok(users)
+}
+
+#[cfg(test)]
+mod tests {
+    use argon2::password_hash::{rand_core::OsRng, SaltString};
+    use argon2::PasswordHasher;
+
+    use super::*;
+
+    fn argon2_hash(password: &str) -> String {
+        Argon2::default()
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_verifies_supported_hashes() {
+        let argon2 = StoredPassword::from_hash(&argon2_hash("secret")).unwrap();
+        let bcrypt = StoredPassword::from_hash(&bcrypt::hash("secret", 4).unwrap()).unwrap();
+        let plain = StoredPassword::Plain("secret".to_owned());
+
+        for password in [argon2, bcrypt, plain] {
+            assert!(password.verify("secret"), "{password:?}");
+            assert!(!password.verify("secret2"), "{password:?}");
+            assert!(!password.verify(""), "{password:?}");
+        }
+    }
+
+    #[test]
+    fn test_rejects_unsupported_hashes() {
+        for hash in ["secret", "$argon2id$broken", "$2b$12$short", "$1$md5crypt"] {
+            assert!(StoredPassword::from_hash(hash).is_err(), "{hash}");
+        }
+    }
+
+    #[test]
+    fn test_reads_toml_and_json_files() {
+        let dir = std::env::temp_dir().join(format!("api-credentials-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let hash = bcrypt::hash("secret", 4).unwrap();
+
+        let toml_path = dir.join("credentials.toml");
+        std::fs::write(
+            &toml_path,
+            format!(
+                "[[users]]\nusername = \"alice\"\npassword_hash = \"{hash}\"\n\n\
+                 [[users]]\nusername = \"bob\"\npassword_hash = \"{hash}\"\nroles = [\"admin\"]\n"
+            ),
+        )
+        .unwrap();
+        let json_path = dir.join("credentials.json");
+        std::fs::write(
+            &json_path,
+            serde_json::json!({"users": [{"username": "alice", "password_hash": hash}]})
+                .to_string(),
+        )
+        .unwrap();
+        let duplicated_path = dir.join("duplicated.json");
+        std::fs::write(
+            &duplicated_path,
+            serde_json::json!({"users": [
+                {"username": "alice", "password_hash": hash},
+                {"username": "alice", "password_hash": hash},
+            ]})
+            .to_string(),
+        )
+        .unwrap();
+
+        let users = read_credentials_file(&toml_path).unwrap();
+        assert_eq!(users.len(), 2);
+        assert!(users["bob"].password.verify("secret"));
+        assert_eq!(users["bob"].roles, vec!["admin"]);
+        assert!(users["alice"].roles.is_empty());
+
+        assert_eq!(read_credentials_file(&json_path).unwrap().len(), 1);
+        assert!(matches!(
+            read_credentials_file(&duplicated_path),
+            Err(CredentialsError::InvalidUser(_))
+        ));
+        assert!(matches!(
+            read_credentials_file(&dir.join("missing.toml")),
+            Err(CredentialsError::Io(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
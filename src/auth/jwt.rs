@@ -0,0 +1,382 @@
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+
+/// Algorithms tokens may be signed with, symmetric ones would let anyone holding the key
+/// issue tokens.
+const SUPPORTED_ALGORITHMS: [Algorithm; 2] = [Algorithm::RS256, Algorithm::ES256];
+
+#[derive(Debug)]
+pub enum JwtError {
+    Io(std::io::Error),
+    InvalidJwks(String),
+    InvalidToken(String),
+}
+
+impl std::error::Error for JwtError {}
+impl std::fmt::Display for JwtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JwtError::Io(e) => write!(f, "JwtError: {e}"),
+            JwtError::InvalidJwks(reason) => write!(f, "JwtError: {reason}"),
+            JwtError::InvalidToken(reason) => write!(f, "JwtError: {reason}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for JwtError {
+    fn from(e: std::io::Error) -> Self {
+        JwtError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JwtConfig {
+    /// JWKS file with the public keys of the identity provider.
+    pub jwks_path: PathBuf,
+    pub issuer: String,
+    pub audience: String,
+    /// How often the JWKS file is read again, to pick up rotated keys.
+    pub refresh_interval: Duration,
+}
+
+/// Claims of a validated token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenClaims {
+    pub subject: String,
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    /// Space separated scopes, as issued by OAuth 2.0 servers.
+    #[serde(default)]
+    scope: Option<String>,
+    /// Scopes as a list, as issued by some identity providers instead of `scope`.
+    #[serde(default)]
+    scp: Option<Vec<String>>,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+struct VerificationKey {
+    key_id: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Validates RS256 and ES256 signed JWTs against the keys of a JWKS file.
+pub struct JwtValidator {
+    config: JwtConfig,
+    keys: RwLock<Vec<VerificationKey>>,
+}
+
+impl JwtValidator {
+    pub fn open(config: JwtConfig) -> Result<Self, JwtError> {
+        let keys = read_jwks(&config)?;
+
+        Ok(Self {
+            config,
+            keys: RwLock::new(keys),
+        })
+    }
+
+    pub fn config(&self) -> &JwtConfig {
+        &self.config
+    }
+
+    /// Reads the JWKS file again, keeping the current keys if it's invalid. Returns the number
+    /// of keys.
+    pub fn refresh(&self) -> Result<usize, JwtError> {
+        let keys = read_jwks(&self.config)?;
+        let keys_count = keys.len();
+
+        *self.keys.write().unwrap() = keys;
+
+        Ok(keys_count)
+    }
+
+    /// Checks the signature, issuer, audience and expiry of `token`.
+    pub fn validate(&self, token: &str) -> Result<TokenClaims, JwtError> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|e| JwtError::InvalidToken(e.to_string()))?;
+
+        if !SUPPORTED_ALGORITHMS.contains(&header.alg) {
+            return Err(JwtError::InvalidToken(format!(
+                "unsupported algorithm {:?}",
+                header.alg
+            )));
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let keys = self.keys.read().unwrap();
+        let candidates = keys.iter().filter(|key| {
+            key.algorithm == header.alg && (header.kid.is_none() || key.key_id == header.kid)
+        });
+
+        let mut error = JwtError::InvalidToken("no matching key".to_owned());
+        for key in candidates {
+            match jsonwebtoken::decode::<Claims>(token, &key.key, &validation) {
+                Ok(token) => return Ok(token_claims(token.claims)),
+                Err(e) => error = JwtError::InvalidToken(e.to_string()),
+            }
+        }
+
+        Err(error)
+    }
+}
+
+fn token_claims(claims: Claims) -> TokenClaims {
+    let mut scopes = claims.scp.unwrap_or_default();
+    if let Some(scope) = claims.scope {
+        scopes.extend(scope.split_whitespace().map(str::to_owned));
+    }
+    scopes.sort();
+    scopes.dedup();
+
+    TokenClaims {
+        subject: claims.sub,
+        roles: claims.roles,
+        scopes,
+    }
+}
+
+fn read_jwks(config: &JwtConfig) -> Result<Vec<VerificationKey>, JwtError> {
+    let content = std::fs::read_to_string(&config.jwks_path)?;
+    let jwks = serde_json::from_str::<JwkSet>(&content)
+        .map_err(|e| JwtError::InvalidJwks(e.to_string()))?;
+
+    let keys = jwks
+        .keys
+        .iter()
+        .filter_map(verification_key)
+        .collect::<Vec<VerificationKey>>();
+
+    // an empty set would reject every token, most likely by mistake
+    if keys.is_empty() {
+        return Err(JwtError::InvalidJwks(format!(
+            "no RS256 or ES256 keys in {}",
+            config.jwks_path.display()
+        )));
+    }
+
+    Ok(keys)
+}
+
+fn verification_key(jwk: &Jwk) -> Option<VerificationKey> {
+    let algorithm = match (&jwk.algorithm, jwk.common.algorithm) {
+        (AlgorithmParameters::RSA(_), None | Some(Algorithm::RS256)) => Algorithm::RS256,
+        (AlgorithmParameters::EllipticCurve(params), None | Some(Algorithm::ES256))
+            if params.curve == EllipticCurve::P256 =>
+        {
+            Algorithm::ES256
+        }
+        _ => return None,
+    };
+
+    Some(VerificationKey {
+        key_id: jwk.common.key_id.clone(),
+        algorithm,
+        key: DecodingKey::from_jwk(jwk).ok()?,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use jsonwebtoken::{EncodingKey, Header};
+    use openssl::bn::BigNumContext;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// Key pair generated for a test, with its public part as a JWK.
+    pub(crate) struct TestKey {
+        pub algorithm: Algorithm,
+        pub key_id: String,
+        pub encoding_key: EncodingKey,
+        pub jwk: Value,
+    }
+
+    impl TestKey {
+        pub fn rsa(key_id: &str) -> Self {
+            let rsa = Rsa::generate(2048).unwrap();
+            let jwk = json!({
+                "kty": "RSA",
+                "kid": key_id,
+                "n": URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+                "e": URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+            });
+
+            Self {
+                algorithm: Algorithm::RS256,
+                key_id: key_id.to_owned(),
+                encoding_key: EncodingKey::from_rsa_pem(&rsa.private_key_to_pem().unwrap())
+                    .unwrap(),
+                jwk,
+            }
+        }
+
+        pub fn ec(key_id: &str) -> Self {
+            let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+            let ec = EcKey::generate(&group).unwrap();
+
+            let mut x = openssl::bn::BigNum::new().unwrap();
+            let mut y = openssl::bn::BigNum::new().unwrap();
+            ec.public_key()
+                .affine_coordinates(&group, &mut x, &mut y, &mut BigNumContext::new().unwrap())
+                .unwrap();
+            let jwk = json!({
+                "kty": "EC",
+                "kid": key_id,
+                "crv": "P-256",
+                "x": URL_SAFE_NO_PAD.encode(x.to_vec_padded(32).unwrap()),
+                "y": URL_SAFE_NO_PAD.encode(y.to_vec_padded(32).unwrap()),
+            });
+            let pem = PKey::from_ec_key(ec)
+                .unwrap()
+                .private_key_to_pem_pkcs8()
+                .unwrap();
+
+            Self {
+                algorithm: Algorithm::ES256,
+                key_id: key_id.to_owned(),
+                encoding_key: EncodingKey::from_ec_pem(&pem).unwrap(),
+                jwk,
+            }
+        }
+
+        pub fn sign(&self, claims: &Value) -> String {
+            let mut header = Header::new(self.algorithm);
+            header.kid = Some(self.key_id.clone());
+
+            jsonwebtoken::encode(&header, claims, &self.encoding_key).unwrap()
+        }
+    }
+
+    pub(crate) fn write_jwks(name: &str, keys: &[&TestKey]) -> JwtConfig {
+        let path = std::env::temp_dir().join(format!("{name}-{}.json", std::process::id()));
+        let jwks = json!({"keys": keys.iter().map(|key| key.jwk.clone()).collect::<Vec<Value>>()});
+        std::fs::write(&path, jwks.to_string()).unwrap();
+
+        JwtConfig {
+            jwks_path: path,
+            issuer: "https://sso.example.com".to_owned(),
+            audience: "distance-calculator".to_owned(),
+            refresh_interval: Duration::from_secs(300),
+        }
+    }
+
+    pub(crate) fn claims(subject: &str, scope: &str) -> Value {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        json!({
+            "sub": subject,
+            "iss": "https://sso.example.com",
+            "aud": "distance-calculator",
+            "exp": now + 600,
+            "scope": scope,
+        })
+    }
+
+    #[test]
+    fn test_validates_rs256_and_es256_tokens() {
+        let rsa = TestKey::rsa("rsa");
+        let ec = TestKey::ec("ec");
+        let config = write_jwks("jwks-algorithms", &[&rsa, &ec]);
+        let validator = JwtValidator::open(config.clone()).unwrap();
+
+        for key in [&rsa, &ec] {
+            let claims = validator
+                .validate(&key.sign(&claims("service", "distance airports:read")))
+                .unwrap();
+
+            assert_eq!(
+                claims,
+                TokenClaims {
+                    subject: "service".to_owned(),
+                    roles: vec![],
+                    scopes: vec!["airports:read".to_owned(), "distance".to_owned()],
+                }
+            );
+        }
+
+        std::fs::remove_file(config.jwks_path).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_invalid_tokens() {
+        let key = TestKey::ec("ec");
+        let unknown_key = TestKey::ec("ec");
+        let config = write_jwks("jwks-invalid", &[&key]);
+        let validator = JwtValidator::open(config.clone()).unwrap();
+
+        let mut expired = claims("service", "distance");
+        expired["exp"] = json!(1683000000);
+        let mut wrong_issuer = claims("service", "distance");
+        wrong_issuer["iss"] = json!("https://evil.example.com");
+        let mut wrong_audience = claims("service", "distance");
+        wrong_audience["aud"] = json!("other-service");
+        let hs256 = jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims("service", "distance"),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        for token in [
+            key.sign(&expired),
+            key.sign(&wrong_issuer),
+            key.sign(&wrong_audience),
+            unknown_key.sign(&claims("service", "distance")),
+            hs256,
+            "not-a-token".to_owned(),
+        ] {
+            assert!(validator.validate(&token).is_err(), "{token}");
+        }
+
+        std::fs::remove_file(config.jwks_path).unwrap();
+    }
+
+    #[test]
+    fn test_refreshes_rotated_keys() {
+        let old_key = TestKey::rsa("old");
+        let new_key = TestKey::ec("new");
+        let config = write_jwks("jwks-rotation", &[&old_key]);
+        let validator = JwtValidator::open(config.clone()).unwrap();
+        let token = new_key.sign(&claims("service", "distance"));
+
+        assert!(validator.validate(&token).is_err());
+
+        write_jwks("jwks-rotation", &[&new_key]);
+        assert_eq!(validator.refresh().unwrap(), 1);
+        assert!(validator.validate(&token).is_ok());
+
+        // invalid files don't replace loaded keys
+        std::fs::write(&config.jwks_path, r#"{"keys": []}"#).unwrap();
+        assert!(validator.refresh().is_err());
+        assert!(validator.validate(&token).is_ok());
+
+        std::fs::remove_file(config.jwks_path).unwrap();
+    }
+}
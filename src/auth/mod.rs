@@ -0,0 +1,589 @@
+mod access;
+mod api_keys;
+mod credentials;
+mod jwt;
+mod principal;
+
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::dev::{Payload, ServiceRequest};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::{Header, AUTHORIZATION};
+use actix_web::web::{self, Data};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use actix_web_httpauth::extractors::AuthenticationError;
+use actix_web_httpauth::headers::authorization::{self, Authorization};
+use actix_web_httpauth::headers::www_authenticate::basic::Basic;
+use moka::sync::Cache;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::log;
+
+use crate::tls::{ClientCertificate, ClientSubjects};
+use crate::usage;
+
+#[cfg(test)]
+pub(crate) use access::tests::AuthenticateAs;
+pub(crate) use access::{AdminScope, AirportsScope, DistanceScope, RequireRole, RequireScope};
+pub(crate) use api_keys::{ApiKey, ApiKeyStore, API_KEY_PREFIX};
+pub(crate) use credentials::CredentialsError;
+use credentials::{read_credentials_file, StoredPassword, StoredUser};
+pub(crate) use jwt::{JwtConfig, JwtValidator};
+pub(crate) use principal::{Identity, Principal, ADMIN_ROLE};
+
+/// Header carrying API keys, as an alternative to `Authorization: Bearer`.
+pub(crate) const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Hash checked for unknown users, so that response times don't reveal which users exist.
+const UNKNOWN_USER_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$xgBiAEx2uFEaXAwa1jjOWg$aJSSzdx1WmDgb8NCDG14jF3fKJbRyz2WvDYM4r5MshM";
+
+pub(crate) struct ApiCredentials {
+    /// Credentials file the users were read from, `None` for `API_USERNAME`/`API_PASSWORD`.
+    path: Option<PathBuf>,
+    users: RwLock<HashMap<String, StoredUser>>,
+    /// Digests of recently verified credentials, hashes are deliberately slow to check and
+    /// clients send credentials with every request.
+    verified: Cache<[u8; 32], ()>,
+}
+
+impl ApiCredentials {
+    pub fn from_file(path: PathBuf) -> Result<Self, CredentialsError> {
+        let users = read_credentials_file(&path)?;
+
+        Ok(ApiCredentials::with_users(Some(path), users))
+    }
+
+    fn with_users(path: Option<PathBuf>, users: HashMap<String, StoredUser>) -> Self {
+        ApiCredentials {
+            path,
+            users: RwLock::new(users),
+            verified: Cache::builder()
+                .max_capacity(1000)
+                .time_to_live(Duration::from_secs(60))
+                .build(),
+        }
+    }
+
+    /// Reads the credentials file again, keeping the current users if it's invalid. Returns
+    /// the number of users.
+    pub fn reload(&self) -> Result<usize, CredentialsError> {
+        let Some(path) = &self.path else {
+            return Ok(self.users.read().unwrap().len());
+        };
+
+        let users = read_credentials_file(path)?;
+        let users_count = users.len();
+
+        *self.users.write().unwrap() = users;
+        self.verified.invalidate_all();
+
+        Ok(users_count)
+    }
+
+    pub fn validate(&self, user_id: &str, password: &str) -> bool {
+        let digest: [u8; 32] = Sha256::new()
+            .chain_update(user_id)
+            .chain_update([0])
+            .chain_update(password)
+            .finalize()
+            .into();
+        if self.verified.contains_key(&digest) {
+            return true;
+        }
+
+        let stored_password = self
+            .users
+            .read()
+            .unwrap()
+            .get(user_id)
+            .map(|user| user.password.clone());
+        let valid = match stored_password {
+            Some(stored_password) => stored_password.verify(password),
+            None => {
+                StoredPassword::Argon2(UNKNOWN_USER_HASH.to_owned()).verify(password);
+                false
+            }
+        };
+
+        if valid {
+            self.verified.insert(digest, ());
+        }
+
+        valid
+    }
+
+    /// Roles of the user, empty for unknown users.
+    pub fn roles(&self, user_id: &str) -> Vec<String> {
+        self.users
+            .read()
+            .unwrap()
+            .get(user_id)
+            .map(|user| user.roles.clone())
+            .unwrap_or_default()
+    }
+
+    /// Single admin user with a plaintext password, for services operated by one user.
+    pub fn new(user_id: &str, password: &str) -> Self {
+        ApiCredentials::with_users(
+            None,
+            HashMap::from([(
+                user_id.to_owned(),
+                StoredUser {
+                    password: StoredPassword::Plain(password.to_owned()),
+                    roles: vec![ADMIN_ROLE.to_owned()],
+                },
+            )]),
+        )
+    }
+}
+
+/// Usage of the caller is available also to clients over their quotas.
+fn counts_towards_quota(path: &str) -> bool {
+    path.strip_prefix("/api").unwrap_or(path) != "/usage"
+}
+
+/// Credentials sent with a request, either a Basic login, an API key given in the
+/// `X-API-Key` header or a bearer token, which is either an API key or a JWT. Without any of
+/// them, the subject of the client certificate of the connection is used.
+#[derive(Debug, Clone)]
+pub(crate) enum ApiAuthentication {
+    Basic(authorization::Basic),
+    ApiKey(String),
+    Bearer(String),
+    Certificate(String),
+}
+
+impl FromRequest for ApiAuthentication {
+    type Error = AuthenticationError<Basic>;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Some(api_key) = request.headers().get(API_KEY_HEADER) {
+            return ready(
+                api_key
+                    .to_str()
+                    .map(|api_key| ApiAuthentication::ApiKey(api_key.to_owned()))
+                    .map_err(|_| challenge()),
+            );
+        }
+
+        if let Ok(bearer) = Authorization::<authorization::Bearer>::parse(request) {
+            return ready(Ok(ApiAuthentication::Bearer(
+                bearer.as_ref().token().to_string(),
+            )));
+        }
+
+        if !request.headers().contains_key(AUTHORIZATION) {
+            if let Some(certificate) = request.conn_data::<ClientCertificate>() {
+                return ready(Ok(ApiAuthentication::Certificate(
+                    certificate.subject.clone(),
+                )));
+            }
+        }
+
+        ready(
+            Authorization::<authorization::Basic>::parse(request)
+                .map(|basic| ApiAuthentication::Basic(basic.into_scheme()))
+                .map_err(|_| challenge()),
+        )
+    }
+}
+
+fn challenge() -> AuthenticationError<Basic> {
+    AuthenticationError::new(Basic::with_realm("Access to the API"))
+}
+
+pub(crate) async fn api_auth_validator(
+    request: ServiceRequest,
+    authentication: ApiAuthentication,
+) -> Result<ServiceRequest, (Error, ServiceRequest)> {
+    let principal = match authentication {
+        ApiAuthentication::Basic(credentials) => validate_basic(&request, credentials).await,
+        ApiAuthentication::ApiKey(api_key) => validate_api_key(&request, &api_key).await,
+        ApiAuthentication::Bearer(token) if token.starts_with(API_KEY_PREFIX) => {
+            validate_api_key(&request, &token).await
+        }
+        ApiAuthentication::Bearer(token) => Ok(validate_jwt(&request, &token)),
+        ApiAuthentication::Certificate(subject) => Ok(validate_certificate(&request, &subject)),
+    };
+
+    match principal {
+        Ok(Some(principal)) => {
+            if counts_towards_quota(request.path()) {
+                if let Err(e) = usage::check_quota(&request, &principal).await {
+                    return Err((e, request));
+                }
+            }
+
+            request.extensions_mut().insert(principal);
+            Ok(request)
+        }
+        Ok(None) => Err((challenge().into(), request)),
+        Err(e) => Err((e, request)),
+    }
+}
+
+async fn validate_basic(
+    request: &ServiceRequest,
+    credentials: authorization::Basic,
+) -> Result<Option<Principal>, Error> {
+    let api_credentials = request
+        .app_data::<Data<ApiCredentials>>()
+        .expect("App should've panicked if no credentials provided.")
+        .clone();
+
+    // verifying password hashes would block the worker
+    let principal = web::block(move || {
+        let user_id = credentials.user_id();
+        credentials
+            .password()
+            .is_some_and(|password| api_credentials.validate(user_id, password))
+            .then(|| Principal::user(user_id, api_credentials.roles(user_id)))
+    })
+    .await
+    .unwrap_or(None);
+
+    Ok(principal)
+}
+
+async fn validate_api_key(
+    request: &ServiceRequest,
+    api_key: &str,
+) -> Result<Option<Principal>, Error> {
+    let Some(api_key_store) = request.app_data::<Data<ApiKeyStore>>() else {
+        return Ok(None);
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+
+    match api_key_store.authenticate(api_key, now).await {
+        Ok(api_key) => Ok(api_key.map(|api_key| Principal::api_key(api_key.id, &api_key.label))),
+        Err(e) => {
+            log::error!("Failed to authenticate API key: {e}");
+            Err(ErrorInternalServerError(json!({"error": "Database fail"})))
+        }
+    }
+}
+
+fn validate_jwt(request: &ServiceRequest, token: &str) -> Option<Principal> {
+    let jwt_validator = request.app_data::<Data<JwtValidator>>()?;
+
+    match jwt_validator.validate(token) {
+        Ok(claims) => Some(Principal::token(
+            &claims.subject,
+            claims.roles,
+            claims.scopes,
+        )),
+        Err(e) => {
+            log::warn!("Rejected bearer token: {e}");
+            None
+        }
+    }
+}
+
+fn validate_certificate(request: &ServiceRequest, subject: &str) -> Option<Principal> {
+    let client_subjects = request.app_data::<Data<ClientSubjects>>()?;
+
+    let Some(user_id) = client_subjects.user(subject) else {
+        log::warn!("Rejected client certificate of unknown subject `{subject}`");
+        return None;
+    };
+    let api_credentials = request
+        .app_data::<Data<ApiCredentials>>()
+        .expect("App should've panicked if no credentials provided.");
+
+    Some(Principal::user(user_id, api_credentials.roles(user_id)))
+}
+
+/// Reads the JWKS file again every `refresh_interval`, to pick up rotated keys.
+pub(crate) async fn refresh_jwks_periodically(jwt_validator: Data<JwtValidator>) {
+    let mut interval = actix_web::rt::time::interval(jwt_validator.config().refresh_interval);
+    // the first tick completes immediately, right after the keys were read
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = jwt_validator.refresh() {
+            log::error!("Failed to refresh JWKS, keeping previous keys: {e}");
+        }
+    }
+}
+
+/// Reloads the credentials file whenever the process receives SIGHUP.
+#[cfg(unix)]
+pub(crate) async fn reload_credentials_on_hangup(api_credentials: Data<ApiCredentials>) {
+    use actix_web::rt::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            log::error!("Failed to listen for SIGHUP: {e}");
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        match api_credentials.reload() {
+            Ok(users_count) => log::info!("Reloaded API credentials of {users_count} users"),
+            Err(e) => log::error!("Failed to reload API credentials, keeping previous ones: {e}"),
+        }
+    }
+}
+
+pub(crate) fn request_principal(request: &HttpRequest) -> Option<Principal> {
+    request.extensions().get::<Principal>().cloned()
+}
+
+/// Name of the client the request was authenticated as, used to attribute changes.
+pub(crate) fn request_user(request: &HttpRequest) -> String {
+    request_principal(request)
+        .map(|principal| principal.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::{get, web, web::Data};
+    use actix_web_httpauth::{
+        headers::authorization::{Authorization, Basic},
+        middleware::HttpAuthentication,
+    };
+
+    use super::jwt::tests::{claims, write_jwks, TestKey};
+    use serde_json::json;
+
+    use super::{
+        api_auth_validator, AdminScope, ApiAuthentication, ApiCredentials, DistanceScope,
+        JwtValidator, Principal, RequireRole, RequireScope, ADMIN_ROLE,
+    };
+    use crate::tls::ClientSubjects;
+    use actix_web::HttpMessage;
+
+    fn write_credentials(path: &std::path::Path, users: &[(&str, &str)]) {
+        let users = users
+            .iter()
+            .map(|(username, password)| {
+                format!(
+                    "[[users]]\nusername = \"{username}\"\npassword_hash = \"{}\"\n",
+                    bcrypt::hash(password, 4).unwrap()
+                )
+            })
+            .collect::<Vec<String>>();
+
+        std::fs::write(path, users.join("\n")).unwrap();
+    }
+
+    #[get("/")]
+    async fn test_handler() -> &'static str {
+        "Hello, world!"
+    }
+
+    #[get("/api/calculate_distance/test")]
+    async fn distance_handler(_scope: RequireScope<DistanceScope>) -> &'static str {
+        "distance"
+    }
+
+    #[get("/test")]
+    async fn admin_handler(_scope: RequireScope<AdminScope>) -> &'static str {
+        "admin"
+    }
+
+    #[get("/api/usage")]
+    async fn usage_handler() -> &'static str {
+        "usage"
+    }
+
+    #[actix_web::test]
+    async fn test_auth_with_jwt_scopes() {
+        let key = TestKey::rsa("rsa");
+        let config = write_jwks("jwks-auth", &[&key]);
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(Data::new(ApiCredentials::new("test", "test")))
+                .app_data(Data::new(JwtValidator::open(config.clone()).unwrap()))
+                .service(distance_handler)
+                .service(
+                    web::scope("/api/admin")
+                        .wrap(RequireRole::new(ADMIN_ROLE))
+                        .service(admin_handler),
+                )
+                .service(usage_handler)
+                .wrap(HttpAuthentication::with_fn(api_auth_validator)),
+        )
+        .await;
+        let token = key.sign(&claims("service", "distance"));
+        let mut admin_claims = claims("operator", "admin");
+        admin_claims["roles"] = json!(["admin"]);
+        let admin_token = key.sign(&admin_claims);
+        let mut role_only_claims = claims("operator", "distance");
+        role_only_claims["roles"] = json!(["admin"]);
+        let role_only_token = key.sign(&role_only_claims);
+
+        for (uri, token, status) in [
+            (
+                "/api/calculate_distance/test",
+                token.as_str(),
+                StatusCode::OK,
+            ),
+            ("/api/admin/test", token.as_str(), StatusCode::FORBIDDEN),
+            ("/api/usage", token.as_str(), StatusCode::OK),
+            ("/api/admin/test", admin_token.as_str(), StatusCode::OK),
+            // the role opens /api/admin, the scope is still required by handlers
+            (
+                "/api/admin/test",
+                role_only_token.as_str(),
+                StatusCode::FORBIDDEN,
+            ),
+            (
+                "/api/calculate_distance/test",
+                "invalid",
+                StatusCode::UNAUTHORIZED,
+            ),
+        ] {
+            let req = actix_web::test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("Authorization", format!("Bearer {token}")))
+                .to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), status, "{uri}");
+        }
+
+        // users are limited by roles only
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/admin/test")
+            .insert_header(Authorization::<Basic>::from(Basic::new(
+                "test",
+                Some("test"),
+            )))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        std::fs::remove_file(config.jwks_path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_auth() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(Data::new(ApiCredentials::new("test", "test")))
+                .service(test_handler)
+                .wrap(HttpAuthentication::with_fn(api_auth_validator)),
+        )
+        .await;
+
+        let basic_auth_header = Basic::new("test", Some("test"));
+        let req = actix_web::test::TestRequest::get()
+            .uri("/")
+            .insert_header(Authorization::<Basic>::from(basic_auth_header))
+            .to_request();
+
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn test_auth_with_client_certificate() {
+        let client_subjects: ClientSubjects =
+            toml::from_str("[subjects]\n\"O=Example, CN=operator\" = \"test\"").unwrap();
+
+        for (subject, principal) in [
+            (
+                "O=Example, CN=operator",
+                Some(Principal::user("test", vec![ADMIN_ROLE.to_owned()])),
+            ),
+            ("O=Example, CN=unknown", None),
+        ] {
+            let request = actix_web::test::TestRequest::default()
+                .app_data(Data::new(ApiCredentials::new("test", "test")))
+                .app_data(Data::new(client_subjects.clone()))
+                .to_srv_request();
+
+            match api_auth_validator(request, ApiAuthentication::Certificate(subject.to_owned()))
+                .await
+            {
+                Ok(request) => {
+                    assert_eq!(request.extensions().get::<Principal>(), principal.as_ref())
+                }
+                Err((e, _)) => {
+                    assert!(principal.is_none(), "{subject}");
+                    assert_eq!(
+                        e.as_response_error().status_code(),
+                        StatusCode::UNAUTHORIZED
+                    );
+                }
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_auth_with_credentials_file() {
+        let path = std::env::temp_dir().join(format!("api-users-{}.toml", std::process::id()));
+        write_credentials(&path, &[("alice", "first"), ("bob", "second")]);
+        let api_credentials = ApiCredentials::from_file(path.clone()).unwrap();
+
+        assert!(api_credentials.validate("alice", "first"));
+        assert!(api_credentials.validate("bob", "second"));
+        assert!(!api_credentials.validate("alice", "second"));
+        assert!(!api_credentials.validate("carol", "first"));
+        assert!(api_credentials.roles("alice").is_empty());
+
+        write_credentials(&path, &[("carol", "third")]);
+        assert_eq!(api_credentials.reload().unwrap(), 1);
+        assert!(api_credentials.validate("carol", "third"));
+        assert!(!api_credentials.validate("alice", "first"));
+
+        // invalid files don't replace loaded users
+        std::fs::write(&path, "users = []").unwrap();
+        assert!(api_credentials.reload().is_err());
+        assert!(api_credentials.validate("carol", "third"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_returns_401_if_no_credentials_provided() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(Data::new(ApiCredentials::new("test", "test")))
+                .service(test_handler)
+                .wrap(HttpAuthentication::with_fn(api_auth_validator)),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::get().uri("/").to_request();
+
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_returns_401_if_incorrect_credentials_provided() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(Data::new(ApiCredentials::new("test", "test")))
+                .service(test_handler)
+                .wrap(HttpAuthentication::with_fn(api_auth_validator)),
+        )
+        .await;
+
+        let basic_auth_header = Basic::new("test", Some("wrong_password"));
+        let req = actix_web::test::TestRequest::get()
+            .uri("/")
+            .insert_header(Authorization::<Basic>::from(basic_auth_header))
+            .to_request();
+
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+
+/// Scope of the distance calculation endpoints.
+pub(crate) const DISTANCE_SCOPE: &str = "distance";
+/// Scope of the airports endpoints.
+pub(crate) const AIRPORTS_SCOPE: &str = "airports:read";
+/// Scope of the admin endpoints.
+pub(crate) const ADMIN_SCOPE: &str = "admin";
+
+/// Role required to call anything under `/api/admin`.
+pub(crate) const ADMIN_ROLE: &str = "admin";
+
+/// Scopes of every user, which endpoints they can call is limited by their roles instead.
+const USER_SCOPES: [&str; 3] = [DISTANCE_SCOPE, AIRPORTS_SCOPE, ADMIN_SCOPE];
+
+/// Scopes of API keys, which are issued to machine clients and never administer the service.
+pub(crate) const API_KEY_SCOPES: [&str; 2] = [DISTANCE_SCOPE, AIRPORTS_SCOPE];
+
+/// Who a request was authenticated as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Identity {
+    User(String),
+    ApiKey {
+        id: i64,
+        label: String,
+    },
+    /// Caller of a JWT issued by the identity provider.
+    Token {
+        subject: String,
+    },
+}
+
+/// Client a request was authenticated as together with what it's allowed to do, stored in the
+/// request extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Principal {
+    pub identity: Identity,
+    /// Roles guarding groups of endpoints, e.g. `admin` for `/api/admin`.
+    pub roles: Vec<String>,
+    /// Scopes of the endpoints the client can call.
+    pub scopes: Vec<String>,
+}
+
+impl Principal {
+    pub fn user(user_id: &str, roles: Vec<String>) -> Self {
+        Self {
+            identity: Identity::User(user_id.to_owned()),
+            roles,
+            scopes: USER_SCOPES.map(str::to_owned).to_vec(),
+        }
+    }
+
+    pub fn api_key(id: i64, label: &str) -> Self {
+        Self {
+            identity: Identity::ApiKey {
+                id,
+                label: label.to_owned(),
+            },
+            roles: vec![],
+            scopes: API_KEY_SCOPES.map(str::to_owned).to_vec(),
+        }
+    }
+
+    pub fn token(subject: &str, roles: Vec<String>, scopes: Vec<String>) -> Self {
+        Self {
+            identity: Identity::Token {
+                subject: subject.to_owned(),
+            },
+            roles,
+            scopes,
+        }
+    }
+
+    /// Stable identifier of the client, unlike the display name it doesn't change with
+    /// API key labels. Every kind of principal has its own prefix, so that a user can't share
+    /// limits and usage with an API key or token by its name.
+    pub fn client_id(&self) -> String {
+        match &self.identity {
+            Identity::User(user_id) => format!("user:{user_id}"),
+            Identity::ApiKey { id, .. } => format!("api-key:{id}"),
+            Identity::Token { subject } => format!("jwt:{subject}"),
+        }
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|granted| granted == role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope)
+    }
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.identity {
+            Identity::User(user_id) => write!(f, "{user_id}"),
+            Identity::ApiKey { id, label } => write!(f, "api-key:{id} ({label})"),
+            Identity::Token { subject } => write!(f, "jwt:{subject}"),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{BufRead, IsTerminal};
+use std::process::ExitCode;
+
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use argon2::{Argon2, PasswordHasher};
+use clap::{Parser, ValueEnum};
+
+/// Hashes a password read from stdin for the `password_hash` of an API credentials file.
+#[derive(Debug, Parser)]
+#[command(name = "distance-calculator-hash-password", version)]
+struct Cli {
+    #[arg(long, value_enum, default_value_t = Algorithm::Argon2)]
+    algorithm: Algorithm,
+
+    /// Cost of bcrypt hashes.
+    #[arg(long, default_value_t = bcrypt::DEFAULT_COST)]
+    cost: u32,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Algorithm {
+    Argon2,
+    Bcrypt,
+}
+
+/// Prompts for the password without echoing it when run interactively, and reads the first
+/// line of stdin when it's piped.
+fn read_password() -> Result<String, String> {
+    let stdin = std::io::stdin();
+
+    let password = match stdin.is_terminal() {
+        true => rpassword::prompt_password("Password: "),
+        false => {
+            let mut password = String::new();
+            stdin.lock().read_line(&mut password).map(|_| password)
+        }
+    }
+    .map_err(|e| format!("failed to read password: {e}"))?;
+
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        return Err("password can't be empty".to_owned());
+    }
+
+    Ok(password.to_owned())
+}
+
+fn hash(cli: &Cli, password: &str) -> Result<String, String> {
+    match cli.algorithm {
+        Algorithm::Argon2 => Argon2::default()
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
+            .map(|hash| hash.to_string())
+            .map_err(|e| format!("failed to hash password: {e}")),
+        Algorithm::Bcrypt => {
+            bcrypt::hash(password, cli.cost).map_err(|e| format!("failed to hash password: {e}"))
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match read_password().and_then(|password| hash(&cli, &password)) {
+        Ok(hash) => {
+            println!("{hash}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Connection, SqliteConnection};
+
+use crate::record::Dms;
+use crate::validation;
+
+/// Altitudes in meters of real airports, from the Dead Sea shore up to the Tibetan plateau.
+const ALTITUDE_RANGE_IN_METERS: std::ops::RangeInclusive<i64> = -500..=5000;
+
+/// Countries used by at most this many airports are checked for misspellings of more
+/// common country names.
+const RARE_COUNTRY_AIRPORTS: usize = 1;
+
+/// Airport row as stored in the `airports` table.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct StoredAirport {
+    pub id: i64,
+    pub icao_code: String,
+    pub iata_code: String,
+    pub name: String,
+    pub city: String,
+    pub country: String,
+    pub lat_deg: i64,
+    pub lat_min: i64,
+    pub lat_sec: i64,
+    pub lat_dir: String,
+    pub lon_deg: i64,
+    pub lon_min: i64,
+    pub lon_sec: i64,
+    pub lon_dir: String,
+    pub altitude: i64,
+    pub lat_decimal: f64,
+    pub lon_decimal: f64,
+}
+
+/// Data quality issue found in a single airport row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub id: i64,
+    pub iata_code: String,
+    pub icao_code: String,
+    pub issue: String,
+}
+
+pub async fn read_airports(path: &Path) -> Result<Vec<StoredAirport>, sqlx::Error> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut connection = SqliteConnection::connect_with(&options).await?;
+
+    sqlx::query_as::<_, StoredAirport>("SELECT * FROM airports ORDER BY id")
+        .fetch_all(&mut connection)
+        .await
+}
+
+/// Checks every airport for placeholder rows, DMS fields inconsistent with decimal
+/// coordinates, duplicated codes, null island coordinates, implausible altitudes and
+/// suspicious country names. Issues are described as `kind: details`, same as rejection
+/// reasons of the import.
+pub fn audit(airports: &[StoredAirport]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut seen_iatas: HashMap<&str, i64> = HashMap::new();
+    let mut seen_icaos: HashMap<&str, i64> = HashMap::new();
+    let suspicious_countries = suspicious_countries(airports);
+
+    for airport in airports {
+        let mut report = |issue: String| {
+            findings.push(Finding {
+                id: airport.id,
+                iata_code: airport.iata_code.to_owned(),
+                icao_code: airport.icao_code.to_owned(),
+                issue,
+            })
+        };
+
+        // placeholder rows are never served, so their remaining fields don't matter
+        if airport.name == "N/A" {
+            report("placeholder row: name is N/A".to_owned());
+            continue;
+        }
+
+        if airport.lat_decimal == 0. && airport.lon_decimal == 0. {
+            report("null island coordinates: 0, 0".to_owned());
+        } else {
+            let latitude = dms(
+                airport.lat_deg,
+                airport.lat_min,
+                airport.lat_sec,
+                &airport.lat_dir,
+            );
+            let longitude = dms(
+                airport.lon_deg,
+                airport.lon_min,
+                airport.lon_sec,
+                &airport.lon_dir,
+            );
+
+            let dms_checks = [
+                validation::validate_dms(
+                    Some(latitude),
+                    airport.lat_decimal,
+                    &['N', 'S'],
+                    90,
+                    "latitude",
+                ),
+                validation::validate_dms(
+                    Some(longitude),
+                    airport.lon_decimal,
+                    &['E', 'W'],
+                    180,
+                    "longitude",
+                ),
+            ];
+            for issue in dms_checks.into_iter().filter_map(Result::err) {
+                report(issue);
+            }
+        }
+
+        for (code, seen, kind) in [
+            (&airport.iata_code, &mut seen_iatas, "IATA"),
+            (&airport.icao_code, &mut seen_icaos, "ICAO"),
+        ] {
+            if code == "N/A" {
+                continue;
+            }
+
+            match seen.get(code.as_str()) {
+                Some(first_id) => report(format!(
+                    "duplicate {kind} code: {code} first seen at id {first_id}"
+                )),
+                None => {
+                    seen.insert(code, airport.id);
+                }
+            }
+        }
+
+        if !ALTITUDE_RANGE_IN_METERS.contains(&airport.altitude) {
+            report(format!("altitude out of range: {} m", airport.altitude));
+        }
+
+        if let Some(reason) = suspicious_countries.get(airport.country.as_str()) {
+            report(format!(
+                "suspicious country name: {:?} {reason}",
+                airport.country
+            ));
+        }
+    }
+
+    findings
+}
+
+fn dms(degrees: i64, minutes: i64, seconds: i64, direction: &str) -> Dms {
+    Dms {
+        degrees,
+        minutes,
+        seconds,
+        direction: direction.chars().next().unwrap_or(' '),
+    }
+}
+
+/// Country names which are empty, contain unexpected characters or look like a typo of a
+/// country used by more airports, together with the reason.
+fn suspicious_countries(airports: &[StoredAirport]) -> HashMap<&str, String> {
+    let mut airports_per_country: HashMap<&str, usize> = HashMap::new();
+    for airport in airports.iter().filter(|airport| airport.name != "N/A") {
+        *airports_per_country.entry(&airport.country).or_insert(0) += 1;
+    }
+
+    let mut suspicious = HashMap::new();
+
+    for (&country, &count) in &airports_per_country {
+        if country.trim().is_empty() || country == "N/A" {
+            suspicious.insert(country, "is missing".to_owned());
+        } else if !country.chars().all(|c| {
+            c.is_alphabetic() || matches!(c, ' ' | '.' | ',' | '\'' | '&' | '-' | '/' | '(' | ')')
+        }) {
+            suspicious.insert(country, "contains unexpected characters".to_owned());
+        } else if country.matches('(').count() != country.matches(')').count() {
+            suspicious.insert(country, "has unbalanced parentheses".to_owned());
+        } else if count <= RARE_COUNTRY_AIRPORTS && country.chars().count() >= 5 {
+            let similar = airports_per_country
+                .iter()
+                .filter(|(other, other_count)| {
+                    **other_count > count && edit_distance(country, other) == 1
+                })
+                .max_by_key(|(_, other_count)| **other_count);
+
+            if let Some((other, _)) = similar {
+                suspicious.insert(country, format!("looks like a misspelling of {other:?}"));
+            }
+        }
+    }
+
+    suspicious
+}
+
+/// Optimal string alignment distance, i.e. the Levenshtein distance which also counts
+/// swapping two adjacent characters as a single edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = usize::from(a[i - 1] != b[j - 1]);
+
+            distances[i][j] = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + substitution);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distances[i][j] = distances[i][j].min(distances[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+/// Writes all findings into a CSV file with `id`, `iata_code`, `icao_code` and `issue`
+/// columns.
+pub fn write_findings(path: &Path, findings: &[Finding]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["id", "iata_code", "icao_code", "issue"])?;
+
+    for finding in findings {
+        writer.write_record([
+            finding.id.to_string().as_str(),
+            &finding.iata_code,
+            &finding.icao_code,
+            &finding.issue,
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn airport(id: i64, iata: &str, icao: &str, country: &str) -> StoredAirport {
+        StoredAirport {
+            id,
+            icao_code: icao.to_owned(),
+            iata_code: iata.to_owned(),
+            name: "GOROKA".to_owned(),
+            city: "GOROKA".to_owned(),
+            country: country.to_owned(),
+            lat_deg: 6,
+            lat_min: 4,
+            lat_sec: 54,
+            lat_dir: "S".to_owned(),
+            lon_deg: 145,
+            lon_min: 23,
+            lon_sec: 30,
+            lon_dir: "E".to_owned(),
+            altitude: 1610,
+            lat_decimal: -6.082,
+            lon_decimal: 145.392,
+        }
+    }
+
+    fn issues(findings: &[Finding]) -> Vec<(i64, &str)> {
+        findings
+            .iter()
+            .map(|finding| (finding.id, finding.issue.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_accepts_consistent_airports() {
+        let airports = vec![
+            airport(1, "GKA", "AYGA", "PAPUA NEW GUINEA"),
+            airport(2, "N/A", "AYGB", "PAPUA NEW GUINEA"),
+            airport(3, "N/A", "N/A", "PAPUA NEW GUINEA"),
+        ];
+
+        assert_eq!(audit(&airports), vec![]);
+    }
+
+    #[test]
+    fn test_reports_row_issues() {
+        let mut placeholder = airport(1, "N/A", "N/A", "N/A");
+        placeholder.name = "N/A".to_owned();
+
+        let mut null_island = airport(2, "AAA", "AAAA", "PAPUA NEW GUINEA");
+        null_island.lat_decimal = 0.;
+        null_island.lon_decimal = 0.;
+
+        let mut mismatched = airport(3, "BBB", "BBBB", "PAPUA NEW GUINEA");
+        mismatched.lat_decimal = 6.082;
+
+        let mut too_high = airport(4, "CCC", "CCCC", "PAPUA NEW GUINEA");
+        too_high.altitude = 12000;
+
+        let airports = vec![
+            placeholder,
+            null_island,
+            mismatched,
+            too_high,
+            airport(5, "AAA", "DDDD", "PAPUA NEW GUINEA"),
+            airport(6, "EEE", "AAAA", "PAPUA NEW GUINEA"),
+        ];
+
+        assert_eq!(
+            issues(&audit(&airports)),
+            vec![
+                (1, "placeholder row: name is N/A"),
+                (2, "null island coordinates: 0, 0"),
+                (
+                    3,
+                    "latitude DMS doesn't match decimal value: 6°4'54\"S vs 6.082"
+                ),
+                (4, "altitude out of range: 12000 m"),
+                (5, "duplicate IATA code: AAA first seen at id 2"),
+                (6, "duplicate ICAO code: AAAA first seen at id 2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_suspicious_countries() {
+        let airports = vec![
+            airport(1, "AAA", "AAAA", "ENGLAND"),
+            airport(2, "BBB", "BBBB", "ENGLAND"),
+            airport(3, "CCC", "CCCC", "ENGALND"),
+            airport(4, "DDD", "DDDD", "GALAPAGOS I. (ECUADOR"),
+            airport(5, "EEE", "EEEE", "FRANCE2"),
+            airport(6, "FFF", "FFFF", "ST.VINCENT/GRENADINES"),
+        ];
+
+        assert_eq!(
+            issues(&audit(&airports)),
+            vec![
+                (
+                    3,
+                    "suspicious country name: \"ENGALND\" looks like a misspelling of \"ENGLAND\""
+                ),
+                (
+                    4,
+                    "suspicious country name: \"GALAPAGOS I. (ECUADOR\" has unbalanced parentheses"
+                ),
+                (
+                    5,
+                    "suspicious country name: \"FRANCE2\" contains unexpected characters"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("ENGALND", "ENGLAND"), 1);
+        assert_eq!(edit_distance("POLAND", "POLAND"), 0);
+        assert_eq!(edit_distance("MALI", "MALTA"), 2);
+    }
+}
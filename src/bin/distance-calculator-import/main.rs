@@ -0,0 +1,406 @@
+mod audit;
+mod metadata;
+mod record;
+mod report;
+mod sources;
+mod validation;
+mod writer;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+
+use metadata::DatasetMetadata;
+use record::SourceRow;
+use sources::generic::ColumnMapping;
+
+/// Imports airport datasets into the SQLite `airports` schema used by the distance calculator
+/// and audits existing databases.
+#[derive(Debug, Parser)]
+#[command(name = "distance-calculator-import", version)]
+struct Cli {
+    /// Path of the SQLite database to create or audit.
+    #[arg(long, default_value = "./global_airports_database.sqlite")]
+    database: PathBuf,
+
+    /// Replace the database if it already exists, once the new one is imported. Stop the
+    /// service serving it first, or import into a new file and reload the service with it.
+    #[arg(long)]
+    overwrite: bool,
+
+    /// Write rejected rows with rejection reasons into given CSV file.
+    #[arg(long)]
+    rejected_report: Option<PathBuf>,
+
+    /// Version of the dataset stored in the metadata, defaults to the checksum prefix.
+    #[arg(long)]
+    dataset_version: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Import(Source),
+    /// Reports data quality issues of an existing database, such as DMS fields inconsistent
+    /// with decimal coordinates, duplicated codes or suspicious country names.
+    Audit {
+        /// Write all findings into given CSV file.
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum Source {
+    /// OurAirports `airports.csv`, see https://ourairports.com/data/.
+    Ourairports {
+        /// Path of `airports.csv`.
+        #[arg(long)]
+        airports: PathBuf,
+
+        /// Path of `countries.csv`, used to store country names instead of ISO codes.
+        #[arg(long)]
+        countries: Option<PathBuf>,
+
+        /// Import airports marked as closed.
+        #[arg(long)]
+        include_closed: bool,
+    },
+    /// Colon separated Partow global airport database, see
+    /// https://www.partow.net/miscellaneous/airportdatabase/.
+    Partow {
+        /// Path of `GlobalAirportDatabase.txt`.
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Any CSV file with a header row, mapped onto the `airports` schema.
+    Csv {
+        /// Path of the CSV file.
+        #[arg(long)]
+        input: PathBuf,
+
+        /// Mapping of a schema field onto a CSV column, e.g. `--map iata_code=IATA`.
+        #[arg(long = "map", value_name = "FIELD=COLUMN", required = true)]
+        mappings: Vec<String>,
+
+        /// Column delimiter.
+        #[arg(long, default_value_t = ',')]
+        delimiter: char,
+    },
+}
+
+fn read_source(source: &Source) -> Result<Vec<SourceRow>, String> {
+    match source {
+        Source::Ourairports {
+            airports,
+            countries,
+            include_closed,
+        } => {
+            let countries = match countries {
+                Some(path) => sources::ourairports::read_countries(open(path)?)
+                    .map_err(|e| format!("failed to read {}: {e}", path.display()))?,
+                None => HashMap::new(),
+            };
+
+            sources::ourairports::read(open(airports)?, &countries, *include_closed)
+                .map_err(|e| format!("failed to read {}: {e}", airports.display()))
+        }
+        Source::Partow { input } => sources::partow::read(BufReader::new(open(input)?))
+            .map_err(|e| format!("failed to read {}: {e}", input.display())),
+        Source::Csv {
+            input,
+            mappings,
+            delimiter,
+        } => {
+            let mapping = ColumnMapping::parse(mappings)?;
+            let delimiter = u8::try_from(*delimiter)
+                .map_err(|_| format!("delimiter has to be an ASCII character: {delimiter:?}"))?;
+
+            sources::generic::read(open(input)?, &mapping, delimiter)
+                .map_err(|e| format!("failed to read {}: {e}", input.display()))
+        }
+    }
+}
+
+impl Source {
+    fn name(&self) -> &'static str {
+        match self {
+            Source::Ourairports { .. } => "ourairports",
+            Source::Partow { .. } => "partow",
+            Source::Csv { .. } => "csv",
+        }
+    }
+
+    fn paths(&self) -> Vec<&Path> {
+        match self {
+            Source::Ourairports {
+                airports,
+                countries,
+                ..
+            } => [Some(airports), countries.as_ref()]
+                .into_iter()
+                .flatten()
+                .map(PathBuf::as_path)
+                .collect(),
+            Source::Partow { input } | Source::Csv { input, .. } => vec![input.as_path()],
+        }
+    }
+}
+
+fn open(path: &Path) -> Result<File, String> {
+    File::open(path).map_err(|e| format!("failed to open {}: {e}", path.display()))
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    match &cli.command {
+        Command::Import(source) => import(&cli, source).await,
+        Command::Audit { report } => audit(&cli.database, report.as_deref()).await,
+    }
+}
+
+async fn import(cli: &Cli, source: &Source) -> Result<(), String> {
+    if cli.database.exists() && !cli.overwrite {
+        return Err(format!(
+            "{} already exists, pass --overwrite to replace it",
+            cli.database.display()
+        ));
+    }
+
+    let rows = read_source(source)?;
+    let rows_count = rows.len();
+    let validated = validation::validate_rows(rows);
+
+    let checksum = metadata::checksum(&source.paths())
+        .map_err(|e| format!("failed to compute checksum: {e}"))?;
+    let dataset_metadata = DatasetMetadata {
+        source: source.name().to_owned(),
+        version: cli
+            .dataset_version
+            .clone()
+            .unwrap_or_else(|| checksum[..12].to_owned()),
+        imported_at: metadata::now(),
+        rows_read: rows_count as i64,
+        airports_count: validated.accepted.len() as i64,
+        rejected_count: validated.rejected.len() as i64,
+        checksum,
+    };
+
+    // the database may be served while it's imported, so it's only replaced once the new one
+    // is complete
+    let temp_database = temp_database_path(&cli.database);
+    remove_database_files(&temp_database)
+        .map_err(|e| format!("failed to remove {}: {e}", temp_database.display()))?;
+    if let Err(e) =
+        writer::write_database(&temp_database, &validated.accepted, &dataset_metadata).await
+    {
+        let _ = remove_database_files(&temp_database);
+        return Err(format!("failed to write {}: {e}", cli.database.display()));
+    }
+    if let Err(e) = replace_database(&temp_database, &cli.database) {
+        let _ = remove_database_files(&temp_database);
+        return Err(format!("failed to replace {}: {e}", cli.database.display()));
+    }
+
+    println!(
+        "Read {rows_count} rows, imported {} airports into {}, rejected {} rows.",
+        validated.accepted.len(),
+        cli.database.display(),
+        validated.rejected.len()
+    );
+    println!(
+        "Dataset {} version {}, checksum {}.",
+        dataset_metadata.source, dataset_metadata.version, dataset_metadata.checksum
+    );
+
+    let reasons = validated.rejected.iter().map(|row| row.reason.as_str());
+    for (reason, count) in report::summarize(reasons) {
+        println!("  {count:>6} {reason}");
+    }
+
+    if let Some(path) = &cli.rejected_report {
+        report::write_rejected(path, &validated.rejected)
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        println!("Rejected rows written to {}.", path.display());
+    }
+
+    Ok(())
+}
+
+/// Database next to `database` imports are written into, on the same file system so that
+/// it can be renamed over `database`.
+fn temp_database_path(database: &Path) -> PathBuf {
+    let file_name = database
+        .file_name()
+        .map_or_else(|| "airports.sqlite".into(), |name| name.to_string_lossy());
+
+    database.with_file_name(format!(".{file_name}.import-{}", std::process::id()))
+}
+
+/// Files SQLite keeps next to `database`, left over by connections which didn't close cleanly.
+fn sidecar_paths(database: &Path) -> [PathBuf; 3] {
+    ["-wal", "-shm", "-journal"].map(|suffix| {
+        let mut path = database.as_os_str().to_owned();
+        path.push(suffix);
+        PathBuf::from(path)
+    })
+}
+
+fn remove_database_files(database: &Path) -> std::io::Result<()> {
+    for path in std::iter::once(database.to_owned()).chain(sidecar_paths(database)) {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the imported database over `database` and removes the WAL and shared memory files
+/// of the replaced one, which SQLite would otherwise apply to the new database. Connections
+/// still open to the replaced database would lose them too, so it mustn't be served.
+fn replace_database(imported: &Path, database: &Path) -> std::io::Result<()> {
+    std::fs::rename(imported, database)?;
+
+    for path in sidecar_paths(database) {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn audit(database: &Path, report: Option<&Path>) -> Result<(), String> {
+    let airports = audit::read_airports(database)
+        .await
+        .map_err(|e| format!("failed to read {}: {e}", database.display()))?;
+    let findings = audit::audit(&airports);
+
+    println!(
+        "Audited {} airports in {}, found {} issues.",
+        airports.len(),
+        database.display(),
+        findings.len()
+    );
+
+    for (issue, count) in report::summarize(findings.iter().map(|finding| finding.issue.as_str())) {
+        println!("  {count:>6} {issue}");
+    }
+
+    if let Some(path) = report {
+        audit::write_findings(path, &findings)
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        println!("Findings written to {}.", path.display());
+    }
+
+    Ok(())
+}
+
+#[actix_web::main]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csv_import(database: &Path, input: &Path) -> Cli {
+        Cli {
+            database: database.to_owned(),
+            overwrite: true,
+            rejected_report: None,
+            dataset_version: None,
+            command: Command::Import(Source::Csv {
+                input: input.to_owned(),
+                mappings: [
+                    "iata_code=IATA",
+                    "name=Airport",
+                    "lat_decimal=Lat",
+                    "lon_decimal=Lon",
+                ]
+                .map(str::to_owned)
+                .to_vec(),
+                delimiter: ',',
+            }),
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("import-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[actix_web::test]
+    async fn test_failed_import_keeps_existing_database() {
+        let dir = test_dir("failed");
+        // a non-empty directory can't be replaced, so the import fails once the temporary
+        // database is written
+        let database = dir.join("airports.sqlite");
+        std::fs::create_dir(&database).unwrap();
+        std::fs::write(database.join("existing"), "existing database").unwrap();
+        let input = dir.join("airports.csv");
+        std::fs::write(&input, "IATA,Airport,Lat,Lon\nKRK,Balice,50.0777,19.7848\n").unwrap();
+
+        let cli = csv_import(&database, &input);
+        let Command::Import(source) = &cli.command else {
+            unreachable!()
+        };
+        let error = import(&cli, source).await.unwrap_err();
+        assert!(error.starts_with("failed to replace"), "{error}");
+
+        assert_eq!(
+            std::fs::read(database.join("existing")).unwrap(),
+            b"existing database"
+        );
+        let mut files = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, vec!["airports.csv", "airports.sqlite"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_import_replaces_database_and_its_wal() {
+        let dir = test_dir("replaced");
+        let database = dir.join("airports.sqlite");
+        std::fs::write(&database, "existing database").unwrap();
+        for sidecar in sidecar_paths(&database) {
+            std::fs::write(sidecar, "stale").unwrap();
+        }
+        let input = dir.join("airports.csv");
+        std::fs::write(&input, "IATA,Airport,Lat,Lon\nKRK,Balice,50.0777,19.7848\n").unwrap();
+
+        let cli = csv_import(&database, &input);
+        let Command::Import(source) = &cli.command else {
+            unreachable!()
+        };
+        import(&cli, source).await.unwrap();
+
+        let airports = audit::read_airports(&database).await.unwrap();
+        assert_eq!(airports.len(), 1);
+        for sidecar in sidecar_paths(&database) {
+            assert!(!sidecar.exists(), "{}", sidecar.display());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+/// Describes an imported dataset, stored in the `dataset_metadata` table so deployments can
+/// tell which data they serve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetMetadata {
+    pub source: String,
+    pub version: String,
+    /// Unix timestamp of the import.
+    pub imported_at: i64,
+    pub rows_read: i64,
+    pub airports_count: i64,
+    pub rejected_count: i64,
+    /// Hex encoded SHA-256 of the source files, in the order they were read.
+    pub checksum: String,
+}
+
+/// Computes SHA-256 over the contents of all `paths`.
+pub fn checksum(paths: &[&Path]) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+
+    for path in paths {
+        io::copy(&mut File::open(path)?, &mut hasher)?;
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_covers_all_files() {
+        let dir = std::env::temp_dir().join(format!("import-checksum-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.csv");
+        let second = dir.join("second.csv");
+        std::fs::write(&first, "abc").unwrap();
+        std::fs::write(&second, "def").unwrap();
+
+        // SHA-256 of "abc" and "abcdef"
+        assert_eq!(
+            checksum(&[&first]).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            checksum(&[&first, &second]).unwrap(),
+            "bef57ec7f53a6d40beb640a780a639c83bc29ac8a9816f1fc6c5c6dcd93c4721"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
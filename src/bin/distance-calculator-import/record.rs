@@ -0,0 +1,132 @@
+use std::fmt;
+
+/// Maximum difference in degrees allowed between DMS fields and their decimal counterpart.
+/// Partow dataset stores decimals rounded to 3 places, which gives up to ~2 arc seconds of
+/// difference, so anything bigger than that is treated as an inconsistency.
+pub const DMS_TOLERANCE_IN_DEGREES: f64 = 0.001;
+
+/// Coordinate expressed in degrees, minutes, seconds and hemisphere direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dms {
+    pub degrees: i64,
+    pub minutes: i64,
+    pub seconds: i64,
+    pub direction: char,
+}
+
+impl Dms {
+    pub fn latitude_from_decimal(value: f64) -> Self {
+        Self::from_decimal(value, 'N', 'S')
+    }
+
+    pub fn longitude_from_decimal(value: f64) -> Self {
+        Self::from_decimal(value, 'E', 'W')
+    }
+
+    fn from_decimal(value: f64, positive: char, negative: char) -> Self {
+        let total_seconds = (value.abs() * 3600.).round() as i64;
+
+        Self {
+            degrees: total_seconds / 3600,
+            minutes: total_seconds % 3600 / 60,
+            seconds: total_seconds % 60,
+            direction: if value < 0. { negative } else { positive },
+        }
+    }
+
+    pub fn to_decimal(self) -> f64 {
+        let value = self.degrees as f64 + self.minutes as f64 / 60. + self.seconds as f64 / 3600.;
+
+        match self.direction {
+            'S' | 'W' => -value,
+            _ => value,
+        }
+    }
+}
+
+impl fmt::Display for Dms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}°{}'{}\"{}",
+            self.degrees, self.minutes, self.seconds, self.direction
+        )
+    }
+}
+
+/// Airport parsed from one of the supported sources, before it's validated and written
+/// into the `airports` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AirportRecord {
+    pub icao_code: Option<String>,
+    pub iata_code: Option<String>,
+    pub name: String,
+    pub city: String,
+    pub country: String,
+    /// Altitude in meters.
+    pub altitude: i64,
+    pub lat_decimal: f64,
+    pub lon_decimal: f64,
+    pub lat_dms: Option<Dms>,
+    pub lon_dms: Option<Dms>,
+}
+
+/// Source row which couldn't be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedRow {
+    pub line: usize,
+    pub reason: String,
+    pub raw: String,
+}
+
+/// Single row read from a source, either parsed successfully or rejected right away.
+#[derive(Debug)]
+pub struct SourceRow {
+    pub line: usize,
+    pub raw: String,
+    pub record: Result<AirportRecord, String>,
+}
+
+/// Treats empty strings and `N/A` placeholders as missing values.
+pub fn optional_field(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    match value.is_empty() || value == "N/A" {
+        true => None,
+        false => Some(value.to_uppercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_converts_dms_to_decimal_and_back() {
+        let dms = Dms::latitude_from_decimal(-6.0817);
+
+        assert_eq!(
+            dms,
+            Dms {
+                degrees: 6,
+                minutes: 4,
+                seconds: 54,
+                direction: 'S'
+            }
+        );
+        assert!((dms.to_decimal() + 6.0817).abs() < DMS_TOLERANCE_IN_DEGREES);
+    }
+
+    #[test]
+    fn test_longitude_directions() {
+        assert_eq!(Dms::longitude_from_decimal(145.392).direction, 'E');
+        assert_eq!(Dms::longitude_from_decimal(-0.461).direction, 'W');
+    }
+
+    #[test]
+    fn test_optional_field() {
+        assert_eq!(optional_field(" lhr "), Some("LHR".to_owned()));
+        assert_eq!(optional_field("N/A"), None);
+        assert_eq!(optional_field(""), None);
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::record::RejectedRow;
+
+/// Groups rejection reasons or audit issues by their kind, i.e. the part before the colon,
+/// dropping row specific details such as the offending values, so the summary stays readable.
+pub fn summarize<'a>(reasons: impl IntoIterator<Item = &'a str>) -> BTreeMap<String, usize> {
+    let mut summary = BTreeMap::new();
+
+    for reason in reasons {
+        let kind = reason.split(':').next().unwrap_or_default().to_owned();
+
+        *summary.entry(kind).or_insert(0) += 1;
+    }
+
+    summary
+}
+
+/// Writes all rejected rows into a CSV file with `line`, `reason` and `row` columns.
+pub fn write_rejected(path: &Path, rejected: &[RejectedRow]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["line", "reason", "row"])?;
+
+    for row in rejected {
+        writer.write_record([row.line.to_string().as_str(), &row.reason, &row.raw])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarizes_reasons_by_kind() {
+        let summary = summarize([
+            "invalid IATA code: \"X\"",
+            "invalid IATA code: \"Y\"",
+            "duplicate ICAO code: first seen at line 3",
+        ]);
+
+        assert_eq!(summary.get("invalid IATA code"), Some(&2));
+        assert_eq!(summary.get("duplicate ICAO code"), Some(&1));
+    }
+}
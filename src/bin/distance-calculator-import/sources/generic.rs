@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use csv::StringRecord;
+
+use crate::record::{optional_field, AirportRecord, Dms, SourceRow};
+
+/// Fields of the `airports` schema which can be mapped onto columns of a generic CSV file.
+pub const MAPPABLE_FIELDS: &[&str] = &[
+    "icao_code",
+    "iata_code",
+    "name",
+    "city",
+    "country",
+    "altitude",
+    "lat_decimal",
+    "lon_decimal",
+    "lat_deg",
+    "lat_min",
+    "lat_sec",
+    "lat_dir",
+    "lon_deg",
+    "lon_min",
+    "lon_sec",
+    "lon_dir",
+];
+
+const REQUIRED_FIELDS: &[&str] = &["name", "lat_decimal", "lon_decimal"];
+
+/// Mapping from `airports` schema fields to column names of the source file.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnMapping(HashMap<String, String>);
+
+impl ColumnMapping {
+    /// Parses `field=column` pairs, e.g. `iata_code=IATA`.
+    pub fn parse(pairs: &[String]) -> Result<Self, String> {
+        let mut mapping = HashMap::new();
+
+        for pair in pairs {
+            let (field, column) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("invalid mapping {pair:?}, expected field=column"))?;
+
+            if !MAPPABLE_FIELDS.contains(&field) {
+                return Err(format!(
+                    "unknown field {field:?}, expected one of: {}",
+                    MAPPABLE_FIELDS.join(", ")
+                ));
+            }
+
+            mapping.insert(field.to_owned(), column.to_owned());
+        }
+
+        for field in REQUIRED_FIELDS {
+            if !mapping.contains_key(*field) {
+                return Err(format!("missing mapping for required field {field:?}"));
+            }
+        }
+
+        if !mapping.contains_key("iata_code") && !mapping.contains_key("icao_code") {
+            return Err("at least one of iata_code or icao_code has to be mapped".to_owned());
+        }
+
+        Ok(Self(mapping))
+    }
+
+    fn resolve(&self, headers: &StringRecord) -> Result<HashMap<String, usize>, String> {
+        self.0
+            .iter()
+            .map(|(field, column)| {
+                headers
+                    .iter()
+                    .position(|header| header == column)
+                    .map(|idx| (field.to_owned(), idx))
+                    .ok_or_else(|| format!("column {column:?} not found in the file"))
+            })
+            .collect()
+    }
+}
+
+/// Reads any CSV file with a header row, using `mapping` to find the schema fields.
+pub fn read(
+    reader: impl Read,
+    mapping: &ColumnMapping,
+    delimiter: u8,
+) -> Result<Vec<SourceRow>, String> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(reader);
+    let headers = csv_reader.headers().map_err(|e| e.to_string())?.clone();
+    let columns = mapping.resolve(&headers)?;
+    let mut rows = vec![];
+
+    for result in csv_reader.records() {
+        let record = result.map_err(|e| e.to_string())?;
+
+        rows.push(SourceRow {
+            line: record
+                .position()
+                .map_or(0, |position| position.line() as usize),
+            raw: record.iter().collect::<Vec<_>>().join(","),
+            record: parse_record(&record, &columns),
+        });
+    }
+
+    Ok(rows)
+}
+
+fn parse_record(
+    record: &StringRecord,
+    columns: &HashMap<String, usize>,
+) -> Result<AirportRecord, String> {
+    let field = |name: &str| -> &str {
+        columns
+            .get(name)
+            .and_then(|idx| record.get(*idx))
+            .map_or("", str::trim)
+    };
+
+    Ok(AirportRecord {
+        icao_code: optional_field(field("icao_code")),
+        iata_code: optional_field(field("iata_code")),
+        name: optional_field(field("name")).unwrap_or_default(),
+        city: optional_field(field("city")).unwrap_or_default(),
+        country: optional_field(field("country")).unwrap_or_default(),
+        altitude: parse_optional_number(field("altitude"), "altitude")?.unwrap_or(0),
+        lat_decimal: parse_number(field("lat_decimal"), "lat_decimal")?,
+        lon_decimal: parse_number(field("lon_decimal"), "lon_decimal")?,
+        lat_dms: parse_dms(&field, "lat")?,
+        lon_dms: parse_dms(&field, "lon")?,
+    })
+}
+
+fn parse_dms<'a>(field: &impl Fn(&str) -> &'a str, prefix: &str) -> Result<Option<Dms>, String> {
+    let direction = field(&format!("{prefix}_dir"));
+
+    let Some(direction) = direction.chars().next() else {
+        return Ok(None);
+    };
+
+    let component = |name: &str| -> Result<i64, String> {
+        let name = format!("{prefix}_{name}");
+        Ok(parse_optional_number(field(&name), &name)?.unwrap_or(0))
+    };
+
+    Ok(Some(Dms {
+        degrees: component("deg")?,
+        minutes: component("min")?,
+        seconds: component("sec")?,
+        direction: direction.to_ascii_uppercase(),
+    }))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    parse_optional_number(value, name)?.ok_or_else(|| format!("missing {name} value"))
+}
+
+fn parse_optional_number<T: std::str::FromStr>(
+    value: &str,
+    name: &str,
+) -> Result<Option<T>, String> {
+    match value.is_empty() {
+        true => Ok(None),
+        false => value
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| format!("invalid {name} value: {value:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping() -> ColumnMapping {
+        ColumnMapping::parse(&[
+            "iata_code=IATA".to_owned(),
+            "name=Airport".to_owned(),
+            "lat_decimal=Lat".to_owned(),
+            "lon_decimal=Lon".to_owned(),
+            "lat_deg=LatD".to_owned(),
+            "lat_min=LatM".to_owned(),
+            "lat_sec=LatS".to_owned(),
+            "lat_dir=LatH".to_owned(),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reads_mapped_columns() {
+        let input = "IATA;Airport;Lat;Lon;LatD;LatM;LatS;LatH\n\
+                     krk;Balice;50.0777;19.7848;50;4;40;N\n\
+                     waw;Chopin;;20.9671;;;;\n";
+
+        let rows = read(input.as_bytes(), &mapping(), b';').unwrap();
+
+        let balice = rows[0].record.as_ref().unwrap();
+        assert_eq!(balice.iata_code.as_deref(), Some("KRK"));
+        assert_eq!(balice.name, "BALICE");
+        assert_eq!(balice.lat_dms.unwrap().minutes, 4);
+        assert_eq!(balice.lon_dms, None);
+
+        assert_eq!(rows[1].record, Err("missing lat_decimal value".to_owned()));
+    }
+
+    #[test]
+    fn test_rejects_incomplete_mapping() {
+        assert!(ColumnMapping::parse(&["name=Airport".to_owned()]).is_err());
+        assert!(ColumnMapping::parse(&["unknown=Column".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_fails_when_mapped_column_is_missing() {
+        let input = "IATA;Airport\nKRK;Balice\n";
+
+        assert!(read(input.as_bytes(), &mapping(), b';').is_err());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod generic;
+pub mod ourairports;
+pub mod partow;
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use serde::Deserialize;
+
+use crate::record::{optional_field, AirportRecord, SourceRow};
+
+const FEET_TO_METERS: f64 = 0.3048;
+
+/// Row of `airports.csv` from https://ourairports.com/data/, columns not needed by the
+/// `airports` schema are skipped.
+#[derive(Debug, Deserialize)]
+struct OurAirportsRow {
+    ident: String,
+    #[serde(rename = "type")]
+    airport_type: String,
+    name: String,
+    latitude_deg: f64,
+    longitude_deg: f64,
+    elevation_ft: Option<f64>,
+    iso_country: String,
+    municipality: String,
+    gps_code: String,
+    iata_code: String,
+}
+
+/// Row of `countries.csv` from https://ourairports.com/data/.
+#[derive(Debug, Deserialize)]
+struct OurAirportsCountry {
+    code: String,
+    name: String,
+}
+
+/// Reads `countries.csv` into a mapping from ISO country code to country name.
+pub fn read_countries(reader: impl Read) -> Result<HashMap<String, String>, csv::Error> {
+    csv::Reader::from_reader(reader)
+        .deserialize::<OurAirportsCountry>()
+        .map(|country| country.map(|country| (country.code, country.name.to_uppercase())))
+        .collect()
+}
+
+/// Reads `airports.csv`, countries are stored as names when `countries` are provided and as
+/// ISO codes otherwise. Closed airports are skipped unless `include_closed` is set.
+pub fn read(
+    reader: impl Read,
+    countries: &HashMap<String, String>,
+    include_closed: bool,
+) -> Result<Vec<SourceRow>, csv::Error> {
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let headers = csv_reader.headers()?.clone();
+    let mut rows = vec![];
+
+    for result in csv_reader.records() {
+        let record = result?;
+        let line = record
+            .position()
+            .map_or(0, |position| position.line() as usize);
+        let raw = record.iter().collect::<Vec<_>>().join(",");
+
+        let row = match record.deserialize::<OurAirportsRow>(Some(&headers)) {
+            Ok(row) => row,
+            Err(e) => {
+                rows.push(SourceRow {
+                    line,
+                    raw,
+                    record: Err(e.to_string()),
+                });
+                continue;
+            }
+        };
+
+        if row.airport_type == "closed" && !include_closed {
+            continue;
+        }
+
+        rows.push(SourceRow {
+            line,
+            raw,
+            record: Ok(to_record(row, countries)),
+        });
+    }
+
+    Ok(rows)
+}
+
+fn to_record(row: OurAirportsRow, countries: &HashMap<String, String>) -> AirportRecord {
+    // `ident` is the ICAO code for most airports, but falls back to local identifiers
+    // (e.g. `00AK`), `gps_code` is the more reliable source of the ICAO code
+    let icao_code = [&row.gps_code, &row.ident].into_iter().find_map(|code| {
+        optional_field(code)
+            .filter(|code| code.len() == 4 && code.chars().all(|c| c.is_ascii_alphabetic()))
+    });
+
+    AirportRecord {
+        icao_code,
+        iata_code: optional_field(&row.iata_code),
+        name: row.name.trim().to_uppercase(),
+        city: row.municipality.trim().to_uppercase(),
+        country: countries
+            .get(&row.iso_country)
+            .cloned()
+            .unwrap_or(row.iso_country),
+        altitude: row
+            .elevation_ft
+            .map_or(0, |elevation| (elevation * FEET_TO_METERS).round() as i64),
+        lat_decimal: row.latitude_deg,
+        lon_decimal: row.longitude_deg,
+        lat_dms: None,
+        lon_dms: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AIRPORTS: &str = r#""id","ident","type","name","latitude_deg","longitude_deg","elevation_ft","continent","iso_country","iso_region","municipality","scheduled_service","gps_code","iata_code","local_code","home_link","wikipedia_link","keywords"
+2434,"EGLL","large_airport","London Heathrow Airport",51.4706,-0.461941,83,"EU","GB","GB-ENG","London","yes","EGLL","LHR",,"https://www.heathrow.com/","https://en.wikipedia.org/wiki/Heathrow_Airport","LON, Londres"
+6523,"00A","heliport","Total RF Heliport",40.070985,-74.933689,11,"NA","US","US-PA","Bensalem","no","K00A",,"00A","https://www.penndot.pa.gov/",,
+1,"XXXX","closed","Closed Field",10,10,,"EU","GB","GB-ENG","Nowhere","no",,,,,,
+2,"YYYY","small_airport","Broken",not-a-number,10,,"EU","GB","GB-ENG","Nowhere","no",,,,,,
+"#;
+
+    const COUNTRIES: &str = r#""id","code","name","continent","wikipedia_link","keywords"
+302672,"GB","United Kingdom","EU","https://en.wikipedia.org/wiki/United_Kingdom","Great Britain"
+"#;
+
+    #[test]
+    fn test_reads_ourairports_rows() {
+        let countries = read_countries(COUNTRIES.as_bytes()).unwrap();
+        let rows = read(AIRPORTS.as_bytes(), &countries, false).unwrap();
+
+        assert_eq!(rows.len(), 3);
+
+        let heathrow = rows[0].record.as_ref().unwrap();
+        assert_eq!(heathrow.iata_code.as_deref(), Some("LHR"));
+        assert_eq!(heathrow.icao_code.as_deref(), Some("EGLL"));
+        assert_eq!(heathrow.name, "LONDON HEATHROW AIRPORT");
+        assert_eq!(heathrow.country, "UNITED KINGDOM");
+        assert_eq!(heathrow.altitude, 25);
+
+        let heliport = rows[1].record.as_ref().unwrap();
+        assert_eq!(heliport.iata_code, None);
+        assert_eq!(heliport.icao_code, None);
+        assert_eq!(heliport.country, "US");
+
+        assert!(rows[2].record.is_err());
+    }
+
+    #[test]
+    fn test_includes_closed_airports_on_request() {
+        let rows = read(AIRPORTS.as_bytes(), &HashMap::new(), true).unwrap();
+
+        assert_eq!(rows.len(), 4);
+    }
+}
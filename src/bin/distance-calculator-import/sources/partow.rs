@@ -0,0 +1,106 @@
+use std::io::BufRead;
+
+use crate::record::{optional_field, AirportRecord, Dms, SourceRow};
+
+const FIELDS_COUNT: usize = 16;
+
+/// Reads the colon separated format of https://www.partow.net/miscellaneous/airportdatabase/,
+/// e.g. `AYGA:GKA:GOROKA:GOROKA:PAPUA NEW GUINEA:006:004:054:S:145:023:030:E:01610:-6.082:145.392`.
+pub fn read(reader: impl BufRead) -> Result<Vec<SourceRow>, std::io::Error> {
+    let mut rows = vec![];
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        rows.push(SourceRow {
+            line: idx + 1,
+            record: parse_line(&line),
+            raw: line,
+        });
+    }
+
+    Ok(rows)
+}
+
+fn parse_line(line: &str) -> Result<AirportRecord, String> {
+    let fields = line.split(':').map(str::trim).collect::<Vec<_>>();
+
+    if fields.len() != FIELDS_COUNT {
+        return Err(format!(
+            "expected {FIELDS_COUNT} fields, found {}",
+            fields.len()
+        ));
+    }
+
+    let lat_dms = parse_dms(&fields[5..9], "latitude")?;
+    let lon_dms = parse_dms(&fields[9..13], "longitude")?;
+
+    Ok(AirportRecord {
+        icao_code: optional_field(fields[0]),
+        iata_code: optional_field(fields[1]),
+        name: optional_field(fields[2]).unwrap_or_default(),
+        city: optional_field(fields[3]).unwrap_or_default(),
+        country: optional_field(fields[4]).unwrap_or_default(),
+        altitude: parse_number(fields[13], "altitude")?,
+        lat_decimal: parse_number(fields[14], "latitude")?,
+        lon_decimal: parse_number(fields[15], "longitude")?,
+        lat_dms,
+        lon_dms,
+    })
+}
+
+/// Partow marks unknown coordinates with the `U` direction, these are reported as missing.
+fn parse_dms(fields: &[&str], name: &str) -> Result<Option<Dms>, String> {
+    let direction = match fields[3].chars().next() {
+        Some('U') | None => return Ok(None),
+        Some(direction) => direction.to_ascii_uppercase(),
+    };
+
+    Ok(Some(Dms {
+        degrees: parse_number(fields[0], name)?,
+        minutes: parse_number(fields[1], name)?,
+        seconds: parse_number(fields[2], name)?,
+        direction,
+    }))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("invalid {name} value: {value:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_partow_rows() {
+        let input = "AYGA:GKA:GOROKA:GOROKA:PAPUA NEW GUINEA:006:004:054:S:145:023:030:E:01610:-6.082:145.392\n\
+                     \n\
+                     AYLA:LAE:N/A:LAE:PAPUA NEW GUINEA:000:000:000:U:000:000:000:U:00000:0.000:0.000\n\
+                     BROKEN:LINE\n";
+
+        let rows = read(input.as_bytes()).unwrap();
+
+        assert_eq!(rows.len(), 3);
+
+        let goroka = rows[0].record.as_ref().unwrap();
+        assert_eq!(goroka.iata_code.as_deref(), Some("GKA"));
+        assert_eq!(goroka.icao_code.as_deref(), Some("AYGA"));
+        assert_eq!(goroka.altitude, 1610);
+        assert_eq!(goroka.lat_dms.unwrap().direction, 'S');
+        assert_eq!(goroka.lon_decimal, 145.392);
+
+        let lae = rows[1].record.as_ref().unwrap();
+        assert_eq!(rows[1].line, 3);
+        assert_eq!(lae.name, "");
+        assert_eq!(lae.lat_dms, None);
+
+        assert!(rows[2].record.is_err());
+    }
+}
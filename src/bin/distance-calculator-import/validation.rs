@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use crate::record::{AirportRecord, Dms, RejectedRow, SourceRow, DMS_TOLERANCE_IN_DEGREES};
+
+/// Result of validating and deduplicating source rows.
+#[derive(Debug, Default)]
+pub struct ValidatedRows {
+    pub accepted: Vec<AirportRecord>,
+    pub rejected: Vec<RejectedRow>,
+}
+
+/// Validates every row and drops duplicates, keeping the first occurrence of each IATA and
+/// ICAO code.
+pub fn validate_rows(rows: Vec<SourceRow>) -> ValidatedRows {
+    let mut validated = ValidatedRows::default();
+    let mut seen_iatas: HashMap<String, usize> = HashMap::new();
+    let mut seen_icaos: HashMap<String, usize> = HashMap::new();
+
+    for row in rows {
+        let result = row.record.and_then(|record| {
+            validate_record(&record)?;
+
+            if let Some(line) = record
+                .iata_code
+                .as_ref()
+                .and_then(|iata| seen_iatas.get(iata))
+            {
+                return Err(format!("duplicate IATA code: first seen at line {line}"));
+            }
+
+            if let Some(line) = record
+                .icao_code
+                .as_ref()
+                .and_then(|icao| seen_icaos.get(icao))
+            {
+                return Err(format!("duplicate ICAO code: first seen at line {line}"));
+            }
+
+            Ok(record)
+        });
+
+        match result {
+            Ok(record) => {
+                if let Some(iata) = &record.iata_code {
+                    seen_iatas.insert(iata.to_owned(), row.line);
+                }
+                if let Some(icao) = &record.icao_code {
+                    seen_icaos.insert(icao.to_owned(), row.line);
+                }
+
+                validated.accepted.push(record);
+            }
+            Err(reason) => validated.rejected.push(RejectedRow {
+                line: row.line,
+                reason,
+                raw: row.raw,
+            }),
+        }
+    }
+
+    validated
+}
+
+pub fn validate_record(record: &AirportRecord) -> Result<(), String> {
+    if record.iata_code.is_none() && record.icao_code.is_none() {
+        return Err("neither IATA nor ICAO code present".to_owned());
+    }
+
+    if let Some(iata) = &record.iata_code {
+        if iata.len() != 3 || !iata.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("invalid IATA code: {iata:?}"));
+        }
+    }
+
+    if let Some(icao) = &record.icao_code {
+        if icao.len() != 4 || !icao.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("invalid ICAO code: {icao:?}"));
+        }
+    }
+
+    if record.name.is_empty() {
+        return Err("missing airport name".to_owned());
+    }
+
+    if !(-90. ..=90.).contains(&record.lat_decimal) {
+        return Err(format!("latitude out of range: {}", record.lat_decimal));
+    }
+
+    if !(-180. ..=180.).contains(&record.lon_decimal) {
+        return Err(format!("longitude out of range: {}", record.lon_decimal));
+    }
+
+    if record.lat_decimal == 0. && record.lon_decimal == 0. {
+        return Err("missing coordinates".to_owned());
+    }
+
+    validate_dms(
+        record.lat_dms,
+        record.lat_decimal,
+        &['N', 'S'],
+        90,
+        "latitude",
+    )?;
+    validate_dms(
+        record.lon_dms,
+        record.lon_decimal,
+        &['E', 'W'],
+        180,
+        "longitude",
+    )?;
+
+    Ok(())
+}
+
+/// Checks that DMS fields, when the source provides them, describe the same point as the
+/// decimal coordinate.
+pub fn validate_dms(
+    dms: Option<Dms>,
+    decimal: f64,
+    directions: &[char],
+    max_degrees: i64,
+    name: &str,
+) -> Result<(), String> {
+    let Some(dms) = dms else {
+        return Ok(());
+    };
+
+    if !directions.contains(&dms.direction) {
+        return Err(format!("invalid {name} direction: {:?}", dms.direction));
+    }
+
+    if !(0..=max_degrees).contains(&dms.degrees)
+        || !(0..60).contains(&dms.minutes)
+        || !(0..60).contains(&dms.seconds)
+    {
+        return Err(format!("{name} DMS out of range: {dms}"));
+    }
+
+    if (dms.to_decimal() - decimal).abs() > DMS_TOLERANCE_IN_DEGREES {
+        return Err(format!(
+            "{name} DMS doesn't match decimal value: {dms} vs {decimal}"
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(iata: &str, icao: &str) -> AirportRecord {
+        AirportRecord {
+            icao_code: Some(icao.to_owned()),
+            iata_code: Some(iata.to_owned()),
+            name: "GOROKA".to_owned(),
+            city: "GOROKA".to_owned(),
+            country: "PAPUA NEW GUINEA".to_owned(),
+            altitude: 1610,
+            lat_decimal: -6.082,
+            lon_decimal: 145.392,
+            lat_dms: Some(Dms {
+                degrees: 6,
+                minutes: 4,
+                seconds: 54,
+                direction: 'S',
+            }),
+            lon_dms: None,
+        }
+    }
+
+    fn row(line: usize, record: Result<AirportRecord, String>) -> SourceRow {
+        SourceRow {
+            line,
+            raw: format!("row {line}"),
+            record,
+        }
+    }
+
+    #[test]
+    fn test_accepts_valid_record() {
+        assert_eq!(validate_record(&record("GKA", "AYGA")), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_dms_mismatch() {
+        let mut mismatched = record("GKA", "AYGA");
+        mismatched.lat_decimal = 6.082;
+
+        let error = validate_record(&mismatched).unwrap_err();
+        assert!(error.starts_with("latitude DMS doesn't match decimal value"));
+    }
+
+    #[test]
+    fn test_rejects_missing_coordinates() {
+        let mut missing = record("GKA", "AYGA");
+        missing.lat_decimal = 0.;
+        missing.lon_decimal = 0.;
+        missing.lat_dms = None;
+
+        assert_eq!(
+            validate_record(&missing),
+            Err("missing coordinates".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_deduplicates_rows() {
+        let validated = validate_rows(vec![
+            row(1, Ok(record("GKA", "AYGA"))),
+            row(2, Ok(record("GKA", "AYGB"))),
+            row(3, Ok(record("GKB", "AYGA"))),
+            row(4, Err("broken".to_owned())),
+            row(5, Ok(record("GKC", "AYGC"))),
+        ]);
+
+        assert_eq!(validated.accepted.len(), 2);
+        assert_eq!(
+            validated
+                .rejected
+                .iter()
+                .map(|rejected| (rejected.line, rejected.reason.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (2, "duplicate IATA code: first seen at line 1"),
+                (3, "duplicate ICAO code: first seen at line 1"),
+                (4, "broken"),
+            ]
+        );
+    }
+}
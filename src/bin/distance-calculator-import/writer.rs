@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Connection, SqliteConnection};
+
+use crate::metadata::DatasetMetadata;
+use crate::record::{AirportRecord, Dms};
+
+/// Schema of the `airports` table as read by `GlobalAirportsRepository`, missing codes and
+/// names are stored as `N/A` the same way the bundled Partow database does.
+const CREATE_AIRPORTS_TABLE: &str = "
+    CREATE TABLE airports
+    (
+        id          integer primary key autoincrement,
+        icao_code   char(4),
+        iata_code   char(3),
+        name        varchar(50),
+        city        varchar(50),
+        country     varchar(50),
+        lat_deg     int,
+        lat_min     int,
+        lat_sec     int,
+        lat_dir     char(1),
+        lon_deg     int,
+        lon_min     int,
+        lon_sec     int,
+        lon_dir     char(1),
+        altitude    int,
+        lat_decimal double,
+        lon_decimal double
+    )";
+
+const CREATE_IATA_INDEX: &str = "CREATE INDEX idx_airports_iata ON airports(iata_code)";
+
+const CREATE_METADATA_TABLE: &str = "
+    CREATE TABLE dataset_metadata
+    (
+        source         varchar(50) not null,
+        version        varchar(50) not null,
+        imported_at    int         not null,
+        rows_read      int         not null,
+        airports_count int         not null,
+        rejected_count int         not null,
+        checksum       char(64)    not null
+    )";
+
+const INSERT_METADATA: &str = "
+    INSERT INTO dataset_metadata (
+        source, version, imported_at, rows_read, airports_count, rejected_count, checksum
+    ) VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+const INSERT_AIRPORT: &str = "
+    INSERT INTO airports (
+        icao_code, iata_code, name, city, country,
+        lat_deg, lat_min, lat_sec, lat_dir,
+        lon_deg, lon_min, lon_sec, lon_dir,
+        altitude, lat_decimal, lon_decimal
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+const MISSING_VALUE: &str = "N/A";
+
+/// Creates a new airports database at `path` and writes all `records` together with the
+/// dataset `metadata` in one transaction.
+pub async fn write_database(
+    path: &Path,
+    records: &[AirportRecord],
+    metadata: &DatasetMetadata,
+) -> Result<(), sqlx::Error> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    let mut connection = SqliteConnection::connect_with(&options).await?;
+    let mut transaction = connection.begin().await?;
+
+    sqlx::query(CREATE_AIRPORTS_TABLE)
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query(CREATE_IATA_INDEX)
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query(CREATE_METADATA_TABLE)
+        .execute(&mut transaction)
+        .await?;
+
+    sqlx::query(INSERT_METADATA)
+        .bind(&metadata.source)
+        .bind(&metadata.version)
+        .bind(metadata.imported_at)
+        .bind(metadata.rows_read)
+        .bind(metadata.airports_count)
+        .bind(metadata.rejected_count)
+        .bind(&metadata.checksum)
+        .execute(&mut transaction)
+        .await?;
+
+    for record in records {
+        let lat_dms = record
+            .lat_dms
+            .unwrap_or_else(|| Dms::latitude_from_decimal(record.lat_decimal));
+        let lon_dms = record
+            .lon_dms
+            .unwrap_or_else(|| Dms::longitude_from_decimal(record.lon_decimal));
+
+        sqlx::query(INSERT_AIRPORT)
+            .bind(record.icao_code.as_deref().unwrap_or(MISSING_VALUE))
+            .bind(record.iata_code.as_deref().unwrap_or(MISSING_VALUE))
+            .bind(&record.name)
+            .bind(&record.city)
+            .bind(&record.country)
+            .bind(lat_dms.degrees)
+            .bind(lat_dms.minutes)
+            .bind(lat_dms.seconds)
+            .bind(lat_dms.direction.to_string())
+            .bind(lon_dms.degrees)
+            .bind(lon_dms.minutes)
+            .bind(lon_dms.seconds)
+            .bind(lon_dms.direction.to_string())
+            .bind(record.altitude)
+            .bind(record.lat_decimal)
+            .bind(record.lon_decimal)
+            .execute(&mut transaction)
+            .await?;
+    }
+
+    transaction.commit().await?;
+    connection.close().await
+}
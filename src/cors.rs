@@ -0,0 +1,412 @@
+use std::future::Future;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+
+use actix_cors::Cors;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::HeaderName;
+use actix_web::http::{Method, Uri};
+use actix_web::middleware::Condition;
+use actix_web::Error;
+use futures::future::LocalBoxFuture;
+
+use crate::config::CorsPolicyConfig;
+
+/// Response headers browsers let scripts read, besides the CORS-safelisted ones.
+const EXPOSED_HEADERS: [&str; 5] = [
+    "X-Request-Id",
+    "RateLimit-Limit",
+    "RateLimit-Remaining",
+    "RateLimit-Reset",
+    "Retry-After",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CorsConfig {
+    /// Origins allowed to call the service from browsers, `None` allows any origin.
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<HeaderName>,
+    /// Whether browsers send cookies and `Authorization` headers they manage themselves, e.g.
+    /// after a Basic login prompt.
+    pub allow_credentials: bool,
+    /// How long browsers may cache preflight responses.
+    pub max_age_seconds: usize,
+}
+
+impl CorsConfig {
+    /// Parses the `[cors]` section of the configuration, returning `None` if cross-origin
+    /// requests aren't allowed.
+    pub fn parse(policy: &CorsPolicyConfig) -> Result<Option<Self>, String> {
+        let Some(allowed_origins) = &policy.allowed_origins else {
+            return Ok(None);
+        };
+
+        let allowed_origins = parse_origins(allowed_origins)?;
+        let allowed_methods = parse_list(&policy.allowed_methods, "cors.allowed_methods")?;
+        let allowed_headers = parse_list(&policy.allowed_headers, "cors.allowed_headers")?;
+
+        // browsers refuse credentialed responses allowing any origin
+        if allowed_origins.is_none() && policy.allow_credentials {
+            return Err("cors.allow_credentials requires explicit cors.allowed_origins".to_owned());
+        }
+
+        Ok(Some(Self {
+            allowed_origins,
+            allowed_methods,
+            allowed_headers,
+            allow_credentials: policy.allow_credentials,
+            max_age_seconds: policy.max_age_seconds,
+        }))
+    }
+
+    fn cors(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.allowed_methods.clone())
+            .allowed_headers(self.allowed_headers.clone())
+            .expose_headers(EXPOSED_HEADERS)
+            .max_age(self.max_age_seconds);
+
+        cors = match &self.allowed_origins {
+            Some(allowed_origins) => allowed_origins
+                .iter()
+                .fold(cors, |cors, origin| cors.allowed_origin(origin)),
+            None => cors.allow_any_origin().send_wildcard(),
+        };
+
+        if self.allow_credentials {
+            cors = cors.supports_credentials();
+        }
+
+        cors
+    }
+}
+
+/// CORS middleware of `config`, which has to wrap authentication for preflight requests to be
+/// answered without credentials. Without a config, requests pass through untouched.
+pub(crate) fn middleware(config: Option<&CorsConfig>) -> Condition<Cors> {
+    Condition::new(
+        config.is_some(),
+        config.map_or_else(Cors::default, CorsConfig::cors),
+    )
+}
+
+/// CORS middleware of `config` for routes under `prefixes` only, for routes which aren't in
+/// a scope of their own, like the docs paperclip mounts on the app.
+pub(crate) fn middleware_for(
+    prefixes: &'static [&'static str],
+    config: Option<&CorsConfig>,
+) -> ForPaths<Condition<Cors>> {
+    ForPaths {
+        prefixes,
+        transform: middleware(config),
+    }
+}
+
+/// Applies `transform` to requests under `prefixes`, other requests skip it.
+pub(crate) struct ForPaths<T> {
+    prefixes: &'static [&'static str],
+    transform: T,
+}
+
+impl<S, B, T, TB> Transform<S, ServiceRequest> for ForPaths<T>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+    T: Transform<SharedService<S>, ServiceRequest, Response = ServiceResponse<TB>, Error = Error>,
+    T::Transform: 'static,
+    T::Future: 'static,
+    TB: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = ForPathsMiddleware<S, T::Transform>;
+    type InitError = T::InitError;
+    type Future = LocalBoxFuture<'static, Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        let service = Rc::new(service);
+        let transformed = self.transform.new_transform(SharedService(service.clone()));
+        let prefixes = self.prefixes;
+
+        Box::pin(async move {
+            Ok(ForPathsMiddleware {
+                service,
+                transformed: transformed.await?,
+                prefixes,
+            })
+        })
+    }
+}
+
+/// Service shared by `ForPathsMiddleware` with the transform it applies.
+pub(crate) struct SharedService<S>(Rc<S>);
+
+impl<S: Service<ServiceRequest>> Service<ServiceRequest> for SharedService<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        self.0.call(request)
+    }
+}
+
+pub(crate) struct ForPathsMiddleware<S, M> {
+    service: Rc<S>,
+    transformed: M,
+    prefixes: &'static [&'static str],
+}
+
+impl<S, B, M, MB> Service<ServiceRequest> for ForPathsMiddleware<S, M>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+    M: Service<ServiceRequest, Response = ServiceResponse<MB>, Error = Error>,
+    M::Future: 'static,
+    MB: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.transformed.poll_ready(cx)
+    }
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let path = request.path();
+        let matches = self.prefixes.iter().any(|prefix| {
+            path.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+
+        match matches {
+            true => boxed(self.transformed.call(request)),
+            false => boxed(self.service.call(request)),
+        }
+    }
+}
+
+fn boxed<B: MessageBody + 'static>(
+    response: impl Future<Output = Result<ServiceResponse<B>, Error>> + 'static,
+) -> LocalBoxFuture<'static, Result<ServiceResponse<BoxBody>, Error>> {
+    Box::pin(async move { Ok(response.await?.map_into_boxed_body()) })
+}
+
+/// Parses origins like `https://map.example.com`, or `*` for any origin.
+fn parse_origins(origins: &[String]) -> Result<Option<Vec<String>>, String> {
+    if origins.iter().any(|origin| origin.trim() == "*") {
+        return Ok(None);
+    }
+
+    let origins = parse_list::<Uri>(origins, "cors.allowed_origins")?;
+    origins
+        .into_iter()
+        .map(
+            |origin| match (origin.scheme(), origin.authority(), origin.path()) {
+                (Some(scheme), Some(authority), "" | "/") => Ok(format!("{scheme}://{authority}")),
+                _ => Err(format!(
+                    "invalid cors.allowed_origins `{origin}`: origins are a scheme and host only"
+                )),
+            },
+        )
+        .collect::<Result<Vec<String>, String>>()
+        .map(Some)
+}
+
+fn parse_list<T>(list: &[String], name: &str) -> Result<Vec<T>, String>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    list.iter()
+        .map(|item| item.trim())
+        .map(|item| {
+            item.parse()
+                .map_err(|e| format!("invalid {name} `{item}`: {e}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::web::{self, Data};
+    use actix_web::App;
+    use actix_web_httpauth::middleware::HttpAuthentication;
+
+    use crate::auth::{api_auth_validator, ApiCredentials};
+
+    use super::*;
+
+    fn config() -> CorsConfig {
+        CorsConfig {
+            allowed_origins: Some(vec!["https://map.example.com".to_owned()]),
+            allowed_methods: vec![Method::GET],
+            allowed_headers: vec![header::AUTHORIZATION, HeaderName::from_static("x-api-key")],
+            allow_credentials: true,
+            max_age_seconds: 600,
+        }
+    }
+
+    fn origins(origins: &[&str]) -> Vec<String> {
+        origins.iter().map(|origin| origin.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parses_origins() {
+        assert_eq!(parse_origins(&origins(&[" * "])).unwrap(), None);
+        assert_eq!(
+            parse_origins(&origins(&[
+                "https://map.example.com",
+                "http://localhost:3000/"
+            ]))
+            .unwrap(),
+            Some(vec![
+                "https://map.example.com".to_owned(),
+                "http://localhost:3000".to_owned()
+            ])
+        );
+        assert!(parse_origins(&origins(&["map.example.com"])).is_err());
+        assert!(parse_origins(&origins(&["https://map.example.com/api"])).is_err());
+    }
+
+    #[test]
+    fn test_parses_policy() {
+        assert_eq!(CorsConfig::parse(&CorsPolicyConfig::default()), Ok(None));
+
+        let config = CorsConfig::parse(&CorsPolicyConfig {
+            allowed_origins: Some(origins(&["https://map.example.com"])),
+            ..CorsPolicyConfig::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.allowed_methods.len(), 5);
+        assert_eq!(config.allowed_headers[2], "x-api-key");
+
+        assert!(CorsConfig::parse(&CorsPolicyConfig {
+            allowed_origins: Some(origins(&["*"])),
+            allow_credentials: true,
+            ..CorsPolicyConfig::default()
+        })
+        .is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_answers_preflight_requests_before_authentication() {
+        let config = config();
+        let app = actix_web::test::init_service(
+            App::new().wrap(middleware(Some(&config))).service(
+                web::scope("/api")
+                    .app_data(Data::new(ApiCredentials::new("test", "test")))
+                    .wrap(HttpAuthentication::with_fn(api_auth_validator))
+                    .route("/test", web::get().to(|| async { "test" })),
+            ),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/api/test")
+            .insert_header((header::ORIGIN, "https://map.example.com"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            "https://map.example.com"
+        );
+        assert_eq!(
+            resp.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+                .unwrap(),
+            "true"
+        );
+
+        let req = actix_web::test::TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/api/test")
+            .insert_header((header::ORIGIN, "https://evil.example.com"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert!(resp
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+
+        // rejected requests are readable by the page too, so that it can ask for credentials
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/test")
+            .insert_header((header::ORIGIN, "https://map.example.com"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            resp.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            "https://map.example.com"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_passes_requests_through_without_config() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(middleware(None))
+                .route("/test", web::get().to(|| async { "test" })),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::get()
+            .uri("/test")
+            .insert_header((header::ORIGIN, "https://map.example.com"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_applies_to_given_paths_only() {
+        let config = config();
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(middleware_for(&["/docs"], Some(&config)))
+                .route("/docs/spec", web::get().to(|| async { "spec" }))
+                .route("/metrics", web::get().to(|| async { "metrics" })),
+        )
+        .await;
+
+        for (uri, allowed) in [("/docs/spec", true), ("/metrics", false)] {
+            let req = actix_web::test::TestRequest::get()
+                .uri(uri)
+                .insert_header((header::ORIGIN, "https://map.example.com"))
+                .to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(
+                resp.headers()
+                    .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN),
+                allowed,
+                "{uri}"
+            );
+        }
+    }
+}
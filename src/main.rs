@@ -1,82 +1,361 @@
 pub(crate) mod api;
 pub(crate) mod auth;
+pub(crate) mod config;
+pub(crate) mod cors;
+pub(crate) mod metrics;
 pub(crate) mod models;
+pub(crate) mod rate_limit;
 pub(crate) mod services;
+pub(crate) mod shutdown;
+pub(crate) mod telemetry;
+pub(crate) mod tls;
+pub(crate) mod usage;
 
 use actix_web::middleware::{Compress, Logger};
 use actix_web::web::Data;
 use actix_web::{App, HttpServer};
 use actix_web_httpauth::middleware::HttpAuthentication;
-use auth::ApiCredentials;
+use auth::{ApiCredentials, ApiKeyStore, JwtValidator, RequireRole, ADMIN_ROLE};
+use config::Config;
+use metrics::HttpMetrics;
 use paperclip::actix::web::Scope;
 use paperclip::actix::OpenApiExt;
-use services::airports::GlobalAirportsRepository;
+use rate_limit::{AuthenticationLimiter, RateLimiter, RateLimits};
+use services::airports::{
+    open_airports_repository, validate_airports_database, AirportsDatabaseReloader,
+    AirportsOverlayStore,
+};
 use services::app_state::AppState;
 use services::healthcheck::hostname_provider::HostnameCrateHostnameProvider;
 use services::healthcheck::time_provider::SystemTimeProvider;
-use sqlx::sqlite::SqliteConnectOptions;
-use sqlx::SqlitePool;
-use std::str::FromStr;
+use shutdown::{RequestsInFlight, ShutdownConfig, TrackRequests};
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
+use telemetry::RequestId;
+use tls::{CertificateResolver, ClientSubjects};
+use usage::{UsageRecorder, UsageStore, UsageTracker};
 
 const DATABASE_URL: &str = "sqlite:./global_airports_database.sqlite";
 
-fn init_logger() {
-    tracing_subscriber::fmt().json().compact().init();
-    tracing::info!("Initialized json logger successfully.");
-}
-
+/// Loads the `.env` file in development, before the configuration is read. Returns its path,
+/// to be logged once the logger is initialized.
 #[cfg(debug_assertions)]
-fn setup_dev() {
+fn setup_dev() -> Option<std::path::PathBuf> {
     let environment = std::env::var("ENVIRONMENT").unwrap_or("development".into());
 
     if environment == "development" {
-        tracing::info!("Attempting to load .env file");
-        dotenv::dotenv().ok();
+        return dotenv::dotenv().ok();
     }
+
+    None
 }
 
 #[cfg(not(debug_assertions))]
-fn setup_dev() {}
+fn setup_dev() -> Option<std::path::PathBuf> {
+    None
+}
+
+/// Logs why the service can't start and exits, instead of panicking with a backtrace.
+fn exit_with(message: impl Display) -> ! {
+    tracing::error!("{message}");
+    std::process::exit(1);
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    init_logger();
-    setup_dev();
+    let env_file = setup_dev();
+
+    // the logger isn't configured yet, so configuration errors are printed to stderr
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
-    let db_opts = SqliteConnectOptions::from_str(DATABASE_URL)
-        .expect("Failed to parse database url")
-        .read_only(true);
+    if let Err(e) = telemetry::init(config.logging.format, &config.tracing) {
+        eprintln!("Failed to set up the OTLP exporter: {e}");
+        std::process::exit(1);
+    }
+    if let Some(env_file) = env_file {
+        tracing::info!("Loaded {}", env_file.display());
+    }
+    tracing::info!(
+        "Effective configuration: {}",
+        serde_json::to_string(&config.redacted()).expect("Configuration is serializable")
+    );
 
-    let db_pool = SqlitePool::connect_with(db_opts)
+    let database_url = &config.database.url;
+    let repository_options = config.repository_options();
+    let airports_repository = open_airports_repository(database_url, &repository_options)
         .await
-        .expect("Failed to connect to database");
-
-    let airports_repository = Box::new(GlobalAirportsRepository::new(db_pool).await);
-
-    HttpServer::new(move || {
-        App::new()
-            .app_data(Data::new(AppState::new(
-                Box::new(HostnameCrateHostnameProvider::new()),
-                Box::new(SystemTimeProvider::new()),
-                airports_repository.clone(),
-            )))
-            .wrap(Logger::default())
+        .unwrap_or_else(|e| exit_with(format!("Failed to open airports database: {e}")));
+    let airports_count = validate_airports_database(airports_repository.as_ref())
+        .await
+        .unwrap_or_else(|e| exit_with(format!("Invalid airports database: {e}")));
+    tracing::info!("Serving {airports_count} airports");
+
+    let mut airports_reloader =
+        AirportsDatabaseReloader::new(database_url).with_options(repository_options);
+    let overlay_store = match &config.database.overlay_url {
+        Some(overlay_database_url) => {
+            let overlay_store = AirportsOverlayStore::open(overlay_database_url)
+                .await
+                .unwrap_or_else(|e| {
+                    exit_with(format!("Failed to open airports overlay database: {e}"))
+                });
+            tracing::info!("Serving airports overlay from {overlay_database_url}");
+
+            let overlay_store = Arc::new(overlay_store);
+            airports_reloader = airports_reloader.with_overlay(overlay_store.clone());
+            Some(Data::from(overlay_store))
+        }
+        None => None,
+    };
+
+    let api_key_store = match &config.database.api_keys_url {
+        Some(api_keys_database_url) => {
+            let api_key_store = ApiKeyStore::open(api_keys_database_url)
+                .await
+                .unwrap_or_else(|e| exit_with(format!("Failed to open API keys database: {e}")));
+            tracing::info!("Accepting API keys from {api_keys_database_url}");
+
+            Some(Data::new(api_key_store))
+        }
+        None => None,
+    };
+
+    let jwt_validator = match config.jwt() {
+        Some(jwt_config) => {
+            let jwks_path = jwt_config.jwks_path.display().to_string();
+            let jwt_validator = Data::new(JwtValidator::open(jwt_config).unwrap_or_else(|e| {
+                exit_with(format!("Failed to read JWKS file {jwks_path}: {e}"))
+            }));
+            tracing::info!("Accepting JWTs signed with keys of {jwks_path}");
+
+            actix_web::rt::spawn(auth::refresh_jwks_periodically(jwt_validator.clone()));
+            Some(jwt_validator)
+        }
+        None => None,
+    };
+
+    let usage_tracker = match &config.database.usage_url {
+        Some(usage_database_url) => {
+            let usage_store = UsageStore::open(usage_database_url)
+                .await
+                .unwrap_or_else(|e| exit_with(format!("Failed to open usage database: {e}")));
+            let quotas = config
+                .quotas()
+                .unwrap_or_else(|e| exit_with(format!("Invalid quotas configuration: {e}")));
+            tracing::info!("Recording usage in {usage_database_url}");
+
+            Some(Data::new(UsageTracker::new(usage_store, quotas)))
+        }
+        None => None,
+    };
+
+    let tls_config = config.tls();
+    let tls_server_config = match &tls_config {
+        Some(tls_config) => {
+            let resolver = Arc::new(
+                CertificateResolver::open(&tls_config.cert_path, &tls_config.key_path)
+                    .unwrap_or_else(|e| exit_with(format!("Failed to read TLS certificate: {e}"))),
+            );
+            let server_config = tls::server_config(tls_config, resolver.clone())
+                .unwrap_or_else(|e| exit_with(format!("Failed to configure TLS: {e}")));
+            tracing::info!(
+                "Serving TLS certificate of {}",
+                tls_config.cert_path.display()
+            );
+
+            actix_web::rt::spawn(tls::reload_certificates_periodically(
+                resolver,
+                tls_config.reload_interval,
+            ));
+            Some(server_config)
+        }
+        None => None,
+    };
+    let client_subjects = match tls_config.and_then(|tls_config| tls_config.client_subjects_path) {
+        Some(client_subjects_path) => {
+            let client_subjects =
+                ClientSubjects::from_file(&client_subjects_path).unwrap_or_else(|e| {
+                    exit_with(format!(
+                        "Failed to read client subjects file {}: {e}",
+                        client_subjects_path.display()
+                    ))
+                });
+            tracing::info!(
+                "Accepting client certificates of subjects in {}",
+                client_subjects_path.display()
+            );
+
+            Some(Data::new(client_subjects))
+        }
+        None => None,
+    };
+
+    let cors_config = config.cors();
+
+    let rate_limit_config = config
+        .rate_limits()
+        .unwrap_or_else(|e| exit_with(format!("Invalid rate limits configuration: {e}")));
+    let rate_limits = Arc::new(RateLimits::new(rate_limit_config));
+
+    let api_credentials = match (
+        &config.auth.credentials_file,
+        &config.auth.username,
+        &config.auth.password,
+    ) {
+        (Some(credentials_file), _, _) => ApiCredentials::from_file(credentials_file.clone())
+            .unwrap_or_else(|e| {
+                exit_with(format!(
+                    "Failed to read credentials file {}: {e}",
+                    credentials_file.display()
+                ))
+            }),
+        (None, Some(username), Some(password)) => ApiCredentials::new(username, password),
+        // required by `Config::validate`
+        (None, _, _) => exit_with("auth.username and auth.password are required"),
+    };
+    let api_credentials = Data::new(api_credentials);
+    #[cfg(unix)]
+    actix_web::rt::spawn(auth::reload_credentials_on_hangup(api_credentials.clone()));
+
+    let workers = config.server.workers.unwrap_or_else(num_cpus::get_physical);
+    // app state is shared between workers, so a reloaded airports database is visible to all of them
+    let app_state = Data::new(
+        AppState::new(
+            Box::new(HostnameCrateHostnameProvider::new()),
+            Box::new(SystemTimeProvider::new()),
+            airports_reloader.serve(airports_repository),
+        )
+        .with_workers(workers),
+    );
+    let airports_reloader = Data::new(airports_reloader);
+
+    // databases are closed once the server stops, after the app factory below is dropped
+    let databases = (
+        app_state.clone(),
+        overlay_store.clone(),
+        api_key_store.clone(),
+        usage_tracker.clone(),
+    );
+    let shutdown_config = ShutdownConfig {
+        delay: Duration::from_secs(config.server.shutdown_delay_seconds),
+        drain_timeout: Duration::from_secs(config.server.drain_timeout_seconds),
+    };
+    let requests_in_flight = Arc::new(RequestsInFlight::default());
+    let tracked_requests = requests_in_flight.clone();
+
+    let server = HttpServer::new(move || {
+        let mut app = App::new().app_data(app_state.clone());
+
+        if let Some(overlay_store) = &overlay_store {
+            app = app.app_data(overlay_store.clone());
+        }
+        if let Some(api_key_store) = &api_key_store {
+            app = app.app_data(api_key_store.clone());
+        }
+        if let Some(jwt_validator) = &jwt_validator {
+            app = app.app_data(jwt_validator.clone());
+        }
+        if let Some(usage_tracker) = &usage_tracker {
+            app = app.app_data(usage_tracker.clone());
+        }
+        if let Some(client_subjects) = &client_subjects {
+            app = app.app_data(client_subjects.clone());
+        }
+
+        // cross-origin requests are allowed to the API and its docs only
+        app.wrap(cors::middleware_for(&["/docs"], cors_config.as_ref()))
+            .wrap(HttpMetrics)
+            .wrap(TrackRequests::new(tracked_requests.clone()))
+            .wrap(RequestId)
+            .wrap(Logger::new(telemetry::ACCESS_LOG_FORMAT))
             .wrap(Compress::default())
+            .service(api::health::check_liveness)
+            .service(api::health::check_readiness)
             .service(api::health::check_health)
+            .service(api::health::build_info)
+            .service(api::metrics::metrics_handler)
             .wrap_api()
             .service(
                 Scope::new("/api")
-                    .app_data(Data::new(ApiCredentials::from_env()))
-                    .wrap(HttpAuthentication::basic(auth::basic_auth_validator))
+                    .app_data(api_credentials.clone())
+                    .app_data(airports_reloader.clone())
+                    // authentication wraps rate limiting and usage recording, so requests are
+                    // counted per principal, and is wrapped by the limit of failed attempts per
+                    // address, so that locked out addresses don't get their credentials checked
+                    .wrap(UsageRecorder)
+                    .wrap(RateLimiter::new(rate_limits.clone()))
+                    .wrap(HttpAuthentication::with_fn(auth::api_auth_validator))
+                    .wrap(AuthenticationLimiter::new(rate_limits.clone()))
+                    // CORS wraps authentication, so that preflight requests are answered
+                    // without credentials
+                    .wrap(cors::middleware(cors_config.as_ref()))
                     .service(api::distance::handlers::coordinates_handler)
                     .service(api::distance::handlers::airports_handler)
-                    .service(api::airports::handlers::unique_iatas_handler),
+                    .service(api::airports::handlers::unique_iatas_handler)
+                    .service(api::airports::handlers::list_airports_handler)
+                    .service(api::airports::handlers::lookup_airports_handler)
+                    .service(api::airports::handlers::dataset_metadata_handler)
+                    .service(api::usage::handlers::caller_usage_handler)
+                    .service(
+                        Scope::new("/admin")
+                            .wrap(RequireRole::new(ADMIN_ROLE))
+                            .service(api::admin::handlers::reload_airports_handler)
+                            .service(api::admin::handlers::cache_stats_handler)
+                            .service(api::admin::handlers::overlay_audit_log_handler)
+                            .service(api::admin::handlers::create_airport_handler)
+                            .service(api::admin::handlers::update_airport_handler)
+                            .service(api::admin::handlers::delete_airport_handler)
+                            .service(api::admin::handlers::issue_api_key_handler)
+                            .service(api::admin::handlers::list_api_keys_handler)
+                            .service(api::admin::handlers::revoke_api_key_handler)
+                            .service(api::admin::handlers::usage_summary_handler),
+                    ),
             )
             .with_json_spec_at("/docs/spec")
             .with_swagger_ui_at("/docs")
             .build()
     })
-    .bind(("0.0.0.0", 8000))?
-    .run()
-    .await
+    .on_connect(tls::client_certificate_on_connect)
+    .workers(workers)
+    // signals are handled by `shutdown`, which fails readiness before stopping the server
+    .disable_signals()
+    .shutdown_timeout(shutdown_config.drain_timeout.as_secs());
+
+    let bind_address = config.server.bind_address.as_str();
+    let server = match tls_server_config {
+        Some(tls_server_config) => server.bind_rustls(bind_address, tls_server_config)?,
+        None => server.bind(bind_address)?,
+    }
+    .run();
+
+    let (app_state, overlay_store, api_key_store, usage_tracker) = databases;
+    let result = shutdown::run_until_stopped(
+        server,
+        &app_state,
+        &requests_in_flight,
+        shutdown_config,
+        shutdown::termination_signal(),
+    )
+    .await;
+
+    shutdown::close_databases(
+        &app_state,
+        overlay_store.as_ref(),
+        api_key_store.as_ref(),
+        usage_tracker.as_ref(),
+    )
+    .await;
+    if let Ok(Some(summary)) = &result {
+        tracing::info!("{summary}");
+    }
+
+    telemetry::shutdown(&config.tracing).await;
+    result.map(|_| ())
 }
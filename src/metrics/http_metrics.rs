@@ -0,0 +1,128 @@
+use std::future::{ready, Ready};
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures::future::LocalBoxFuture;
+
+use super::metrics;
+
+/// Counts requests and measures their duration by method, route pattern and status. Routes
+/// are labelled by their patterns, e.g. `/api/airports/{iata}`, so that paths don't blow up
+/// the number of series. Also tracks the number of requests in flight.
+pub(crate) struct HttpMetrics;
+
+impl<S, B> Transform<S, ServiceRequest> for HttpMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = HttpMetricsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HttpMetricsMiddleware { service }))
+    }
+}
+
+pub(crate) struct HttpMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for HttpMetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let started_at = Instant::now();
+        let method = request.method().to_string();
+        let route = request
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_owned());
+        let in_flight = InFlight::start();
+        let response = self.service.call(request);
+
+        Box::pin(async move {
+            let response = response.await;
+            drop(in_flight);
+            let status = match &response {
+                Ok(response) => response.status(),
+                Err(e) => e.as_response_error().status_code(),
+            };
+
+            let labels = [method.as_str(), route.as_str(), status.as_str()];
+            metrics().http_requests.with_label_values(&labels).inc();
+            metrics()
+                .http_request_duration
+                .with_label_values(&labels)
+                .observe(started_at.elapsed().as_secs_f64());
+
+            response
+        })
+    }
+}
+
+/// Counts a request in flight until it's dropped, so that requests cut off by a shutdown are
+/// counted out too.
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        metrics().http_requests_in_flight.inc();
+        Self
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        metrics().http_requests_in_flight.dec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{get, web, App};
+
+    use super::*;
+
+    #[get("/metrics-test/{iata}")]
+    async fn test_handler(_iata: web::Path<String>) -> &'static str {
+        "test"
+    }
+
+    #[actix_web::test]
+    async fn test_counts_requests_by_route_pattern() {
+        let app =
+            actix_web::test::init_service(App::new().wrap(HttpMetrics).service(test_handler)).await;
+
+        for uri in ["/metrics-test/WAW", "/metrics-test/KRK", "/metrics-test"] {
+            let req = actix_web::test::TestRequest::get().uri(uri).to_request();
+            actix_web::test::call_service(&app, req).await;
+        }
+
+        assert_eq!(
+            metrics()
+                .http_requests
+                .with_label_values(&["GET", "/metrics-test/{iata}", "200"])
+                .get(),
+            2
+        );
+        assert_eq!(
+            metrics()
+                .http_request_duration
+                .with_label_values(&["GET", "/metrics-test/{iata}", "200"])
+                .get_sample_count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,240 @@
+mod http_metrics;
+
+use std::sync::OnceLock;
+
+use prometheus::core::Collector;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::models::{Datum, Formula};
+use crate::services::airports::AirportsRepository;
+
+pub(crate) use http_metrics::HttpMetrics;
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Metrics of the whole process, shared by all workers.
+pub(crate) fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+pub(crate) struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub http_requests_in_flight: IntGauge,
+    pub distance_computations: IntCounterVec,
+    pub vincenty_non_convergence: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status."),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to respond to HTTP requests by route and status.",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_requests_in_flight =
+            IntGauge::new("http_requests_in_flight", "HTTP requests being handled.").unwrap();
+        let distance_computations = IntCounterVec::new(
+            Opts::new(
+                "distance_computations_total",
+                "Distances computed between two points by formula and datum.",
+            ),
+            &["formula", "datum"],
+        )
+        .unwrap();
+        let vincenty_non_convergence = IntCounter::new(
+            "vincenty_non_convergence_total",
+            "Vincenty computations which failed to converge.",
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(http_requests_in_flight.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(distance_computations.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(vincenty_non_convergence.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            http_requests_in_flight,
+            distance_computations,
+            vincenty_non_convergence,
+        }
+    }
+
+    /// Requests responded to since the start, of all routes.
+    pub fn http_requests_total(&self) -> u64 {
+        self.http_requests
+            .collect()
+            .iter()
+            .flat_map(|family| family.get_metric())
+            .map(|metric| metric.get_counter().get_value() as u64)
+            .sum()
+    }
+
+    pub fn record_distance_computation(&self, formula: &Formula, datum: &Datum) {
+        let formula = match formula {
+            Formula::GreatCircle => "great_circle",
+            Formula::Haversine => "haversine",
+            Formula::Vincenty => "vincenty",
+        };
+        let datum = match datum {
+            Datum::WGS84 => "wgs84",
+            Datum::NAD27 => "nad27",
+            Datum::NAD83 => "nad83",
+        };
+
+        self.distance_computations
+            .with_label_values(&[formula, datum])
+            .inc();
+    }
+
+    /// Renders the metrics in the Prometheus text format, together with the cache and pool
+    /// metrics of `repository`, which are read when scraped.
+    pub fn render(&self, repository: &dyn AirportsRepository) -> Result<String, prometheus::Error> {
+        let mut metric_families = self.registry.gather();
+        metric_families.extend(repository_registry(repository)?.gather());
+        metric_families.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&metric_families, &mut buffer)?;
+
+        Ok(String::from_utf8(buffer).expect("Prometheus text format is UTF-8"))
+    }
+}
+
+/// Registry of the current counters of the repository caches and pool, which are kept by the
+/// repository itself and start over when it's reloaded.
+fn repository_registry(repository: &dyn AirportsRepository) -> Result<Registry, prometheus::Error> {
+    let registry = Registry::new();
+
+    let cache_hits = IntCounterVec::new(
+        Opts::new(
+            "airports_cache_hits_total",
+            "Airports cache lookups served from the cache.",
+        ),
+        &["cache"],
+    )?;
+    let cache_misses = IntCounterVec::new(
+        Opts::new(
+            "airports_cache_misses_total",
+            "Airports cache lookups which had to query the database.",
+        ),
+        &["cache"],
+    )?;
+    let cache_hit_ratio = GaugeVec::new(
+        Opts::new(
+            "airports_cache_hit_ratio",
+            "Share of airports cache lookups served from the cache.",
+        ),
+        &["cache"],
+    )?;
+    for stats in repository.cache_stats() {
+        cache_hits
+            .with_label_values(&[&stats.name])
+            .inc_by(stats.hits);
+        cache_misses
+            .with_label_values(&[&stats.name])
+            .inc_by(stats.misses);
+
+        let lookups = stats.hits + stats.misses;
+        if lookups > 0 {
+            cache_hit_ratio
+                .with_label_values(&[&stats.name])
+                .set(stats.hits as f64 / lookups as f64);
+        }
+    }
+    registry.register(Box::new(cache_hits))?;
+    registry.register(Box::new(cache_misses))?;
+    registry.register(Box::new(cache_hit_ratio))?;
+
+    if let Some(pool_stats) = repository.pool_stats() {
+        let connections = IntGaugeVec::new(
+            Opts::new(
+                "airports_database_connections",
+                "Connections of the airports database pool by state.",
+            ),
+            &["state"],
+        )?;
+        connections
+            .with_label_values(&["idle"])
+            .set(pool_stats.idle.into());
+        connections
+            .with_label_values(&["in_use"])
+            .set(pool_stats.size.saturating_sub(pool_stats.idle).into());
+        registry.register(Box::new(connections))?;
+    }
+
+    Ok(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::Coordinates;
+    use crate::services::airports::GlobalAirportsRepository;
+    use crate::services::distance::DistanceCalculatorFactory;
+    use crate::DATABASE_URL;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_renders_repository_metrics() {
+        let repository = GlobalAirportsRepository::open(DATABASE_URL).await.unwrap();
+        repository.fetch_airport_by_iata("WAW").await.unwrap();
+        repository.fetch_airport_by_iata("WAW").await.unwrap();
+
+        let rendered = metrics().render(&repository).unwrap();
+
+        assert!(rendered.contains("airports_cache_hits_total{cache=\"airports\"} 1\n"));
+        assert!(rendered.contains("airports_cache_misses_total{cache=\"airports\"} 1\n"));
+        assert!(rendered.contains("airports_cache_hit_ratio{cache=\"airports\"} 0.5\n"));
+        assert!(rendered.contains("airports_database_connections{state=\"idle\"}"));
+    }
+
+    #[test]
+    fn test_counts_distance_computations() {
+        let computations = || {
+            metrics()
+                .distance_computations
+                .with_label_values(&["vincenty", "nad83"])
+                .get()
+        };
+        let (computations_before, non_convergence_before) =
+            (computations(), metrics().vincenty_non_convergence.get());
+
+        let calculator = DistanceCalculatorFactory::create(&Formula::Vincenty, &Datum::NAD83);
+        calculator
+            .calculate_distance(&Coordinates::new(0.0, 0.0), &Coordinates::new(1.0, 1.0))
+            .unwrap();
+        // nearly antipodal points
+        assert!(calculator
+            .calculate_distance(&Coordinates::new(0.0, 0.0), &Coordinates::new(0.5, 179.7))
+            .is_err());
+
+        assert_eq!(computations(), computations_before + 2);
+        assert!(metrics().vincenty_non_convergence.get() > non_convergence_before);
+    }
+}
@@ -1,6 +1,7 @@
+use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 
-#[derive(sqlx::FromRow, Debug, Serialize, Deserialize, Clone)]
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize, Clone, PartialEq, Apiv2Schema)]
 pub struct Airport {
     pub id: u32,
     pub icao_code: String,
@@ -20,3 +21,33 @@ pub struct Airport {
     pub lat_decimal: f64,
     pub lon_decimal: f64,
 }
+
+/// Degrees, minutes, seconds and direction of a decimal coordinate, in the shape stored in
+/// the `airports` table.
+pub struct Dms {
+    pub degrees: i64,
+    pub minutes: i64,
+    pub seconds: i64,
+    pub direction: String,
+}
+
+impl Dms {
+    pub fn latitude(value: f64) -> Self {
+        Self::from_decimal(value, "N", "S")
+    }
+
+    pub fn longitude(value: f64) -> Self {
+        Self::from_decimal(value, "E", "W")
+    }
+
+    fn from_decimal(value: f64, positive: &str, negative: &str) -> Self {
+        let total_seconds = (value.abs() * 3600.).round() as i64;
+
+        Self {
+            degrees: total_seconds / 3600,
+            minutes: total_seconds % 3600 / 60,
+            seconds: total_seconds % 60,
+            direction: if value < 0. { negative } else { positive }.to_owned(),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// Information about the running build, embedded at compile time by `build.rs`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: String,
+    /// Commit the binary was built from, `unknown` outside of a repository without `GIT_COMMIT`.
+    pub git_commit: String,
+    /// Unix timestamp of the build.
+    pub built_at: u64,
+    pub rustc_version: String,
+    /// Enabled cargo features, e.g. `postgres`.
+    pub features: Vec<String>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            git_commit: env!("BUILD_GIT_COMMIT").to_owned(),
+            built_at: env!("BUILD_TIMESTAMP")
+                .parse()
+                .expect("BUILD_TIMESTAMP is a Unix timestamp"),
+            rustc_version: env!("BUILD_RUSTC_VERSION").to_owned(),
+            features: env!("BUILD_FEATURES")
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+
+/// Counters of a repository cache since the repository was opened.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Apiv2Schema)]
+pub struct CacheStats {
+    pub name: String,
+    /// Approximate number of cached entries.
+    pub entries: u64,
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped because of the capacity limit or expiration.
+    pub evictions: u64,
+}
@@ -0,0 +1,16 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+
+/// Describes the airports dataset served by the deployment, as written by the importer.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize, Clone, PartialEq, Apiv2Schema)]
+pub struct DatasetMetadata {
+    pub source: String,
+    pub version: String,
+    /// Unix timestamp of the import.
+    pub imported_at: i64,
+    pub rows_read: i64,
+    pub airports_count: i64,
+    pub rejected_count: i64,
+    /// Hex encoded SHA-256 of the source files.
+    pub checksum: String,
+}
@@ -1,16 +1,11 @@
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy, Apiv2Schema)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, Apiv2Schema)]
 #[serde(rename_all = "lowercase")]
 pub enum Datum {
+    #[default]
     WGS84,
     NAD27,
     NAD83,
 }
-
-impl Default for Datum {
-    fn default() -> Self {
-        Self::WGS84
-    }
-}
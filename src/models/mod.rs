@@ -1,11 +1,21 @@
 pub(crate) mod earth;
 
 mod airport;
+mod build_info;
+mod cache_stats;
 mod coordinates;
+mod dataset_metadata;
 mod datums;
 mod formulas;
+mod pool_stats;
+mod usage;
 
-pub use self::airport::Airport;
+pub use self::airport::{Airport, Dms};
+pub use self::build_info::BuildInfo;
+pub use self::cache_stats::CacheStats;
 pub use self::coordinates::Coordinates;
+pub use self::dataset_metadata::DatasetMetadata;
 pub use self::datums::Datum;
 pub use self::formulas::Formula;
+pub use self::pool_stats::PoolStats;
+pub use self::usage::{ClientUsage, DailyUsage, UsageCounts};
@@ -0,0 +1,38 @@
+use std::ops::AddAssign;
+
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+
+/// Usage of the API by a client over some period.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Apiv2Schema)]
+pub struct UsageCounts {
+    pub requests: i64,
+    /// Distances between consecutive route points calculated.
+    pub legs: i64,
+    /// Airports looked up by their codes, including the stops of routes.
+    pub airport_lookups: i64,
+}
+
+impl AddAssign for UsageCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.requests += other.requests;
+        self.legs += other.legs;
+        self.airport_lookups += other.airport_lookups;
+    }
+}
+
+/// Usage of a client on a single UTC day.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Apiv2Schema)]
+pub struct DailyUsage {
+    /// Day in the `YYYY-MM-DD` format.
+    pub day: String,
+    pub usage: UsageCounts,
+}
+
+/// Usage of a single client, as seen by admins.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Apiv2Schema)]
+pub struct ClientUsage {
+    /// User name, `api-key:<id>` or `jwt:<subject>`.
+    pub client: String,
+    pub usage: UsageCounts,
+}
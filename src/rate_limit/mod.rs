@@ -0,0 +1,667 @@
+mod token_bucket;
+
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpMessage, HttpResponse};
+use futures::future::LocalBoxFuture;
+use moka::sync::Cache;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::log;
+
+use crate::auth::Principal;
+
+pub(crate) use token_bucket::Limit;
+use token_bucket::{Decision, TokenBucket};
+
+/// Limits applied to `/api` requests, read from a TOML file:
+///
+/// ```toml
+/// [default]
+/// requests = 100
+/// period_seconds = 60
+///
+/// [clients."user:importer"]
+/// requests = 1000
+/// period_seconds = 60
+///
+/// [[endpoints]]
+/// path = "/api/airports/lookup"
+/// requests = 10
+/// period_seconds = 60
+///
+/// [failed_authentications]
+/// requests = 10
+/// period_seconds = 60
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct RateLimitConfig {
+    /// Limit of clients without their own one, requests aren't limited without it.
+    #[serde(default)]
+    pub default: Option<Limit>,
+    /// Limits of clients `user:<name>`, `api-key:<id>` or `jwt:<subject>`.
+    #[serde(default)]
+    pub clients: HashMap<String, Limit>,
+    /// Limits of endpoints under given paths, counted in addition to the client's limit.
+    #[serde(default)]
+    pub endpoints: Vec<EndpointLimit>,
+    /// Limit of failed authentications per peer IP address, after which requests of the
+    /// address are rejected before their credentials are checked. Not limited without it, as
+    /// behind a reverse proxy all clients share the address of the proxy.
+    #[serde(default)]
+    pub failed_authentications: Option<Limit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct EndpointLimit {
+    pub path: String,
+    #[serde(flatten)]
+    pub limit: Limit,
+}
+
+impl RateLimitConfig {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        let config = toml::from_str::<Self>(&content)
+            .map_err(|e| format!("failed to parse {}: {e}", path.display()))?;
+
+        for limit in config
+            .default
+            .iter()
+            .chain(config.clients.values())
+            .chain(config.endpoints.iter().map(|endpoint| &endpoint.limit))
+            .chain(config.failed_authentications.iter())
+        {
+            limit.validate()?;
+        }
+
+        Ok(config)
+    }
+
+    /// Limits of requests of `client` to `path`, together with the names of the buckets
+    /// they're counted in: the client's own limit named after the client or the default one,
+    /// and the limit of the most specific endpoint the path is under, named after its path.
+    fn limits<'a>(&'a self, client: &'a str, path: &str) -> Vec<(&'a str, Limit)> {
+        let client_limit = match self.clients.get(client) {
+            Some(limit) => Some((client, *limit)),
+            None => self.default.map(|limit| ("default", limit)),
+        };
+
+        let endpoint_limit = self
+            .endpoints
+            .iter()
+            .filter(|endpoint| is_under(path, &endpoint.path))
+            .max_by_key(|endpoint| endpoint.path.len())
+            .map(|endpoint| (endpoint.path.as_str(), endpoint.limit));
+
+        client_limit.into_iter().chain(endpoint_limit).collect()
+    }
+}
+
+/// Whether `path` is `prefix` or one of the paths below it, `/api/airports` doesn't cover
+/// `/api/airportsX`.
+fn is_under(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix.trim_end_matches('/')) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Token buckets of clients, kept in memory until they've been idle for long enough to
+/// refill completely.
+pub(crate) struct RateLimits {
+    config: RateLimitConfig,
+    buckets: Cache<(String, String), Arc<Mutex<TokenBucket>>>,
+}
+
+impl RateLimits {
+    pub fn new(config: RateLimitConfig) -> Self {
+        let longest_period = config
+            .default
+            .iter()
+            .chain(config.clients.values())
+            .chain(config.endpoints.iter().map(|endpoint| &endpoint.limit))
+            .chain(config.failed_authentications.iter())
+            .map(|limit| limit.period_seconds)
+            .max()
+            .unwrap_or(1);
+
+        Self {
+            config,
+            buckets: Cache::builder()
+                .max_capacity(100_000)
+                .time_to_idle(Duration::from_secs(longest_period))
+                .build(),
+        }
+    }
+
+    fn bucket(
+        &self,
+        client: &str,
+        bucket: &str,
+        limit: &Limit,
+        now: Instant,
+    ) -> Arc<Mutex<TokenBucket>> {
+        self.buckets
+            .get_with((client.to_owned(), bucket.to_owned()), || {
+                Arc::new(Mutex::new(TokenBucket::full(limit, now)))
+            })
+    }
+
+    /// Takes a token from every bucket the request is counted in, if all of them allow it.
+    /// Returns the decision of the bucket which is closest to rejecting requests, together
+    /// with its name.
+    fn check<'a>(
+        &'a self,
+        client: &'a str,
+        path: &str,
+        now: Instant,
+    ) -> Option<(&'a str, Decision)> {
+        let buckets = self
+            .config
+            .limits(client, path)
+            .into_iter()
+            .map(|(name, limit)| (name, self.bucket(client, name, &limit, now), limit))
+            .collect::<Vec<_>>();
+
+        let rejected = buckets
+            .iter()
+            .map(|(name, bucket, limit)| (*name, lock(bucket).peek(limit, now)))
+            .filter(|(_, decision)| !decision.allowed)
+            .max_by_key(|(_, decision)| decision.retry_after);
+        if rejected.is_some() {
+            return rejected;
+        }
+
+        buckets
+            .iter()
+            .map(|(name, bucket, limit)| (*name, lock(bucket).take(limit, now)))
+            .min_by_key(|(_, decision)| decision.remaining)
+    }
+
+    /// Rejects requests of an address which failed to authenticate too many times.
+    fn check_authentication(&self, address: &str, now: Instant) -> Option<Decision> {
+        let limit = self.config.failed_authentications?;
+
+        let bucket = self.bucket(address, FAILED_AUTHENTICATIONS_BUCKET, &limit, now);
+        let decision = lock(&bucket).peek(&limit, now);
+
+        Some(decision)
+    }
+
+    fn record_failed_authentication(&self, address: &str, now: Instant) {
+        let Some(limit) = self.config.failed_authentications else {
+            return;
+        };
+
+        let bucket = self.bucket(address, FAILED_AUTHENTICATIONS_BUCKET, &limit, now);
+        lock(&bucket).take(&limit, now);
+    }
+}
+
+const FAILED_AUTHENTICATIONS_BUCKET: &str = "failed_authentications";
+
+fn lock(bucket: &Mutex<TokenBucket>) -> std::sync::MutexGuard<'_, TokenBucket> {
+    bucket.lock().expect("Token bucket lock poisoned")
+}
+
+/// Client requests are counted for, the authenticated principal or the peer address for
+/// anonymous requests.
+fn client_id(request: &ServiceRequest) -> String {
+    if let Some(principal) = request.extensions().get::<Principal>() {
+        return principal.client_id();
+    }
+
+    peer_id(request)
+}
+
+fn peer_id(request: &ServiceRequest) -> String {
+    match request.peer_addr() {
+        Some(address) => format!("ip:{}", address.ip()),
+        None => "ip:unknown".to_owned(),
+    }
+}
+
+/// Middleware rejecting requests over the client's limit with `429 Too Many Requests`. Has to
+/// be wrapped by the authentication middleware, so that requests are counted per principal.
+pub(crate) struct RateLimiter {
+    rate_limits: Arc<RateLimits>,
+}
+
+impl RateLimiter {
+    pub fn new(rate_limits: Arc<RateLimits>) -> Self {
+        Self { rate_limits }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimiterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimiterMiddleware {
+            service,
+            rate_limits: self.rate_limits.clone(),
+        }))
+    }
+}
+
+pub(crate) struct RateLimiterMiddleware<S> {
+    service: S,
+    rate_limits: Arc<RateLimits>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let client = client_id(&request);
+        let Some((bucket, decision)) =
+            self.rate_limits
+                .check(&client, request.path(), Instant::now())
+        else {
+            let response = self.service.call(request);
+            return Box::pin(async move { Ok(response.await?.map_into_left_body()) });
+        };
+
+        if !decision.allowed {
+            log::warn!(
+                "{client} exceeded the `{bucket}` rate limit with {}",
+                request.path()
+            );
+
+            return Box::pin(ready(Ok(request
+                .into_response(too_many_requests(&decision))
+                .map_into_right_body())));
+        }
+
+        let response = self.service.call(request);
+        Box::pin(async move {
+            let mut response = response.await?;
+            insert_headers(response.headers_mut(), &decision);
+
+            Ok(response.map_into_left_body())
+        })
+    }
+}
+
+/// Middleware rejecting requests of addresses which failed to authenticate too many times
+/// with `429 Too Many Requests`. Wraps the authentication middleware, so that credentials of
+/// those requests aren't checked at all, and counts its `401 Unauthorized` responses.
+pub(crate) struct AuthenticationLimiter {
+    rate_limits: Arc<RateLimits>,
+}
+
+impl AuthenticationLimiter {
+    pub fn new(rate_limits: Arc<RateLimits>) -> Self {
+        Self { rate_limits }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AuthenticationLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AuthenticationLimiterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuthenticationLimiterMiddleware {
+            service,
+            rate_limits: self.rate_limits.clone(),
+        }))
+    }
+}
+
+pub(crate) struct AuthenticationLimiterMiddleware<S> {
+    service: S,
+    rate_limits: Arc<RateLimits>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuthenticationLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let address = peer_id(&request);
+
+        if let Some(decision) = self
+            .rate_limits
+            .check_authentication(&address, Instant::now())
+        {
+            if !decision.allowed {
+                log::warn!("{address} failed to authenticate too many times");
+
+                return Box::pin(ready(Ok(request
+                    .into_response(too_many_requests(&decision))
+                    .map_into_right_body())));
+            }
+        }
+
+        let rate_limits = self.rate_limits.clone();
+        let response = self.service.call(request);
+        Box::pin(async move {
+            // the authentication middleware returns rejected credentials as errors
+            let response = response.await;
+            let status = match &response {
+                Ok(response) => response.status(),
+                Err(e) => e.as_response_error().status_code(),
+            };
+            if status == StatusCode::UNAUTHORIZED {
+                rate_limits.record_failed_authentication(&address, Instant::now());
+            }
+
+            response.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+fn too_many_requests(decision: &Decision) -> HttpResponse {
+    let mut response = HttpResponse::TooManyRequests().json(json!({
+        "error": "Too many requests",
+        "details": {"retry_after": seconds(decision.retry_after.unwrap_or_default())}
+    }));
+    insert_headers(response.headers_mut(), decision);
+
+    response
+}
+
+fn insert_headers(headers: &mut HeaderMap, decision: &Decision) {
+    let mut insert = |name: &'static str, value: u64| {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    };
+
+    insert("ratelimit-limit", decision.limit.into());
+    insert("ratelimit-remaining", decision.remaining.into());
+    insert("ratelimit-reset", seconds(decision.reset));
+
+    if let Some(retry_after) = decision.retry_after {
+        headers.insert(RETRY_AFTER, HeaderValue::from(seconds(retry_after)));
+    }
+}
+
+/// Whole seconds, rounded up so that clients don't retry too early.
+fn seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::web::Data;
+    use actix_web::{get, App};
+    use actix_web_httpauth::headers::authorization::{Authorization, Basic};
+    use actix_web_httpauth::middleware::HttpAuthentication;
+
+    use crate::auth::{self, ApiCredentials};
+
+    use super::*;
+
+    #[get("/api/airports/lookup")]
+    async fn lookup_handler() -> &'static str {
+        "lookup"
+    }
+
+    #[get("/api/airports/iatas")]
+    async fn iatas_handler() -> &'static str {
+        "iatas"
+    }
+
+    fn limit(requests: u32) -> Limit {
+        Limit {
+            requests,
+            period_seconds: 60,
+        }
+    }
+
+    #[test]
+    fn test_resolves_limits() {
+        let config = RateLimitConfig {
+            default: Some(limit(100)),
+            clients: HashMap::from([("importer".to_owned(), limit(1000))]),
+            endpoints: vec![
+                EndpointLimit {
+                    path: "/api/airports".to_owned(),
+                    limit: limit(50),
+                },
+                EndpointLimit {
+                    path: "/api/airports/lookup".to_owned(),
+                    limit: limit(10),
+                },
+            ],
+            ..RateLimitConfig::default()
+        };
+
+        assert_eq!(
+            config.limits("alice", "/api/distance/airports"),
+            vec![("default", limit(100))]
+        );
+        assert_eq!(
+            config.limits("importer", "/api/distance/airports"),
+            vec![("importer", limit(1000))]
+        );
+        assert_eq!(
+            config.limits("importer", "/api/airports/lookup"),
+            vec![
+                ("importer", limit(1000)),
+                ("/api/airports/lookup", limit(10))
+            ]
+        );
+        assert_eq!(
+            config.limits("alice", "/api/airports"),
+            vec![("default", limit(100)), ("/api/airports", limit(50))]
+        );
+        assert_eq!(
+            config.limits("alice", "/api/airportsX"),
+            vec![("default", limit(100))]
+        );
+        assert!(RateLimitConfig::default()
+            .limits("alice", "/api/airports")
+            .is_empty());
+
+        // failed authentications are keyed by the peer address, so they're only limited on request
+        let config =
+            toml::from_str::<RateLimitConfig>("[default]\nrequests = 100\nperiod_seconds = 60\n")
+                .unwrap();
+        assert_eq!(config.failed_authentications, None);
+    }
+
+    #[actix_web::test]
+    async fn test_rejects_requests_over_limit() {
+        let rate_limits = Arc::new(RateLimits::new(RateLimitConfig {
+            default: Some(limit(5)),
+            clients: HashMap::new(),
+            endpoints: vec![EndpointLimit {
+                path: "/api/airports/lookup".to_owned(),
+                limit: limit(1),
+            }],
+            ..RateLimitConfig::default()
+        }));
+        let app = actix_web::test::init_service(
+            App::new()
+                .service(lookup_handler)
+                .service(iatas_handler)
+                .wrap(RateLimiter::new(rate_limits)),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/airports/lookup")
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("ratelimit-limit").unwrap(), "1");
+        assert_eq!(resp.headers().get("ratelimit-remaining").unwrap(), "0");
+
+        let req = actix_web::test::TestRequest::get()
+            .uri("/api/airports/lookup")
+            .peer_addr("10.0.0.1:4001".parse().unwrap())
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers().get(RETRY_AFTER).unwrap(), "60");
+
+        // other endpoints and clients have their own buckets
+        for (uri, peer_addr) in [
+            ("/api/airports/iatas", "10.0.0.1:4002"),
+            ("/api/airports/lookup", "10.0.0.2:4000"),
+        ] {
+            let req = actix_web::test::TestRequest::get()
+                .uri(uri)
+                .peer_addr(peer_addr.parse().unwrap())
+                .to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+
+            assert_eq!(resp.status(), StatusCode::OK, "{uri} {peer_addr}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_counts_requests_per_principal() {
+        let rate_limits = Arc::new(RateLimits::new(RateLimitConfig {
+            default: Some(limit(1)),
+            clients: HashMap::from([("user:alice".to_owned(), limit(2))]),
+            ..RateLimitConfig::default()
+        }));
+        let app = actix_web::test::init_service(
+            App::new()
+                .service(iatas_handler)
+                .wrap(RateLimiter::new(rate_limits))
+                .wrap_fn(|request, service| {
+                    request
+                        .extensions_mut()
+                        .insert(Principal::user("alice", vec![]));
+                    service.call(request)
+                }),
+        )
+        .await;
+
+        let mut statuses = vec![];
+        for _ in 0..3 {
+            let req = actix_web::test::TestRequest::get()
+                .uri("/api/airports/iatas")
+                .to_request();
+            statuses.push(actix_web::test::call_service(&app, req).await.status());
+        }
+
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::TOO_MANY_REQUESTS
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_endpoint_limits_dont_lift_client_limits() {
+        let rate_limits = Arc::new(RateLimits::new(RateLimitConfig {
+            default: Some(limit(2)),
+            endpoints: vec![EndpointLimit {
+                path: "/api/airports/lookup".to_owned(),
+                limit: limit(5),
+            }],
+            ..RateLimitConfig::default()
+        }));
+        let app = actix_web::test::init_service(
+            App::new()
+                .service(lookup_handler)
+                .wrap(RateLimiter::new(rate_limits)),
+        )
+        .await;
+
+        let mut statuses = vec![];
+        for _ in 0..3 {
+            let req = actix_web::test::TestRequest::get()
+                .uri("/api/airports/lookup")
+                .to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+            statuses.push(resp.status());
+        }
+
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::TOO_MANY_REQUESTS
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_limits_failed_authentications() {
+        let rate_limits = Arc::new(RateLimits::new(RateLimitConfig {
+            failed_authentications: Some(limit(2)),
+            ..RateLimitConfig::default()
+        }));
+        // same layers as the `/api` scope of the app
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(Data::new(ApiCredentials::new("alice", "secret")))
+                .service(iatas_handler)
+                .wrap(RateLimiter::new(rate_limits.clone()))
+                .wrap(HttpAuthentication::with_fn(auth::api_auth_validator))
+                .wrap(AuthenticationLimiter::new(rate_limits)),
+        )
+        .await;
+
+        let request = |password: &'static str| {
+            actix_web::test::TestRequest::get()
+                .uri("/api/airports/iatas")
+                .insert_header(Authorization::from(Basic::new("alice", Some(password))))
+                .to_request()
+        };
+
+        let resp = actix_web::test::call_service(&app, request("secret")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for _ in 0..2 {
+            let resp = actix_web::test::call_service(&app, request("guess")).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        // the address is locked out before its credentials are checked, even valid ones
+        for password in ["guess", "secret"] {
+            let resp = actix_web::test::call_service(&app, request(password)).await;
+            assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(resp.headers().get(RETRY_AFTER).unwrap(), "30");
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+/// Number of requests allowed per period, which are also allowed in a single burst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Limit {
+    pub requests: u32,
+    pub period_seconds: u64,
+}
+
+impl Limit {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.requests > 0 && self.period_seconds > 0 {
+            true => Ok(()),
+            false => Err("requests and period_seconds have to be positive".to_owned()),
+        }
+    }
+
+    /// Tokens added to the bucket per second.
+    fn refill_rate(&self) -> f64 {
+        self.requests as f64 / self.period_seconds as f64
+    }
+}
+
+/// Outcome of taking a token from a bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the bucket is full again.
+    pub reset: Duration,
+    /// Time until the next request is allowed, for rejected requests.
+    pub retry_after: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn full(limit: &Limit, now: Instant) -> Self {
+        Self {
+            tokens: limit.requests as f64,
+            updated_at: now,
+        }
+    }
+
+    pub fn take(&mut self, limit: &Limit, now: Instant) -> Decision {
+        self.decide(limit, now, true)
+    }
+
+    /// Same as `take`, but an allowed request doesn't use up a token.
+    pub fn peek(&mut self, limit: &Limit, now: Instant) -> Decision {
+        self.decide(limit, now, false)
+    }
+
+    fn decide(&mut self, limit: &Limit, now: Instant, take: bool) -> Decision {
+        let capacity = limit.requests as f64;
+        let refill_rate = limit.refill_rate();
+
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * refill_rate).min(capacity);
+        self.updated_at = now;
+
+        let allowed = self.tokens >= 1.;
+        let retry_after = match allowed {
+            true => {
+                if take {
+                    self.tokens -= 1.;
+                }
+                None
+            }
+            false => Some(Duration::from_secs_f64((1. - self.tokens) / refill_rate)),
+        };
+
+        Decision {
+            allowed,
+            limit: limit.requests,
+            remaining: self.tokens.floor() as u32,
+            reset: Duration::from_secs_f64((capacity - self.tokens) / refill_rate),
+            retry_after,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allows_bursts_up_to_capacity() {
+        let limit = Limit {
+            requests: 3,
+            period_seconds: 3,
+        };
+        let now = Instant::now();
+        let mut bucket = TokenBucket::full(&limit, now);
+
+        let remaining = (0..3)
+            .map(|_| bucket.take(&limit, now).remaining)
+            .collect::<Vec<u32>>();
+        assert_eq!(remaining, vec![2, 1, 0]);
+
+        let rejected = bucket.take(&limit, now);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.retry_after, Some(Duration::from_secs(1)));
+        assert_eq!(rejected.reset, Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_refills_over_time() {
+        let limit = Limit {
+            requests: 2,
+            period_seconds: 10,
+        };
+        let now = Instant::now();
+        let mut bucket = TokenBucket::full(&limit, now);
+        bucket.take(&limit, now);
+        bucket.take(&limit, now);
+
+        assert!(!bucket.take(&limit, now + Duration::from_secs(4)).allowed);
+
+        let refilled = bucket.take(&limit, now + Duration::from_secs(6));
+        assert!(refilled.allowed);
+        assert_eq!(refilled.remaining, 0);
+
+        // tokens don't accumulate beyond capacity
+        let idle = bucket.take(&limit, now + Duration::from_secs(100));
+        assert_eq!(idle.remaining, 1);
+    }
+}
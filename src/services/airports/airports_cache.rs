@@ -0,0 +1,285 @@
+use std::borrow::Borrow;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use moka::future::Cache;
+
+use crate::models::{Airport, CacheStats, DatasetMetadata};
+
+/// Capacity and expiration of the caches of database backed repositories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Maximum number of cached airports.
+    pub capacity: u64,
+    /// Time after which cached entries are refreshed from the database, `None` keeps them
+    /// until they're evicted.
+    pub time_to_live: Option<Duration>,
+    /// Time after which entries which weren't read are dropped.
+    pub time_to_idle: Option<Duration>,
+    /// Time codes which weren't found are remembered as missing, so airports added to the
+    /// database become visible.
+    pub negative_time_to_live: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 9300,
+            time_to_live: None,
+            time_to_idle: None,
+            negative_time_to_live: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// Cache counting its hits, misses and evictions.
+#[derive(Clone)]
+pub struct MeteredCache<K, V> {
+    name: &'static str,
+    cache: Cache<K, V>,
+    counters: Arc<Counters>,
+}
+
+impl<K, V> MeteredCache<K, V>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    pub fn new(
+        name: &'static str,
+        capacity: u64,
+        time_to_live: Option<Duration>,
+        time_to_idle: Option<Duration>,
+    ) -> Self {
+        let counters = Arc::new(Counters::default());
+        let eviction_counters = counters.clone();
+
+        let mut builder = Cache::builder()
+            .max_capacity(capacity)
+            .eviction_listener_with_queued_delivery_mode(move |_, _, cause| {
+                if cause.was_evicted() {
+                    eviction_counters.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        if let Some(time_to_live) = time_to_live {
+            builder = builder.time_to_live(time_to_live);
+        }
+        if let Some(time_to_idle) = time_to_idle {
+            builder = builder.time_to_idle(time_to_idle);
+        }
+
+        Self {
+            name,
+            cache: builder.build(),
+            counters,
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_fresh(key, |_| true)
+    }
+
+    /// Same as `get`, but values which aren't `fresh` anymore are treated as missing.
+    pub fn get_fresh<Q>(&self, key: &Q, fresh: impl FnOnce(&V) -> bool) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let value = self.cache.get(key).filter(fresh);
+
+        let counter = match value {
+            Some(_) => &self.counters.hits,
+            None => &self.counters.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        value
+    }
+
+    pub async fn insert(&self, key: K, value: V) {
+        self.cache.insert(key, value).await
+    }
+
+    pub fn invalidate_all(&self) {
+        self.cache.invalidate_all()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            name: self.name.to_owned(),
+            entries: self.cache.entry_count(),
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Airport, or a code which wasn't found, cached by a database backed repository.
+#[derive(Clone)]
+struct CachedAirport {
+    airport: Option<Airport>,
+    cached_at: Instant,
+}
+
+/// Caches shared by the database backed repositories. Codes which weren't found are cached
+/// along with the found airports, so every lookup is counted once, but they expire sooner.
+#[derive(Clone)]
+pub struct AirportsCaches {
+    airports: MeteredCache<String, CachedAirport>,
+    negative_time_to_live: Duration,
+    pub iatas: MeteredCache<(), Vec<String>>,
+    pub dataset_metadata: MeteredCache<(), Option<DatasetMetadata>>,
+}
+
+impl AirportsCaches {
+    pub fn new(config: &CacheConfig) -> Self {
+        Self {
+            airports: MeteredCache::new(
+                "airports",
+                config.capacity,
+                config.time_to_live,
+                config.time_to_idle,
+            ),
+            negative_time_to_live: config.negative_time_to_live,
+            iatas: MeteredCache::new("iatas", 1, config.time_to_live, config.time_to_idle),
+            dataset_metadata: MeteredCache::new(
+                "dataset_metadata",
+                1,
+                config.time_to_live,
+                config.time_to_idle,
+            ),
+        }
+    }
+
+    /// `Some(None)` for codes known to be missing, `None` for codes which aren't cached.
+    pub fn airport(&self, iata_code: &str) -> Option<Option<Airport>> {
+        self.airports
+            .get_fresh(iata_code, |cached| {
+                cached.airport.is_some() || cached.cached_at.elapsed() < self.negative_time_to_live
+            })
+            .map(|cached| cached.airport)
+    }
+
+    pub async fn insert_airport(&self, iata_code: &str, airport: Option<Airport>) {
+        let cached = CachedAirport {
+            airport,
+            cached_at: Instant::now(),
+        };
+
+        self.airports.insert(iata_code.to_owned(), cached).await
+    }
+
+    pub fn invalidate_all(&self) {
+        self.airports.invalidate_all();
+        self.iatas.invalidate_all();
+        self.dataset_metadata.invalidate_all();
+    }
+
+    pub fn stats(&self) -> Vec<CacheStats> {
+        vec![
+            self.airports.stats(),
+            self.iatas.stats(),
+            self.dataset_metadata.stats(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use moka::future::ConcurrentCacheExt;
+
+    use super::*;
+
+    fn airport(iata_code: &str) -> Airport {
+        Airport {
+            id: 1,
+            icao_code: "N/A".to_owned(),
+            iata_code: iata_code.to_owned(),
+            name: iata_code.to_owned(),
+            city: String::new(),
+            country: String::new(),
+            lat_deg: 0,
+            lat_min: 0,
+            lat_sec: 0,
+            lat_dir: "N".to_owned(),
+            lon_deg: 0,
+            lon_min: 0,
+            lon_sec: 0,
+            lon_dir: "E".to_owned(),
+            altitude: 0,
+            lat_decimal: 1.,
+            lon_decimal: 1.,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_counts_hits_and_misses() {
+        let caches = AirportsCaches::new(&CacheConfig::default());
+
+        assert_eq!(caches.airport("KRK"), None);
+        caches.insert_airport("KRK", Some(airport("KRK"))).await;
+        caches.insert_airport("XXX", None).await;
+
+        assert_eq!(caches.airport("KRK"), Some(Some(airport("KRK"))));
+        assert_eq!(caches.airport("XXX"), Some(None));
+
+        // the negative hit is counted once, as a hit
+        let stats = caches.stats();
+        assert_eq!(stats[0].name, "airports");
+        assert_eq!((stats[0].hits, stats[0].misses), (2, 1));
+    }
+
+    #[actix_web::test]
+    async fn test_expires_missing_airports() {
+        let caches = AirportsCaches::new(&CacheConfig {
+            negative_time_to_live: Duration::from_millis(50),
+            ..CacheConfig::default()
+        });
+
+        caches.insert_airport("KRK", Some(airport("KRK"))).await;
+        caches.insert_airport("XXX", None).await;
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(caches.airport("XXX"), None);
+        assert_eq!(caches.airport("KRK"), Some(Some(airport("KRK"))));
+
+        let stats = caches.stats();
+        assert_eq!((stats[0].hits, stats[0].misses), (1, 1));
+    }
+
+    #[actix_web::test]
+    async fn test_evicts_over_capacity() {
+        let cache = MeteredCache::new("test", 2, None, None);
+
+        for key in 0..10 {
+            cache.insert(key, ()).await;
+            cache.cache.sync();
+        }
+
+        // eviction notifications are delivered from a background thread
+        for _ in 0..50 {
+            if cache.stats().evictions == 8 {
+                break;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.evictions, 8);
+    }
+}
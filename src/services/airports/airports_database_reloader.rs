@@ -0,0 +1,280 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::log;
+
+use crate::models::DatasetMetadata;
+
+use super::{
+    open_airports_repository, AirportsOverlayStore, AirportsRepository, AirportsRepositoryHandle,
+    OverlayAirportsRepository, RepositoryOptions,
+};
+
+#[derive(Debug)]
+pub enum ReloadError {
+    Database(sqlx::Error),
+    InvalidDatabase(String),
+    /// The requested database is outside of the directory of the configured one.
+    ForbiddenPath(String),
+}
+
+impl std::error::Error for ReloadError {}
+impl std::fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReloadError::Database(e) => write!(f, "ReloadError: {e}"),
+            ReloadError::InvalidDatabase(reason) => write!(f, "ReloadError: {reason}"),
+            ReloadError::ForbiddenPath(reason) => write!(f, "ReloadError: {reason}"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ReloadError {
+    fn from(e: sqlx::Error) -> Self {
+        ReloadError::Database(e)
+    }
+}
+
+/// Outcome of a successful reload.
+#[derive(Debug)]
+pub struct ReloadSummary {
+    pub database_url: String,
+    pub airports_count: usize,
+    pub dataset: Option<DatasetMetadata>,
+}
+
+/// Opens airports databases and swaps them into the served `AirportsRepositoryHandle`.
+pub struct AirportsDatabaseReloader {
+    default_database_url: String,
+    options: RepositoryOptions,
+    overlay: Option<Arc<AirportsOverlayStore>>,
+}
+
+impl AirportsDatabaseReloader {
+    pub fn new(default_database_url: &str) -> Self {
+        Self {
+            default_database_url: default_database_url.to_owned(),
+            options: RepositoryOptions::default(),
+            overlay: None,
+        }
+    }
+
+    /// Opens reloaded databases with the given `options`.
+    pub fn with_options(mut self, options: RepositoryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Keeps layering `overlay` over every reloaded database.
+    pub fn with_overlay(mut self, overlay: Arc<AirportsOverlayStore>) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// Wraps `repository` the same way the application serves it.
+    pub fn serve(&self, repository: Box<dyn AirportsRepository>) -> Box<dyn AirportsRepository> {
+        match &self.overlay {
+            Some(overlay) => Box::new(OverlayAirportsRepository::new(
+                repository.into(),
+                overlay.clone(),
+            )),
+            None => repository,
+        }
+    }
+
+    /// Opens the database at `database_url` (or the one the application started with),
+    /// validates it and, only if it's valid, makes it the served repository. The previous
+    /// repository keeps serving requests which already took a snapshot of it and is dropped,
+    /// together with its connection pool, once they finish.
+    pub async fn reload(
+        &self,
+        handle: &AirportsRepositoryHandle,
+        database_url: Option<&str>,
+    ) -> Result<ReloadSummary, ReloadError> {
+        let database_url = match database_url {
+            Some(database_path) => self.allowed_database_url(database_path)?,
+            None => self.default_database_url.clone(),
+        };
+        let database_url = database_url.as_str();
+
+        let repository = open_airports_repository(database_url, &self.options).await?;
+        let airports_count = validate_airports_database(repository.as_ref()).await?;
+        let dataset = repository.dataset_metadata().await?;
+
+        let previous = handle.replace(self.serve(repository).into());
+        previous.invalidate_caches().await;
+
+        log::info!("Reloaded airports database from {database_url}, {airports_count} airports");
+
+        Ok(ReloadSummary {
+            database_url: database_url.to_owned(),
+            airports_count,
+            dataset,
+        })
+    }
+
+    /// URL of `database_path`, which may only be an SQLite file in the directory of the
+    /// configured database. The path is canonicalised, so that links and `..` can't escape it.
+    fn allowed_database_url(&self, database_path: &str) -> Result<String, ReloadError> {
+        let Some(default_path) = sqlite_path(&self.default_database_url) else {
+            return Err(ReloadError::ForbiddenPath(
+                "only the configured database can be reloaded".to_owned(),
+            ));
+        };
+        let allowed_directory = canonical_directory(&default_path)?;
+
+        let database_path = sqlite_path(database_path).unwrap_or_else(|| database_path.into());
+        let database_path = database_path.canonicalize().map_err(|e| {
+            ReloadError::InvalidDatabase(format!("{}: {e}", database_path.display()))
+        })?;
+        if database_path.parent() != Some(allowed_directory.as_path()) {
+            return Err(ReloadError::ForbiddenPath(format!(
+                "databases can only be reloaded from {}",
+                allowed_directory.display()
+            )));
+        }
+
+        Ok(format!("sqlite:{}", database_path.display()))
+    }
+}
+
+/// File path of an `sqlite:` URL or a plain path, `None` for other URLs.
+fn sqlite_path(database_url: &str) -> Option<PathBuf> {
+    let path = match database_url.split_once("://") {
+        Some((_, _)) if !database_url.starts_with("sqlite://") => return None,
+        _ => database_url
+            .strip_prefix("sqlite://")
+            .or_else(|| database_url.strip_prefix("sqlite:"))
+            .unwrap_or(database_url),
+    };
+
+    // options like `?mode=ro` aren't part of the path
+    let path = path.split('?').next().unwrap_or(path);
+    Some(PathBuf::from(path))
+}
+
+fn canonical_directory(database_path: &Path) -> Result<PathBuf, ReloadError> {
+    let directory = match database_path.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+
+    directory
+        .canonicalize()
+        .map_err(|e| ReloadError::InvalidDatabase(format!("{}: {e}", directory.display())))
+}
+
+/// Makes sure the database has the `airports` table in the expected shape, readable and not
+/// empty, before it's served on startup or reload. Returns the number of airports.
+pub async fn validate_airports_database(
+    repository: &dyn AirportsRepository,
+) -> Result<usize, ReloadError> {
+    // counting reads every page of the table, so a corrupt file fails here rather than on
+    // requests, readiness probes only check that the table can be queried
+    let airports_count = repository.count_airports().await?;
+    if airports_count == 0 {
+        return Err(ReloadError::InvalidDatabase(
+            "database doesn't contain any airports".to_owned(),
+        ));
+    }
+
+    let iatas = repository.unique_airport_iatas().await?;
+    let Some(iata) = iatas.first() else {
+        return Err(ReloadError::InvalidDatabase(
+            "database doesn't contain any airports with IATA codes".to_owned(),
+        ));
+    };
+
+    // decoding a full row makes sure all columns of the `Airport` model are present
+    repository.fetch_airport_by_iata(iata).await?;
+
+    Ok(airports_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::airports::DummyAirportsRepository;
+    use crate::DATABASE_URL;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_reloads_database() {
+        let handle = AirportsRepositoryHandle::new(Arc::new(DummyAirportsRepository::new(vec![])));
+        let reloader = AirportsDatabaseReloader::new(DATABASE_URL);
+
+        let summary = reloader.reload(&handle, None).await.unwrap();
+
+        // every airport is counted, not only the ones with distinct IATA codes
+        let snapshot = handle.snapshot();
+        assert_eq!(
+            summary.airports_count,
+            snapshot.count_airports().await.unwrap()
+        );
+        assert!(summary.airports_count > snapshot.unique_airport_iatas().await.unwrap().len());
+        assert!(snapshot
+            .fetch_airport_by_iata("LHR")
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[actix_web::test]
+    async fn test_keeps_serving_previous_database_when_reload_fails() {
+        let handle = AirportsRepositoryHandle::new(Arc::new(DummyAirportsRepository::new(vec![])));
+        let previous = handle.snapshot();
+        let reloader = AirportsDatabaseReloader::new(DATABASE_URL);
+
+        let result = reloader
+            .reload(&handle, Some("sqlite:./does_not_exist.sqlite"))
+            .await;
+
+        assert!(result.is_err());
+        assert!(Arc::ptr_eq(&previous, &handle.snapshot()));
+    }
+
+    #[actix_web::test]
+    async fn test_reloads_only_databases_next_to_the_configured_one() {
+        let handle = AirportsRepositoryHandle::new(Arc::new(DummyAirportsRepository::new(vec![])));
+        let previous = handle.snapshot();
+        let reloader = AirportsDatabaseReloader::new(DATABASE_URL);
+
+        let summary = reloader
+            .reload(&handle, Some("./global_airports_database.sqlite"))
+            .await
+            .unwrap();
+        assert!(summary.database_url.starts_with("sqlite:/"));
+        assert!(summary
+            .database_url
+            .ends_with("/global_airports_database.sqlite"));
+
+        let handle = AirportsRepositoryHandle::new(previous.clone());
+        for database_path in [
+            "/etc/passwd",
+            "sqlite:/etc/passwd",
+            "./src/../src/main.rs",
+            "./src/main.rs",
+        ] {
+            let result = reloader.reload(&handle, Some(database_path)).await;
+            assert!(
+                matches!(result, Err(ReloadError::ForbiddenPath(_))),
+                "{database_path}: {result:?}"
+            );
+        }
+        assert!(Arc::ptr_eq(&previous, &handle.snapshot()));
+
+        let reloader = AirportsDatabaseReloader::new("postgres://localhost/airports");
+        let result = reloader
+            .reload(&handle, Some("./global_airports_database.sqlite"))
+            .await;
+        assert!(matches!(result, Err(ReloadError::ForbiddenPath(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_rejects_empty_database() {
+        let handle = AirportsRepositoryHandle::new(Arc::new(DummyAirportsRepository::new(vec![])));
+
+        let result = validate_airports_database(handle.snapshot().as_ref()).await;
+        assert!(matches!(result, Err(ReloadError::InvalidDatabase(_))));
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Row, SqlitePool};
+
+use crate::models::Airport;
+
+const CREATE_OVERLAY_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS overlay_airports
+    (
+        iata_code char(3) primary key,
+        deleted   int     not null default 0,
+        airport   text
+    )";
+
+const CREATE_AUDIT_LOG_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS overlay_audit_log
+    (
+        id         integer primary key autoincrement,
+        changed_at int     not null,
+        changed_by text    not null,
+        action     text    not null,
+        iata_code  char(3) not null,
+        previous   text,
+        current    text
+    )";
+
+/// Change recorded in the overlay on top of the public dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverlayEntry {
+    /// Airport created in, or corrected by, the overlay.
+    Airport(Box<Airport>),
+    /// Airport hidden by the overlay, even if the public dataset contains it.
+    Deleted,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, paperclip::actix::Apiv2Schema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlayAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl OverlayAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            OverlayAction::Create => "create",
+            OverlayAction::Update => "update",
+            OverlayAction::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OverlayError {
+    Database(sqlx::Error),
+    /// Creating an airport which is already served.
+    AlreadyExists,
+    /// Updating or deleting an airport which isn't served.
+    NotFound,
+}
+
+impl std::error::Error for OverlayError {}
+impl std::fmt::Display for OverlayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OverlayError::Database(e) => write!(f, "OverlayError: {e}"),
+            OverlayError::AlreadyExists => write!(f, "OverlayError: airport already exists"),
+            OverlayError::NotFound => write!(f, "OverlayError: airport not found"),
+        }
+    }
+}
+
+impl From<sqlx::Error> for OverlayError {
+    fn from(e: sqlx::Error) -> Self {
+        OverlayError::Database(e)
+    }
+}
+
+/// Airport served before and after a change recorded in the overlay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayChange {
+    pub previous: Option<Airport>,
+    pub current: Option<Airport>,
+}
+
+/// Single change of the overlay, as stored in the audit trail.
+#[derive(Debug, Clone, Serialize, Deserialize, paperclip::actix::Apiv2Schema)]
+pub struct OverlayAuditEntry {
+    pub id: i64,
+    /// Unix timestamp of the change.
+    pub changed_at: i64,
+    pub changed_by: String,
+    pub action: OverlayAction,
+    pub iata_code: String,
+    pub previous: Option<Airport>,
+    pub current: Option<Airport>,
+}
+
+/// Writable SQLite store with custom and corrected airports, layered over the read only
+/// dataset by `OverlayAirportsRepository`. All entries are kept in memory, as the store is
+/// their only writer.
+pub struct AirportsOverlayStore {
+    pool: SqlitePool,
+    entries: RwLock<HashMap<String, OverlayEntry>>,
+    /// Serialises changes, so that each one reads the row written by the previous one.
+    writes: Mutex<()>,
+}
+
+impl AirportsOverlayStore {
+    /// Opens the overlay database at `database_url`, creating it if it doesn't exist yet.
+    pub async fn open(database_url: &str) -> Result<Self, sqlx::Error> {
+        let db_opts = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+        let pool = SqlitePool::connect_with(db_opts).await?;
+
+        sqlx::query(CREATE_OVERLAY_TABLE).execute(&pool).await?;
+        sqlx::query(CREATE_AUDIT_LOG_TABLE).execute(&pool).await?;
+
+        let rows = sqlx::query("SELECT iata_code, deleted, airport FROM overlay_airports")
+            .fetch_all(&pool)
+            .await?;
+
+        let mut entries = HashMap::new();
+        for row in rows {
+            let entry = match row.get::<bool, _>("deleted") {
+                true => OverlayEntry::Deleted,
+                false => OverlayEntry::Airport(Box::new(decode_airport(row.get("airport"))?)),
+            };
+
+            entries.insert(row.get("iata_code"), entry);
+        }
+
+        Ok(Self {
+            pool,
+            entries: RwLock::new(entries),
+            writes: Mutex::new(()),
+        })
+    }
+
+    /// Checks that the overlay database can be queried, for readiness probes.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1 FROM overlay_airports LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    pub fn get(&self, iata_code: &str) -> Option<OverlayEntry> {
+        self.entries
+            .read()
+            .expect("Overlay lock poisoned")
+            .get(iata_code)
+            .cloned()
+    }
+
+    pub fn entries(&self) -> HashMap<String, OverlayEntry> {
+        self.entries.read().expect("Overlay lock poisoned").clone()
+    }
+
+    /// Stores `current` (or a deletion when `None`) for `iata_code` and records the change,
+    /// together with the airport it replaced, in the audit trail. The replaced airport is
+    /// read from the overlay in the same transaction, `dataset` is the one served when the
+    /// overlay has no entry for `iata_code` yet. An updated airport keeps the id of the
+    /// airport it replaces, a created one gets an id above the ids of all airports in the
+    /// overlay, and at least the one it was given.
+    pub async fn record(
+        &self,
+        action: OverlayAction,
+        iata_code: &str,
+        dataset: Option<&Airport>,
+        current: Option<Airport>,
+        changed_by: &str,
+        changed_at: i64,
+    ) -> Result<OverlayChange, OverlayError> {
+        let _write = self.writes.lock().await;
+        let mut transaction = self.pool.begin().await?;
+
+        let row = sqlx::query("SELECT deleted, airport FROM overlay_airports WHERE iata_code = ?")
+            .bind(iata_code)
+            .fetch_optional(&mut transaction)
+            .await?;
+        let previous = match row {
+            Some(row) => match row.get::<bool, _>("deleted") {
+                true => None,
+                false => Some(decode_airport(row.get("airport"))?),
+            },
+            None => dataset.cloned(),
+        };
+
+        let current = match (action, &previous) {
+            (OverlayAction::Create, Some(_)) => return Err(OverlayError::AlreadyExists),
+            (OverlayAction::Update | OverlayAction::Delete, None) => {
+                return Err(OverlayError::NotFound)
+            }
+            (OverlayAction::Delete, Some(_)) => None,
+            (OverlayAction::Update, Some(previous)) => current.map(|current| Airport {
+                id: previous.id,
+                ..current
+            }),
+            (OverlayAction::Create, None) => current.map(|current| Airport {
+                id: current.id.max(self.next_airport_id()),
+                ..current
+            }),
+        };
+
+        let encoded_previous = previous.as_ref().map(encode_airport).transpose()?;
+        let encoded_current = current.as_ref().map(encode_airport).transpose()?;
+
+        sqlx::query(
+            "INSERT INTO overlay_airports (iata_code, deleted, airport) VALUES (?, ?, ?)
+             ON CONFLICT (iata_code) DO UPDATE SET deleted = excluded.deleted, airport = excluded.airport",
+        )
+        .bind(iata_code)
+        .bind(current.is_none())
+        .bind(&encoded_current)
+        .execute(&mut transaction)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO overlay_audit_log (changed_at, changed_by, action, iata_code, previous, current)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(changed_at)
+        .bind(changed_by)
+        .bind(action.as_str())
+        .bind(iata_code)
+        .bind(&encoded_previous)
+        .bind(&encoded_current)
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        let entry = match &current {
+            Some(current) => OverlayEntry::Airport(Box::new(current.clone())),
+            None => OverlayEntry::Deleted,
+        };
+
+        self.entries
+            .write()
+            .expect("Overlay lock poisoned")
+            .insert(iata_code.to_owned(), entry);
+
+        Ok(OverlayChange { previous, current })
+    }
+
+    fn next_airport_id(&self) -> u32 {
+        self.entries
+            .read()
+            .expect("Overlay lock poisoned")
+            .values()
+            .filter_map(|entry| match entry {
+                OverlayEntry::Airport(airport) => Some(airport.id + 1),
+                OverlayEntry::Deleted => None,
+            })
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Returns the audit trail, most recent changes first.
+    pub async fn audit_log(&self) -> Result<Vec<OverlayAuditEntry>, sqlx::Error> {
+        let rows = sqlx::query("SELECT * FROM overlay_audit_log ORDER BY id DESC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter()
+            .map(|row| {
+                let action = match row.get::<&str, _>("action") {
+                    "create" => OverlayAction::Create,
+                    "update" => OverlayAction::Update,
+                    _ => OverlayAction::Delete,
+                };
+
+                Ok(OverlayAuditEntry {
+                    id: row.get("id"),
+                    changed_at: row.get("changed_at"),
+                    changed_by: row.get("changed_by"),
+                    action,
+                    iata_code: row.get("iata_code"),
+                    previous: row
+                        .get::<Option<String>, _>("previous")
+                        .map(decode_airport)
+                        .transpose()?,
+                    current: row
+                        .get::<Option<String>, _>("current")
+                        .map(decode_airport)
+                        .transpose()?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn encode_airport(airport: &Airport) -> Result<String, sqlx::Error> {
+    serde_json::to_string(airport).map_err(|e| sqlx::Error::Protocol(e.to_string()))
+}
+
+fn decode_airport(airport: String) -> Result<Airport, sqlx::Error> {
+    serde_json::from_str(&airport).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
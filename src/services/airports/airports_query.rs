@@ -0,0 +1,484 @@
+use std::cmp::Ordering;
+
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use sqlx::{Database, Encode, QueryBuilder, Type};
+
+use crate::models::Airport;
+
+/// Placeholder stored instead of missing codes.
+const MISSING_CODE: &str = "N/A";
+
+/// Airport fields listings can be sorted by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Apiv2Schema)]
+#[serde(rename_all = "snake_case")]
+pub enum AirportSort {
+    #[default]
+    Id,
+    IataCode,
+    IcaoCode,
+    Name,
+    City,
+    Country,
+    Altitude,
+}
+
+impl AirportSort {
+    fn column(&self) -> &'static str {
+        match self {
+            AirportSort::Id => "id",
+            AirportSort::IataCode => "iata_code",
+            AirportSort::IcaoCode => "icao_code",
+            AirportSort::Name => "name",
+            AirportSort::City => "city",
+            AirportSort::Country => "country",
+            AirportSort::Altitude => "altitude",
+        }
+    }
+
+    fn key(&self, airport: &Airport) -> SortKey {
+        match self {
+            AirportSort::Id => SortKey::Integer(airport.id as i64),
+            AirportSort::IataCode => SortKey::Text(airport.iata_code.to_owned()),
+            AirportSort::IcaoCode => SortKey::Text(airport.icao_code.to_owned()),
+            AirportSort::Name => SortKey::Text(airport.name.to_owned()),
+            AirportSort::City => SortKey::Text(airport.city.to_owned()),
+            AirportSort::Country => SortKey::Text(airport.country.to_owned()),
+            AirportSort::Altitude => SortKey::Integer(airport.altitude),
+        }
+    }
+}
+
+/// Value of the sorted field.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SortKey {
+    Integer(i64),
+    Text(String),
+}
+
+/// Position in a listing, airports are ordered by the sorted field, then by IATA code and
+/// id, so that every airport has a distinct position even if the sorted field repeats.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct AirportCursor {
+    pub key: SortKey,
+    pub iata_code: String,
+    pub id: u32,
+}
+
+/// Rectangle spanning from `min_longitude` eastwards to `max_longitude`, crossing the
+/// antimeridian when `min_longitude` is greater than `max_longitude`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_latitude: f64,
+    pub max_latitude: f64,
+    pub min_longitude: f64,
+    pub max_longitude: f64,
+}
+
+impl BoundingBox {
+    pub fn crosses_antimeridian(&self) -> bool {
+        self.min_longitude > self.max_longitude
+    }
+
+    fn contains(&self, airport: &Airport) -> bool {
+        let latitude = (self.min_latitude..=self.max_latitude).contains(&airport.lat_decimal);
+        let longitude = match self.crosses_antimeridian() {
+            true => {
+                airport.lon_decimal >= self.min_longitude
+                    || airport.lon_decimal <= self.max_longitude
+            }
+            false => (self.min_longitude..=self.max_longitude).contains(&airport.lon_decimal),
+        };
+
+        latitude && longitude
+    }
+}
+
+/// Conditions airports have to meet to be listed, `None` fields don't filter anything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AirportFilter {
+    /// ICAO code, compared case-insensitively.
+    pub icao_code: Option<String>,
+    /// Country name, compared ignoring the case of ASCII letters only, same as the databases.
+    pub country: Option<String>,
+    /// City name, compared ignoring the case of ASCII letters only, same as the databases.
+    pub city: Option<String>,
+    pub has_iata: Option<bool>,
+    pub has_icao: Option<bool>,
+    pub bounding_box: Option<BoundingBox>,
+    pub min_altitude: Option<i64>,
+    pub max_altitude: Option<i64>,
+}
+
+impl AirportFilter {
+    pub fn matches(&self, airport: &Airport) -> bool {
+        let eq_ignore_case = |expected: &Option<String>, value: &str| match expected {
+            Some(expected) => expected.eq_ignore_ascii_case(value),
+            None => true,
+        };
+        let has_code = |expected: Option<bool>, code: &str| match expected {
+            Some(expected) => expected == (code != MISSING_CODE),
+            None => true,
+        };
+
+        eq_ignore_case(&self.icao_code, &airport.icao_code)
+            && eq_ignore_case(&self.country, &airport.country)
+            && eq_ignore_case(&self.city, &airport.city)
+            && has_code(self.has_iata, &airport.iata_code)
+            && has_code(self.has_icao, &airport.icao_code)
+            && self
+                .bounding_box
+                .is_none_or(|bounding_box| bounding_box.contains(airport))
+            && self
+                .min_altitude
+                .is_none_or(|min_altitude| airport.altitude >= min_altitude)
+            && self
+                .max_altitude
+                .is_none_or(|max_altitude| airport.altitude <= max_altitude)
+    }
+}
+
+/// Single page of an airports listing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AirportsQuery {
+    pub filter: AirportFilter,
+    pub sort: AirportSort,
+    pub descending: bool,
+    /// Lists airports positioned after the cursor only.
+    pub after: Option<AirportCursor>,
+    pub limit: usize,
+}
+
+impl AirportsQuery {
+    pub fn cursor(&self, airport: &Airport) -> AirportCursor {
+        AirportCursor {
+            key: self.sort.key(airport),
+            iata_code: airport.iata_code.to_owned(),
+            id: airport.id,
+        }
+    }
+
+    /// Orders airports the way they're listed.
+    pub fn compare(&self, a: &Airport, b: &Airport) -> Ordering {
+        let ordering = self.cursor(a).cmp(&self.cursor(b));
+
+        match self.descending {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    }
+
+    /// Whether `airport` passes the filter and is positioned after the cursor.
+    pub fn matches(&self, airport: &Airport) -> bool {
+        let after_cursor = match &self.after {
+            Some(after) => match self.descending {
+                true => self.cursor(airport) < *after,
+                false => self.cursor(airport) > *after,
+            },
+            None => true,
+        };
+
+        after_cursor && self.filter.matches(airport)
+    }
+
+    /// Lists a page out of `airports` in memory.
+    pub fn apply<'a>(&self, airports: impl IntoIterator<Item = &'a Airport>) -> Vec<Airport> {
+        let mut airports = airports
+            .into_iter()
+            .filter(|airport| self.matches(airport))
+            .collect::<Vec<&Airport>>();
+        airports.sort_by(|a, b| self.compare(a, b));
+
+        airports.into_iter().take(self.limit).cloned().collect()
+    }
+
+    /// Appends conditions, ordering and limit of the query to a `SELECT` from the `airports`
+    /// table. Text columns are compared with `text_collation`, which has to order strings by
+    /// their bytes, same as they're ordered in memory.
+    pub(super) fn push_sql<'args, DB: Database>(
+        &self,
+        builder: &mut QueryBuilder<'args, DB>,
+        text_collation: &str,
+    ) where
+        String: 'args + Encode<'args, DB> + Send + Type<DB>,
+        i64: 'args + Encode<'args, DB> + Send + Type<DB>,
+        f64: 'args + Encode<'args, DB> + Send + Type<DB>,
+    {
+        let filter = &self.filter;
+        builder.push(" WHERE name != 'N/A'");
+
+        // codes are stored upper case, so the column is compared as is and can use an index
+        if let Some(icao_code) = &filter.icao_code {
+            builder
+                .push(" AND icao_code = ")
+                .push_bind(icao_code.to_ascii_uppercase());
+        }
+        // `lower()` folds ASCII letters only under a byte-wise collation, in both SQLite and
+        // PostgreSQL, so the filters match the same airports as in memory
+        for (column, value) in [("country", &filter.country), ("city", &filter.city)] {
+            if let Some(value) = value {
+                builder
+                    .push(format!(
+                        " AND lower({column} COLLATE {text_collation}) = lower("
+                    ))
+                    .push_bind(value.to_owned())
+                    .push(format!(" COLLATE {text_collation})"));
+            }
+        }
+        for (column, has_code) in [
+            ("iata_code", filter.has_iata),
+            ("icao_code", filter.has_icao),
+        ] {
+            if let Some(has_code) = has_code {
+                let operator = if has_code { "!=" } else { "=" };
+                builder.push(format!(" AND {column} {operator} '{MISSING_CODE}'"));
+            }
+        }
+        if let Some(bounding_box) = &filter.bounding_box {
+            builder
+                .push(" AND lat_decimal BETWEEN ")
+                .push_bind(bounding_box.min_latitude)
+                .push(" AND ")
+                .push_bind(bounding_box.max_latitude);
+
+            let longitude_operator = match bounding_box.crosses_antimeridian() {
+                true => "OR",
+                false => "AND",
+            };
+            builder
+                .push(" AND (lon_decimal >= ")
+                .push_bind(bounding_box.min_longitude)
+                .push(format!(" {longitude_operator} lon_decimal <= "))
+                .push_bind(bounding_box.max_longitude)
+                .push(")");
+        }
+        if let Some(min_altitude) = filter.min_altitude {
+            builder.push(" AND altitude >= ").push_bind(min_altitude);
+        }
+        if let Some(max_altitude) = filter.max_altitude {
+            builder.push(" AND altitude <= ").push_bind(max_altitude);
+        }
+
+        let key_column = match self.sort {
+            AirportSort::Id | AirportSort::Altitude => self.sort.column().to_owned(),
+            _ => format!("{} COLLATE {text_collation}", self.sort.column()),
+        };
+        let iata_column = format!("iata_code COLLATE {text_collation}");
+        let (operator, direction) = match self.descending {
+            true => ("<", "DESC"),
+            false => (">", "ASC"),
+        };
+
+        if let Some(after) = &self.after {
+            builder.push(format!(
+                " AND ({key_column}, {iata_column}, id) {operator} ("
+            ));
+            match &after.key {
+                SortKey::Integer(key) => builder.push_bind(*key),
+                SortKey::Text(key) => builder.push_bind(key.to_owned()),
+            };
+            builder
+                .push(", ")
+                .push_bind(after.iata_code.to_owned())
+                .push(", ")
+                .push_bind(after.id as i64)
+                .push(")");
+        }
+
+        builder
+            .push(format!(
+                " ORDER BY {key_column} {direction}, {iata_column} {direction}, id {direction}"
+            ))
+            .push(" LIMIT ")
+            .push_bind(self.limit as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Sqlite;
+
+    use super::*;
+
+    fn airport(id: u32, iata_code: &str, country: &str, altitude: i64) -> Airport {
+        Airport {
+            id,
+            icao_code: "N/A".to_owned(),
+            iata_code: iata_code.to_owned(),
+            name: iata_code.to_owned(),
+            city: String::new(),
+            country: country.to_owned(),
+            lat_deg: 50,
+            lat_min: 0,
+            lat_sec: 0,
+            lat_dir: "N".to_owned(),
+            lon_deg: 179,
+            lon_min: 30,
+            lon_sec: 0,
+            lon_dir: "E".to_owned(),
+            altitude,
+            lat_decimal: 50.,
+            lon_decimal: 179.5,
+        }
+    }
+
+    fn query() -> AirportsQuery {
+        AirportsQuery {
+            filter: AirportFilter::default(),
+            sort: AirportSort::Country,
+            descending: false,
+            after: None,
+            limit: 10,
+        }
+    }
+
+    #[test]
+    fn test_orders_and_pages_airports() {
+        let airports = vec![
+            airport(1, "WAW", "POLAND", 110),
+            airport(2, "CDG", "FRANCE", 119),
+            airport(3, "KRK", "POLAND", 241),
+            airport(4, "ORY", "FRANCE", 89),
+        ];
+        let iatas = |airports: Vec<Airport>| {
+            airports
+                .into_iter()
+                .map(|airport| airport.iata_code)
+                .collect::<Vec<String>>()
+        };
+
+        let mut query = query();
+        query.limit = 3;
+        let page = query.apply(&airports);
+        assert_eq!(iatas(page.clone()), vec!["CDG", "ORY", "KRK"]);
+
+        query.after = Some(query.cursor(page.last().unwrap()));
+        assert_eq!(iatas(query.apply(&airports)), vec!["WAW"]);
+
+        let mut query = self::query();
+        query.descending = true;
+        query.filter.max_altitude = Some(200);
+        assert_eq!(iatas(query.apply(&airports)), vec!["WAW", "ORY", "CDG"]);
+    }
+
+    #[test]
+    fn test_filters_airports() {
+        let mut filter = AirportFilter {
+            country: Some("poland".to_owned()),
+            has_iata: Some(true),
+            ..AirportFilter::default()
+        };
+        assert!(filter.matches(&airport(1, "WAW", "POLAND", 110)));
+        assert!(!filter.matches(&airport(1, "N/A", "POLAND", 110)));
+        assert!(!filter.matches(&airport(1, "CDG", "FRANCE", 119)));
+
+        filter.bounding_box = Some(BoundingBox {
+            min_latitude: 40.,
+            max_latitude: 60.,
+            min_longitude: 170.,
+            max_longitude: -170.,
+        });
+        assert!(filter.matches(&airport(1, "WAW", "POLAND", 110)));
+
+        filter.bounding_box = Some(BoundingBox {
+            min_latitude: 40.,
+            max_latitude: 60.,
+            min_longitude: -170.,
+            max_longitude: 170.,
+        });
+        assert!(!filter.matches(&airport(1, "WAW", "POLAND", 110)));
+
+        // only ASCII letters are folded, like SQL `lower()` does
+        let filter = AirportFilter {
+            city: Some("ŁÓDŹ".to_owned()),
+            ..AirportFilter::default()
+        };
+        let mut lodz = airport(1, "LCJ", "POLAND", 184);
+        lodz.city = "ŁÓDŹ".to_owned();
+        assert!(filter.matches(&lodz));
+        lodz.city = "Łódź".to_owned();
+        assert!(!filter.matches(&lodz));
+    }
+
+    #[test]
+    fn test_builds_sql() {
+        let mut query = query();
+        query.filter.country = Some("Poland".to_owned());
+        query.filter.has_icao = Some(false);
+        query.after = Some(AirportCursor {
+            key: SortKey::Text("POLAND".to_owned()),
+            iata_code: "KRK".to_owned(),
+            id: 3,
+        });
+
+        let mut builder = QueryBuilder::<Sqlite>::new("SELECT * FROM airports");
+        query.push_sql(&mut builder, "BINARY");
+
+        assert_eq!(
+            builder.sql(),
+            "SELECT * FROM airports WHERE name != 'N/A' \
+             AND lower(country COLLATE BINARY) = lower(? COLLATE BINARY) \
+             AND icao_code = 'N/A' \
+             AND (country COLLATE BINARY, iata_code COLLATE BINARY, id) > (?, ?, ?) \
+             ORDER BY country COLLATE BINARY ASC, iata_code COLLATE BINARY ASC, id ASC LIMIT ?"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_folds_case_like_sqlite() {
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query(
+            "CREATE TABLE airports (id INTEGER, iata_code TEXT, icao_code TEXT, name TEXT, \
+             city TEXT, country TEXT, altitude INTEGER, lat_decimal REAL, lon_decimal REAL)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let mut airports = vec![];
+        for (id, city) in [(1, "ŁÓDŹ"), (2, "Łódź"), (3, "łódź"), (4, "ŁóDŹ")] {
+            let mut airport = airport(id, "LCJ", "POLAND", 184);
+            airport.city = city.to_owned();
+            sqlx::query("INSERT INTO airports VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)")
+                .bind(id)
+                .bind(&airport.iata_code)
+                .bind(&airport.icao_code)
+                .bind(&airport.name)
+                .bind(&airport.city)
+                .bind(&airport.country)
+                .bind(airport.altitude)
+                .bind(airport.lat_decimal)
+                .bind(airport.lon_decimal)
+                .execute(&pool)
+                .await
+                .unwrap();
+            airports.push(airport);
+        }
+
+        let mut query = query();
+        query.sort = AirportSort::Id;
+        for city in ["ŁÓDŹ", "łódź", "ŁóDź"] {
+            query.filter.city = Some(city.to_owned());
+
+            let mut builder = QueryBuilder::<Sqlite>::new("SELECT id FROM airports");
+            query.push_sql(&mut builder, "BINARY");
+            let listed = builder
+                .build_query_as::<(i64,)>()
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(id,)| id)
+                .collect::<Vec<i64>>();
+
+            let expected = query
+                .apply(&airports)
+                .iter()
+                .map(|airport| airport.id as i64)
+                .collect::<Vec<i64>>();
+            assert!(!expected.is_empty());
+            assert_eq!(listed, expected, "{city}");
+        }
+    }
+}
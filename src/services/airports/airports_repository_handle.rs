@@ -0,0 +1,74 @@
+use std::sync::{Arc, RwLock};
+
+use super::AirportsRepository;
+
+/// Holds the repository currently served by the application and allows replacing it at
+/// runtime, e.g. when the airports database is reloaded.
+pub struct AirportsRepositoryHandle {
+    current: RwLock<Arc<dyn AirportsRepository>>,
+}
+
+impl AirportsRepositoryHandle {
+    pub fn new(repository: Arc<dyn AirportsRepository>) -> Self {
+        Self {
+            current: RwLock::new(repository),
+        }
+    }
+
+    /// Returns the currently served repository. Handlers should take a single snapshot per
+    /// request, so requests in flight during a reload finish against the old dataset.
+    pub fn snapshot(&self) -> Arc<dyn AirportsRepository> {
+        self.current
+            .read()
+            .expect("Airports repository lock poisoned")
+            .clone()
+    }
+
+    /// Replaces the served repository, returning the previous one.
+    pub fn replace(&self, repository: Arc<dyn AirportsRepository>) -> Arc<dyn AirportsRepository> {
+        let mut current = self
+            .current
+            .write()
+            .expect("Airports repository lock poisoned");
+
+        std::mem::replace(&mut *current, repository)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::airports::DummyAirportsRepository;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_snapshot_survives_replace() {
+        let handle = AirportsRepositoryHandle::new(Arc::new(DummyAirportsRepository::new(vec![])));
+        let snapshot = handle.snapshot();
+
+        let replacement = DummyAirportsRepository::new(vec![]).with_dataset_metadata(
+            crate::models::DatasetMetadata {
+                source: "partow".to_owned(),
+                version: "2".to_owned(),
+                imported_at: 0,
+                rows_read: 0,
+                airports_count: 0,
+                rejected_count: 0,
+                checksum: String::new(),
+            },
+        );
+        let previous = handle.replace(Arc::new(replacement));
+
+        assert!(Arc::ptr_eq(&previous, &snapshot));
+        assert_eq!(snapshot.dataset_metadata().await.unwrap(), None);
+        assert_eq!(
+            handle
+                .snapshot()
+                .dataset_metadata()
+                .await
+                .unwrap()
+                .map(|metadata| metadata.version),
+            Some("2".to_owned())
+        );
+    }
+}
@@ -1,38 +1,64 @@
 use async_trait::async_trait;
-use moka::future::Cache;
-use sqlx::{Row, SqlitePool};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{QueryBuilder, Row, SqlitePool};
+use std::collections::HashMap;
+use std::str::FromStr;
 
-use crate::models::Airport;
+use crate::models::{Airport, CacheStats, DatasetMetadata, PoolStats};
 
-use super::AirportsRepository;
+use super::airports_cache::{AirportsCaches, CacheConfig};
+use super::{AirportsQuery, AirportsRepository};
+
+/// Maximum number of codes bound to a single lookup query, SQLite builds before 3.32 allow
+/// at most 999 parameters per statement.
+const LOOKUP_BATCH_SIZE: usize = 500;
 
 #[derive(Clone)]
 pub struct GlobalAirportsRepository {
     pool: SqlitePool,
-    airports_cache: Cache<String, Option<Airport>>,
-    iatas_cache: Cache<(), Vec<String>>,
+    caches: AirportsCaches,
 }
 
 impl GlobalAirportsRepository {
-    pub async fn new(pool: SqlitePool) -> Self {
-        let airports_cache = Cache::new(9300);
-        let iatas_cache = Cache::new(1);
+    /// Connects to the airports database at `database_url` in read only mode.
+    pub async fn open(database_url: &str) -> Result<Self, sqlx::Error> {
+        let db_opts = SqliteConnectOptions::from_str(database_url)?.read_only(true);
+        let db_pool = SqlitePool::connect_with(db_opts).await?;
+
+        Ok(Self::new(db_pool).await)
+    }
 
+    pub async fn new(pool: SqlitePool) -> Self {
         Self {
             pool,
-            airports_cache,
-            iatas_cache,
+            caches: AirportsCaches::new(&CacheConfig::default()),
         }
     }
+
+    pub fn with_cache_config(mut self, cache_config: &CacheConfig) -> Self {
+        self.caches = AirportsCaches::new(cache_config);
+        self
+    }
+
+    /// Every airport of the dataset, used to preload it into memory.
+    #[tracing::instrument(skip_all)]
+    pub async fn all_airports(&self) -> Result<Vec<Airport>, sqlx::Error> {
+        let query = "SELECT * FROM airports WHERE name != 'N/A' ORDER BY id";
+
+        sqlx::query_as::<_, Airport>(query)
+            .fetch_all(&self.pool)
+            .await
+    }
 }
 
 #[async_trait]
 impl AirportsRepository for GlobalAirportsRepository {
+    #[tracing::instrument(skip(self))]
     async fn fetch_airport_by_iata<'a>(
         &self,
         iata_code: &'a str,
     ) -> Result<Option<Airport>, sqlx::Error> {
-        match self.airports_cache.get(iata_code) {
+        match self.caches.airport(iata_code) {
             Some(airport) => return Ok(airport),
             None => {
                 let query = "SELECT * FROM airports WHERE iata_code = ? AND name != 'N/A'";
@@ -41,17 +67,67 @@ impl AirportsRepository for GlobalAirportsRepository {
                     .fetch_optional(&self.pool)
                     .await?;
 
-                self.airports_cache
-                    .insert(iata_code.to_owned(), airport.clone())
-                    .await;
+                self.caches.insert_airport(iata_code, airport.clone()).await;
 
                 Ok(airport)
             }
         }
     }
 
+    #[tracing::instrument(skip_all, fields(count = iata_codes.len()))]
+    async fn fetch_airports_by_iata(
+        &self,
+        iata_codes: &[String],
+    ) -> Result<HashMap<String, Airport>, sqlx::Error> {
+        let mut airports = HashMap::new();
+        let mut uncached_codes = Vec::new();
+
+        for iata_code in iata_codes {
+            match self.caches.airport(iata_code) {
+                Some(Some(airport)) => {
+                    airports.insert(iata_code.to_owned(), airport);
+                }
+                Some(None) => {}
+                None => uncached_codes.push(iata_code.to_owned()),
+            }
+        }
+        uncached_codes.sort();
+        uncached_codes.dedup();
+
+        for batch in uncached_codes.chunks(LOOKUP_BATCH_SIZE) {
+            let mut builder =
+                QueryBuilder::new("SELECT * FROM airports WHERE name != 'N/A' AND iata_code IN (");
+            let mut codes = builder.separated(", ");
+            for iata_code in batch {
+                codes.push_bind(iata_code.to_owned());
+            }
+            // the first of duplicated codes wins, same as for single lookups
+            builder.push(") ORDER BY id");
+
+            let mut found = HashMap::new();
+            for airport in builder
+                .build_query_as::<Airport>()
+                .fetch_all(&self.pool)
+                .await?
+            {
+                found.entry(airport.iata_code.to_owned()).or_insert(airport);
+            }
+
+            for iata_code in batch {
+                self.caches
+                    .insert_airport(iata_code, found.get(iata_code).cloned())
+                    .await;
+            }
+
+            airports.extend(found);
+        }
+
+        Ok(airports)
+    }
+
+    #[tracing::instrument(skip_all)]
     async fn unique_airport_iatas<'a>(&self) -> Result<Vec<String>, sqlx::Error> {
-        match self.iatas_cache.get(&()) {
+        match self.caches.iatas.get(&()) {
             Some(iatas) => return Ok(iatas),
             None => {
                 let query = "SELECT DISTINCT iata_code FROM airports WHERE name != 'N/A'";
@@ -62,10 +138,90 @@ impl AirportsRepository for GlobalAirportsRepository {
                     .map(|row| row.get::<String, _>("iata_code"))
                     .collect::<Vec<String>>();
 
-                self.iatas_cache.insert((), iatas.clone()).await;
+                self.caches.iatas.insert((), iatas.clone()).await;
 
                 Ok(iatas)
             }
         }
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn list_airports(&self, query: &AirportsQuery) -> Result<Vec<Airport>, sqlx::Error> {
+        let mut builder = QueryBuilder::new("SELECT * FROM airports");
+        query.push_sql(&mut builder, "BINARY");
+
+        builder
+            .build_query_as::<Airport>()
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn count_airports(&self) -> Result<usize, sqlx::Error> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM airports WHERE name != 'N/A'")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count as usize)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn dataset_metadata(&self) -> Result<Option<DatasetMetadata>, sqlx::Error> {
+        if let Some(metadata) = self.caches.dataset_metadata.get(&()) {
+            return Ok(metadata);
+        }
+
+        // databases which weren't created by the importer, such as the bundled one,
+        // don't have the metadata table at all
+        let table_query =
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'dataset_metadata'";
+        let has_metadata_table = sqlx::query_scalar::<_, i64>(table_query)
+            .fetch_one(&self.pool)
+            .await?
+            > 0;
+
+        let metadata = match has_metadata_table {
+            true => {
+                sqlx::query_as::<_, DatasetMetadata>("SELECT * FROM dataset_metadata LIMIT 1")
+                    .fetch_optional(&self.pool)
+                    .await?
+            }
+            false => None,
+        };
+
+        self.caches
+            .dataset_metadata
+            .insert((), metadata.clone())
+            .await;
+
+        Ok(metadata)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1 FROM airports LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    async fn invalidate_caches(&self) {
+        self.caches.invalidate_all();
+    }
+
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        self.caches.stats()
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+        })
+    }
 }
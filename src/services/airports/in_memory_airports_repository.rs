@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+
+use crate::models::{Airport, DatasetMetadata};
+
+use super::{AirportsQuery, AirportsRepository};
+
+/// Size of a spatial grid cell, in degrees.
+const GRID_CELL_IN_DEGREES: f64 = 1.;
+
+/// Serves airports from a dataset loaded into memory once, indexed by IATA and ICAO codes,
+/// country and location. The indexes are never modified after loading, so lookups don't
+/// take any locks.
+pub struct InMemoryAirportsRepository {
+    airports: Vec<Airport>,
+    iatas: Vec<String>,
+    by_iata: HashMap<String, usize>,
+    by_icao: HashMap<String, Vec<usize>>,
+    by_country: HashMap<String, Vec<usize>>,
+    grid: HashMap<(i32, i32), Vec<usize>>,
+    dataset_metadata: Option<DatasetMetadata>,
+}
+
+impl InMemoryAirportsRepository {
+    /// Indexes `airports`, skipping `N/A` placeholder rows. The first airport with a given
+    /// IATA code wins, same as for database lookups.
+    pub fn new(airports: Vec<Airport>, dataset_metadata: Option<DatasetMetadata>) -> Self {
+        let airports = airports
+            .into_iter()
+            .filter(|airport| airport.name != "N/A")
+            .collect::<Vec<Airport>>();
+
+        let mut iatas = Vec::new();
+        let mut by_iata = HashMap::new();
+        let mut by_icao = HashMap::<String, Vec<usize>>::new();
+        let mut by_country = HashMap::<String, Vec<usize>>::new();
+        let mut grid = HashMap::<(i32, i32), Vec<usize>>::new();
+
+        for (index, airport) in airports.iter().enumerate() {
+            if !by_iata.contains_key(&airport.iata_code) {
+                by_iata.insert(airport.iata_code.to_owned(), index);
+                iatas.push(airport.iata_code.to_owned());
+            }
+
+            by_icao
+                .entry(airport.icao_code.to_owned())
+                .or_default()
+                .push(index);
+
+            by_country
+                .entry(airport.country.to_ascii_lowercase())
+                .or_default()
+                .push(index);
+
+            grid.entry(grid_cell(airport.lat_decimal, airport.lon_decimal))
+                .or_default()
+                .push(index);
+        }
+
+        Self {
+            airports,
+            iatas,
+            by_iata,
+            by_icao,
+            by_country,
+            grid,
+            dataset_metadata,
+        }
+    }
+
+    pub fn airports_count(&self) -> usize {
+        self.airports.len()
+    }
+}
+
+impl InMemoryAirportsRepository {
+    /// Airports with the given ICAO code, compared case-insensitively.
+    pub fn airports_by_icao(&self, icao_code: &str) -> Vec<&Airport> {
+        self.by_icao
+            .get(&icao_code.to_ascii_uppercase())
+            .map(|indexes| indexes.iter().map(|index| &self.airports[*index]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Airports located in `country`, ignoring the case of ASCII letters only.
+    pub fn airports_in_country(&self, country: &str) -> Vec<&Airport> {
+        self.by_country
+            .get(&country.to_ascii_lowercase())
+            .map(|indexes| indexes.iter().map(|index| &self.airports[*index]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Airports within the given bounding box, in load order.
+    pub fn airports_in_bounds(
+        &self,
+        min_latitude: f64,
+        max_latitude: f64,
+        min_longitude: f64,
+        max_longitude: f64,
+    ) -> Vec<&Airport> {
+        let (min_row, min_column) = grid_cell(min_latitude, min_longitude);
+        let (max_row, max_column) = grid_cell(max_latitude, max_longitude);
+
+        let mut indexes = (min_row..=max_row)
+            .flat_map(|row| (min_column..=max_column).map(move |column| (row, column)))
+            .filter_map(|cell| self.grid.get(&cell))
+            .flatten()
+            .copied()
+            .filter(|index| {
+                let airport = &self.airports[*index];
+
+                (min_latitude..=max_latitude).contains(&airport.lat_decimal)
+                    && (min_longitude..=max_longitude).contains(&airport.lon_decimal)
+            })
+            .collect::<Vec<usize>>();
+        indexes.sort_unstable();
+
+        indexes
+            .into_iter()
+            .map(|index| &self.airports[index])
+            .collect()
+    }
+}
+
+fn grid_cell(latitude: f64, longitude: f64) -> (i32, i32) {
+    (
+        (latitude / GRID_CELL_IN_DEGREES).floor() as i32,
+        (longitude / GRID_CELL_IN_DEGREES).floor() as i32,
+    )
+}
+
+#[async_trait]
+impl AirportsRepository for InMemoryAirportsRepository {
+    async fn fetch_airport_by_iata<'a>(
+        &self,
+        iata_code: &'a str,
+    ) -> Result<Option<Airport>, sqlx::Error> {
+        Ok(self
+            .by_iata
+            .get(iata_code)
+            .map(|index| self.airports[*index].clone()))
+    }
+
+    async fn unique_airport_iatas<'a>(&self) -> Result<Vec<String>, sqlx::Error> {
+        Ok(self.iatas.clone())
+    }
+
+    async fn list_airports(&self, query: &AirportsQuery) -> Result<Vec<Airport>, sqlx::Error> {
+        // narrow the candidates down with an index first, the query filters them precisely
+        let filter = &query.filter;
+        let candidates = match (&filter.icao_code, &filter.country, &filter.bounding_box) {
+            (Some(icao_code), _, _) => self.airports_by_icao(icao_code),
+            (None, Some(country), _) => self.airports_in_country(country),
+            (None, None, Some(bounding_box)) if bounding_box.crosses_antimeridian() => {
+                let mut candidates = self.airports_in_bounds(
+                    bounding_box.min_latitude,
+                    bounding_box.max_latitude,
+                    bounding_box.min_longitude,
+                    180.,
+                );
+                candidates.extend(self.airports_in_bounds(
+                    bounding_box.min_latitude,
+                    bounding_box.max_latitude,
+                    -180.,
+                    bounding_box.max_longitude,
+                ));
+                candidates
+            }
+            (None, None, Some(bounding_box)) => self.airports_in_bounds(
+                bounding_box.min_latitude,
+                bounding_box.max_latitude,
+                bounding_box.min_longitude,
+                bounding_box.max_longitude,
+            ),
+            (None, None, None) => self.airports.iter().collect(),
+        };
+
+        Ok(query.apply(candidates))
+    }
+
+    async fn count_airports(&self) -> Result<usize, sqlx::Error> {
+        Ok(self.airports_count())
+    }
+
+    async fn dataset_metadata(&self) -> Result<Option<DatasetMetadata>, sqlx::Error> {
+        Ok(self.dataset_metadata.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::airports::airports_query::SortKey;
+    use crate::services::airports::{
+        AirportCursor, AirportFilter, AirportSort, BoundingBox, GlobalAirportsRepository,
+    };
+
+    use super::*;
+
+    fn airport(
+        iata_code: &str,
+        icao_code: &str,
+        country: &str,
+        latitude: f64,
+        longitude: f64,
+    ) -> Airport {
+        Airport {
+            id: 1,
+            icao_code: icao_code.to_owned(),
+            iata_code: iata_code.to_owned(),
+            name: iata_code.to_owned(),
+            city: String::new(),
+            country: country.to_owned(),
+            lat_deg: 0,
+            lat_min: 0,
+            lat_sec: 0,
+            lat_dir: "N".to_owned(),
+            lon_deg: 0,
+            lon_min: 0,
+            lon_sec: 0,
+            lon_dir: "E".to_owned(),
+            altitude: 0,
+            lat_decimal: latitude,
+            lon_decimal: longitude,
+        }
+    }
+
+    fn repository() -> InMemoryAirportsRepository {
+        InMemoryAirportsRepository::new(
+            vec![
+                airport("KRK", "EPKK", "POLAND", 50.0777, 19.7848),
+                airport("KTW", "EPKT", "POLAND", 50.4743, 19.08),
+                airport("WAW", "EPWA", "POLAND", 52.1657, 20.9671),
+                airport("SUV", "NFNA", "FIJI", -18.0433, 178.5592),
+                airport("TVU", "NFNM", "FIJI", -16.6906, -179.8767),
+                airport("KRK", "N/A", "POLAND", 0., 0.),
+            ],
+            None,
+        )
+    }
+
+    #[actix_web::test]
+    async fn test_lookups_by_code() {
+        let repository = repository();
+
+        let krk = repository.fetch_airport_by_iata("KRK").await.unwrap();
+        assert_eq!(krk.unwrap().icao_code, "EPKK");
+        assert_eq!(repository.fetch_airport_by_iata("LHR").await.unwrap(), None);
+
+        let iatas = |airports: Vec<&Airport>| {
+            airports
+                .iter()
+                .map(|airport| airport.iata_code.to_owned())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(iatas(repository.airports_by_icao("epwa")), vec!["WAW"]);
+        assert!(repository.airports_by_icao("LHR").is_empty());
+
+        assert_eq!(
+            repository.unique_airport_iatas().await.unwrap(),
+            vec!["KRK", "KTW", "WAW", "SUV", "TVU"]
+        );
+    }
+
+    #[test]
+    fn test_airports_in_country() {
+        let repository = repository();
+
+        let iatas = repository
+            .airports_in_country("fiji")
+            .iter()
+            .map(|airport| airport.iata_code.as_str())
+            .collect::<Vec<&str>>();
+
+        assert_eq!(iatas, vec!["SUV", "TVU"]);
+        assert!(repository.airports_in_country("ATLANTIS").is_empty());
+    }
+
+    #[test]
+    fn test_airports_in_bounds() {
+        let repository = repository();
+
+        let iatas = repository
+            .airports_in_bounds(49., 51., 18., 21.)
+            .iter()
+            .map(|airport| airport.iata_code.as_str())
+            .collect::<Vec<&str>>();
+
+        assert_eq!(iatas, vec!["KRK", "KTW"]);
+    }
+
+    #[actix_web::test]
+    async fn test_preloads_global_airports_database() {
+        let repository = GlobalAirportsRepository::open("sqlite:./global_airports_database.sqlite")
+            .await
+            .unwrap();
+        let in_memory = InMemoryAirportsRepository::new(
+            repository.all_airports().await.unwrap(),
+            repository.dataset_metadata().await.unwrap(),
+        );
+
+        assert_eq!(
+            in_memory.fetch_airport_by_iata("LHR").await.unwrap(),
+            repository.fetch_airport_by_iata("LHR").await.unwrap()
+        );
+        assert_eq!(
+            in_memory.unique_airport_iatas().await.unwrap().len(),
+            repository.unique_airport_iatas().await.unwrap().len()
+        );
+
+        // listings use indexes, but have to match the ones of the database
+        let queries = [
+            AirportsQuery {
+                filter: AirportFilter {
+                    icao_code: Some("egll".to_owned()),
+                    ..AirportFilter::default()
+                },
+                sort: AirportSort::Id,
+                descending: false,
+                after: None,
+                limit: 20,
+            },
+            AirportsQuery {
+                filter: AirportFilter {
+                    country: Some("poland".to_owned()),
+                    ..AirportFilter::default()
+                },
+                sort: AirportSort::Name,
+                descending: true,
+                after: None,
+                limit: 20,
+            },
+            AirportsQuery {
+                filter: AirportFilter {
+                    bounding_box: Some(BoundingBox {
+                        min_latitude: -25.,
+                        max_latitude: -10.,
+                        min_longitude: 175.,
+                        max_longitude: -175.,
+                    }),
+                    has_iata: Some(true),
+                    ..AirportFilter::default()
+                },
+                sort: AirportSort::IataCode,
+                descending: false,
+                after: None,
+                limit: 20,
+            },
+            AirportsQuery {
+                filter: AirportFilter {
+                    min_altitude: Some(3000),
+                    ..AirportFilter::default()
+                },
+                sort: AirportSort::Country,
+                descending: false,
+                after: Some(AirportCursor {
+                    key: SortKey::Text("CHINA".to_owned()),
+                    iata_code: "LXA".to_owned(),
+                    id: 0,
+                }),
+                limit: 20,
+            },
+        ];
+
+        for query in queries {
+            let listed = in_memory.list_airports(&query).await.unwrap();
+
+            assert!(!listed.is_empty());
+            assert_eq!(listed, repository.list_airports(&query).await.unwrap());
+        }
+    }
+}
@@ -0,0 +1,49 @@
+/// IATA metropolitan area codes together with the airports serving them, ordered by
+/// how prominent the airport is within the area.
+const METRO_AREAS: &[(&str, &[&str])] = &[
+    ("BER", &["TXL", "SXF"]),
+    ("BJS", &["PEK", "PKX"]),
+    ("BUE", &["EZE", "AEP"]),
+    ("CHI", &["ORD", "MDW"]),
+    ("DTT", &["DTW", "DET"]),
+    ("JKT", &["CGK", "HLP"]),
+    ("LON", &["LHR", "LGW", "STN", "LTN", "LCY", "SEN"]),
+    ("MIL", &["MXP", "LIN", "BGY"]),
+    ("MOW", &["SVO", "DME", "VKO"]),
+    ("NYC", &["JFK", "LGA", "EWR"]),
+    ("OSA", &["KIX", "ITM", "UKB"]),
+    ("PAR", &["CDG", "ORY", "BVA"]),
+    ("RIO", &["GIG", "SDU"]),
+    ("ROM", &["FCO", "CIA"]),
+    ("SAO", &["GRU", "CGH", "VCP"]),
+    ("SEL", &["ICN", "GMP"]),
+    ("STO", &["ARN", "BMA", "NYO"]),
+    ("TYO", &["HND", "NRT"]),
+    ("WAS", &["IAD", "DCA", "BWI"]),
+    ("YMQ", &["YUL", "YMX"]),
+    ("YTO", &["YYZ", "YTZ"]),
+];
+
+/// Returns the airports belonging to given metropolitan area code, if it is a known one.
+pub fn metro_area_airports(metro_code: &str) -> Option<&'static [&'static str]> {
+    METRO_AREAS
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(metro_code))
+        .map(|(_, airports)| *airports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_returns_airports_for_known_metro_area() {
+        assert_eq!(metro_area_airports("NYC"), Some(&["JFK", "LGA", "EWR"][..]));
+        assert_eq!(metro_area_airports("nyc"), Some(&["JFK", "LGA", "EWR"][..]));
+    }
+
+    #[test]
+    fn test_returns_none_for_unknown_metro_area() {
+        assert_eq!(metro_area_airports("XXX"), None);
+    }
+}
@@ -1,28 +1,219 @@
-use crate::models::Airport;
+use crate::models::{Airport, CacheStats, DatasetMetadata, PoolStats};
 use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::log;
 
+mod airports_cache;
+mod airports_database_reloader;
+mod airports_overlay_store;
+mod airports_query;
+mod airports_repository_handle;
 mod global_airports_repository;
+mod in_memory_airports_repository;
+mod metro_areas;
+mod overlay_airports_repository;
+#[cfg(feature = "postgres")]
+mod postgres_airports_repository;
+mod route_resolver;
+pub use self::airports_cache::CacheConfig;
+pub use self::airports_database_reloader::{
+    validate_airports_database, AirportsDatabaseReloader, ReloadError,
+};
+pub use self::airports_overlay_store::{
+    AirportsOverlayStore, OverlayAction, OverlayAuditEntry, OverlayChange, OverlayError,
+};
+pub use self::airports_query::{
+    AirportCursor, AirportFilter, AirportSort, AirportsQuery, BoundingBox,
+};
+pub use self::airports_repository_handle::AirportsRepositoryHandle;
 pub use self::global_airports_repository::GlobalAirportsRepository;
+pub use self::in_memory_airports_repository::InMemoryAirportsRepository;
+pub use self::overlay_airports_repository::OverlayAirportsRepository;
+#[cfg(feature = "postgres")]
+pub use self::postgres_airports_repository::PostgresAirportsRepository;
+pub use self::route_resolver::{resolve_route, ResolvedStop};
 
 #[async_trait]
-pub trait AirportsRepository {
+pub trait AirportsRepository: Send + Sync {
     async fn fetch_airport_by_iata<'a>(
         &self,
         iata_code: &'a str,
     ) -> Result<Option<Airport>, sqlx::Error>;
 
+    /// Airports of the given codes, keyed by IATA code, codes which aren't found are left
+    /// out. Looks codes up one by one concurrently unless the repository can batch them.
+    async fn fetch_airports_by_iata(
+        &self,
+        iata_codes: &[String],
+    ) -> Result<HashMap<String, Airport>, sqlx::Error> {
+        let results = join_all(
+            iata_codes
+                .iter()
+                .map(|iata_code| self.fetch_airport_by_iata(iata_code)),
+        )
+        .await;
+
+        let mut airports = HashMap::new();
+        for (iata_code, result) in iata_codes.iter().zip(results) {
+            if let Some(airport) = result? {
+                airports.insert(iata_code.to_owned(), airport);
+            }
+        }
+
+        Ok(airports)
+    }
+
     async fn unique_airport_iatas<'a>(&self) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Airports matching the query, at most `query.limit` of them, in the order of the query.
+    async fn list_airports(&self, query: &AirportsQuery) -> Result<Vec<Airport>, sqlx::Error>;
+
+    /// Number of airports in the database, reading the whole table, to check a database
+    /// before serving it.
+    async fn count_airports(&self) -> Result<usize, sqlx::Error>;
+
+    /// Metadata of the served dataset, `None` for databases created without the importer.
+    async fn dataset_metadata(&self) -> Result<Option<DatasetMetadata>, sqlx::Error>;
+
+    /// Checks that airports can be queried, for readiness probes, without reading the whole
+    /// table like `count_airports` does. Repositories serving them from memory are always ready.
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        Ok(())
+    }
+
+    /// Closes the database connections, waiting for queries in flight, on shutdown.
+    async fn close(&self) {}
+
+    /// Drops all cached data, called on repositories replaced by a reload.
+    async fn invalidate_caches(&self) {}
+
+    /// Counters of the repository caches, empty for repositories which don't cache.
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        vec![]
+    }
+
+    /// Connections of the database pool, `None` for repositories which don't keep one.
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+}
+
+/// How airports are served from the database.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepositoryMode {
+    /// Queries the database on cache misses.
+    #[default]
+    Cached,
+    /// Loads the whole dataset into memory when the database is opened.
+    InMemory,
+}
+
+impl FromStr for RepositoryMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cached" => Ok(RepositoryMode::Cached),
+            "in-memory" => Ok(RepositoryMode::InMemory),
+            _ => Err(format!(
+                "unknown repository mode `{s}`, expected `cached` or `in-memory`"
+            )),
+        }
+    }
+}
+
+/// How repositories opened by `open_airports_repository` serve airports.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RepositoryOptions {
+    pub mode: RepositoryMode,
+    /// Caches of `RepositoryMode::Cached` repositories.
+    pub cache: CacheConfig,
+}
+
+/// Opens the repository for `database_url`, picking PostgreSQL for `postgres://` URLs and
+/// the SQLite dataset otherwise.
+pub async fn open_airports_repository(
+    database_url: &str,
+    options: &RepositoryOptions,
+) -> Result<Box<dyn AirportsRepository>, sqlx::Error> {
+    if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        {
+            let repository = PostgresAirportsRepository::open(database_url)
+                .await?
+                .with_cache_config(&options.cache);
+
+            return match options.mode {
+                RepositoryMode::Cached => Ok(Box::new(repository)),
+                RepositoryMode::InMemory => Ok(Box::new(preload(
+                    repository.all_airports().await?,
+                    repository.dataset_metadata().await?,
+                ))),
+            };
+        }
+
+        #[cfg(not(feature = "postgres"))]
+        return Err(sqlx::Error::Configuration(
+            "PostgreSQL support requires building with the `postgres` feature".into(),
+        ));
+    }
+
+    let repository = GlobalAirportsRepository::open(database_url)
+        .await?
+        .with_cache_config(&options.cache);
+
+    match options.mode {
+        RepositoryMode::Cached => Ok(Box::new(repository)),
+        RepositoryMode::InMemory => Ok(Box::new(preload(
+            repository.all_airports().await?,
+            repository.dataset_metadata().await?,
+        ))),
+    }
+}
+
+fn preload(
+    airports: Vec<Airport>,
+    dataset_metadata: Option<DatasetMetadata>,
+) -> InMemoryAirportsRepository {
+    let repository = InMemoryAirportsRepository::new(airports, dataset_metadata);
+    log::info!(
+        "Preloaded {} airports into memory",
+        repository.airports_count()
+    );
+
+    repository
 }
 
 #[cfg(test)]
 pub struct DummyAirportsRepository {
     pub airports: Vec<Airport>,
+    pub dataset_metadata: Option<DatasetMetadata>,
+    pub ping_error: Option<String>,
 }
 
 #[cfg(test)]
 impl DummyAirportsRepository {
     pub fn new(airports: Vec<Airport>) -> Self {
-        Self { airports }
+        Self {
+            airports,
+            dataset_metadata: None,
+            ping_error: None,
+        }
+    }
+
+    pub fn with_dataset_metadata(mut self, dataset_metadata: DatasetMetadata) -> Self {
+        self.dataset_metadata = Some(dataset_metadata);
+        self
+    }
+
+    /// Makes `ping` fail with `message`, like an unreachable database.
+    pub fn with_ping_error(mut self, message: &str) -> Self {
+        self.ping_error = Some(message.to_owned());
+        self
     }
 }
 
@@ -55,4 +246,23 @@ impl AirportsRepository for DummyAirportsRepository {
 
         Ok(iatas)
     }
+
+    async fn list_airports(&self, query: &AirportsQuery) -> Result<Vec<Airport>, sqlx::Error> {
+        Ok(query.apply(&self.airports))
+    }
+
+    async fn count_airports(&self) -> Result<usize, sqlx::Error> {
+        Ok(self.airports.len())
+    }
+
+    async fn dataset_metadata(&self) -> Result<Option<DatasetMetadata>, sqlx::Error> {
+        Ok(self.dataset_metadata.clone())
+    }
+
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        match &self.ping_error {
+            Some(message) => Err(sqlx::Error::Protocol(message.to_owned())),
+            None => Ok(()),
+        }
+    }
 }
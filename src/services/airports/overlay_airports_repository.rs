@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::models::{Airport, CacheStats, DatasetMetadata, PoolStats};
+
+use super::airports_overlay_store::{AirportsOverlayStore, OverlayEntry};
+use super::{AirportsQuery, AirportsRepository};
+
+/// Serves airports from the overlay store first, falling back to the read only `base`
+/// repository for airports the overlay doesn't know about.
+pub struct OverlayAirportsRepository {
+    base: Arc<dyn AirportsRepository>,
+    overlay: Arc<AirportsOverlayStore>,
+}
+
+impl OverlayAirportsRepository {
+    pub fn new(base: Arc<dyn AirportsRepository>, overlay: Arc<AirportsOverlayStore>) -> Self {
+        Self { base, overlay }
+    }
+}
+
+#[async_trait]
+impl AirportsRepository for OverlayAirportsRepository {
+    async fn fetch_airport_by_iata<'a>(
+        &self,
+        iata_code: &'a str,
+    ) -> Result<Option<Airport>, sqlx::Error> {
+        match self.overlay.get(iata_code) {
+            Some(OverlayEntry::Airport(airport)) => Ok(Some(*airport)),
+            Some(OverlayEntry::Deleted) => Ok(None),
+            None => self.base.fetch_airport_by_iata(iata_code).await,
+        }
+    }
+
+    async fn fetch_airports_by_iata(
+        &self,
+        iata_codes: &[String],
+    ) -> Result<HashMap<String, Airport>, sqlx::Error> {
+        let mut airports = HashMap::new();
+        let mut base_codes = Vec::new();
+
+        for iata_code in iata_codes {
+            match self.overlay.get(iata_code) {
+                Some(OverlayEntry::Airport(airport)) => {
+                    airports.insert(iata_code.to_owned(), *airport);
+                }
+                Some(OverlayEntry::Deleted) => {}
+                None => base_codes.push(iata_code.to_owned()),
+            }
+        }
+
+        airports.extend(self.base.fetch_airports_by_iata(&base_codes).await?);
+
+        Ok(airports)
+    }
+
+    async fn unique_airport_iatas<'a>(&self) -> Result<Vec<String>, sqlx::Error> {
+        let entries = self.overlay.entries();
+
+        let mut iatas = self
+            .base
+            .unique_airport_iatas()
+            .await?
+            .into_iter()
+            .filter(|iata| !entries.contains_key(iata))
+            .collect::<Vec<_>>();
+
+        let mut overlay_iatas = entries
+            .into_iter()
+            .filter(|(_, entry)| matches!(entry, OverlayEntry::Airport(_)))
+            .map(|(iata, _)| iata)
+            .collect::<Vec<_>>();
+        overlay_iatas.sort();
+
+        iatas.extend(overlay_iatas);
+
+        Ok(iatas)
+    }
+
+    async fn list_airports(&self, query: &AirportsQuery) -> Result<Vec<Airport>, sqlx::Error> {
+        let entries = self.overlay.entries();
+
+        // base airports shadowed by overlay entries are dropped, so keep fetching pages of
+        // the base listing until there's enough of them left or the base runs out
+        let mut base_airports = Vec::new();
+        let mut base_query = query.clone();
+        loop {
+            let page = self.base.list_airports(&base_query).await?;
+            let exhausted = page.len() < base_query.limit;
+            base_query.after = page.last().map(|airport| base_query.cursor(airport));
+
+            base_airports.extend(
+                page.into_iter()
+                    .filter(|airport| !entries.contains_key(&airport.iata_code)),
+            );
+
+            if exhausted || base_airports.len() >= query.limit {
+                break;
+            }
+        }
+
+        let overlay_airports = entries.into_values().filter_map(|entry| match entry {
+            OverlayEntry::Airport(airport) => Some(*airport),
+            OverlayEntry::Deleted => None,
+        });
+        base_airports.extend(overlay_airports.filter(|airport| query.matches(airport)));
+
+        base_airports.sort_by(|a, b| query.compare(a, b));
+        base_airports.truncate(query.limit);
+
+        Ok(base_airports)
+    }
+
+    async fn count_airports(&self) -> Result<usize, sqlx::Error> {
+        self.base.count_airports().await
+    }
+
+    async fn dataset_metadata(&self) -> Result<Option<DatasetMetadata>, sqlx::Error> {
+        self.base.dataset_metadata().await
+    }
+
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        self.base.ping().await
+    }
+
+    async fn close(&self) {
+        self.base.close().await
+    }
+
+    async fn invalidate_caches(&self) {
+        self.base.invalidate_caches().await
+    }
+
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        self.base.cache_stats()
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.base.pool_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::airports::{
+        AirportFilter, AirportSort, DummyAirportsRepository, OverlayAction, OverlayError,
+    };
+
+    use super::*;
+
+    fn airport(iata_code: &str, name: &str) -> Airport {
+        Airport {
+            id: 1,
+            icao_code: "N/A".to_owned(),
+            iata_code: iata_code.to_owned(),
+            name: name.to_owned(),
+            city: String::new(),
+            country: String::new(),
+            lat_deg: 0,
+            lat_min: 0,
+            lat_sec: 0,
+            lat_dir: "N".to_owned(),
+            lon_deg: 0,
+            lon_min: 0,
+            lon_sec: 0,
+            lon_dir: "E".to_owned(),
+            altitude: 0,
+            lat_decimal: 1.,
+            lon_decimal: 1.,
+        }
+    }
+
+    fn waw() -> Airport {
+        airport("WAW", "OKECIE")
+    }
+
+    async fn repository() -> (OverlayAirportsRepository, Arc<AirportsOverlayStore>) {
+        let base = DummyAirportsRepository::new(vec![airport("KRK", "BALICE"), waw()]);
+        let overlay = Arc::new(AirportsOverlayStore::open("sqlite::memory:").await.unwrap());
+
+        (
+            OverlayAirportsRepository::new(Arc::new(base), overlay.clone()),
+            overlay,
+        )
+    }
+
+    #[actix_web::test]
+    async fn test_overlay_entries_take_precedence() {
+        let (repository, overlay) = repository().await;
+
+        let corrected = airport("KRK", "JOHN PAUL II");
+        let heliport = Airport {
+            id: 3,
+            ..airport("XHP", "PRIVATE HELIPORT")
+        };
+        let waw = airport("WAW", "OKECIE");
+
+        overlay
+            .record(
+                OverlayAction::Update,
+                "KRK",
+                Some(&airport("KRK", "BALICE")),
+                Some(corrected.clone()),
+                "admin",
+                1,
+            )
+            .await
+            .unwrap();
+        overlay
+            .record(
+                OverlayAction::Create,
+                "XHP",
+                None,
+                Some(heliport.clone()),
+                "admin",
+                2,
+            )
+            .await
+            .unwrap();
+        overlay
+            .record(OverlayAction::Delete, "WAW", Some(&waw), None, "admin", 3)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.fetch_airport_by_iata("KRK").await.unwrap(),
+            Some(corrected.clone())
+        );
+        assert_eq!(
+            repository.fetch_airport_by_iata("XHP").await.unwrap(),
+            Some(heliport.clone())
+        );
+        assert_eq!(repository.fetch_airport_by_iata("WAW").await.unwrap(), None);
+        assert_eq!(
+            repository
+                .fetch_airports_by_iata(&["KRK".to_owned(), "WAW".to_owned(), "XHP".to_owned()])
+                .await
+                .unwrap(),
+            HashMap::from([("KRK".to_owned(), corrected), ("XHP".to_owned(), heliport),])
+        );
+        assert_eq!(
+            repository.unique_airport_iatas().await.unwrap(),
+            vec!["KRK".to_owned(), "XHP".to_owned()]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_lists_overlay_airports() {
+        let (repository, overlay) = repository().await;
+        let corrected = airport("KRK", "JOHN PAUL II");
+        let heliport = Airport {
+            id: 3,
+            ..airport("XHP", "PRIVATE HELIPORT")
+        };
+
+        overlay
+            .record(
+                OverlayAction::Update,
+                "KRK",
+                Some(&airport("KRK", "BALICE")),
+                Some(corrected.clone()),
+                "admin",
+                1,
+            )
+            .await
+            .unwrap();
+        overlay
+            .record(
+                OverlayAction::Create,
+                "XHP",
+                None,
+                Some(heliport.clone()),
+                "admin",
+                2,
+            )
+            .await
+            .unwrap();
+
+        let mut query = AirportsQuery {
+            filter: AirportFilter::default(),
+            sort: AirportSort::Name,
+            descending: false,
+            after: None,
+            limit: 2,
+        };
+        let first_page = repository.list_airports(&query).await.unwrap();
+        assert_eq!(first_page, vec![corrected, waw()]);
+
+        query.after = Some(query.cursor(first_page.last().unwrap()));
+        assert_eq!(
+            repository.list_airports(&query).await.unwrap(),
+            vec![heliport]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_records_audit_trail() {
+        let (_, overlay) = repository().await;
+        let waw = airport("WAW", "OKECIE");
+
+        overlay
+            .record(OverlayAction::Delete, "WAW", Some(&waw), None, "admin", 3)
+            .await
+            .unwrap();
+
+        let audit_log = overlay.audit_log().await.unwrap();
+
+        assert_eq!(audit_log.len(), 1);
+        assert_eq!(audit_log[0].action, OverlayAction::Delete);
+        assert_eq!(audit_log[0].changed_by, "admin");
+        assert_eq!(audit_log[0].previous, Some(waw));
+        assert_eq!(audit_log[0].current, None);
+    }
+
+    #[actix_web::test]
+    async fn test_reads_previous_airport_from_overlay() {
+        let (_, overlay) = repository().await;
+        let corrected = airport("WAW", "CHOPIN");
+
+        let change = overlay
+            .record(
+                OverlayAction::Update,
+                "WAW",
+                Some(&waw()),
+                Some(corrected.clone()),
+                "admin",
+                1,
+            )
+            .await
+            .unwrap();
+        assert_eq!(change.previous, Some(waw()));
+
+        // the stale dataset airport is ignored once the overlay has an entry
+        let change = overlay
+            .record(OverlayAction::Delete, "WAW", Some(&waw()), None, "admin", 2)
+            .await
+            .unwrap();
+        assert_eq!(change.previous, Some(corrected));
+
+        let result = overlay
+            .record(OverlayAction::Delete, "WAW", Some(&waw()), None, "admin", 3)
+            .await;
+        assert!(matches!(result, Err(OverlayError::NotFound)));
+
+        let result = overlay
+            .record(
+                OverlayAction::Create,
+                "KRK",
+                Some(&airport("KRK", "BALICE")),
+                Some(airport("KRK", "BALICE")),
+                "admin",
+                4,
+            )
+            .await;
+        assert!(matches!(result, Err(OverlayError::AlreadyExists)));
+
+        assert_eq!(overlay.audit_log().await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,352 @@
+use async_trait::async_trait;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgPool, PgRow};
+use sqlx::{QueryBuilder, Row};
+use std::collections::HashMap;
+
+use crate::models::{Airport, CacheStats, DatasetMetadata, PoolStats};
+
+use super::airports_cache::{AirportsCaches, CacheConfig};
+use super::{AirportsQuery, AirportsRepository};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Serves airports from a PostgreSQL database with the same schema as the SQLite dataset.
+#[derive(Clone)]
+pub struct PostgresAirportsRepository {
+    pool: PgPool,
+    caches: AirportsCaches,
+}
+
+impl PostgresAirportsRepository {
+    /// Connects to the database at `database_url` and brings its schema up to date.
+    pub async fn open(database_url: &str) -> Result<Self, sqlx::Error> {
+        let pool = PgPool::connect(database_url).await?;
+        MIGRATOR.run(&pool).await?;
+
+        Ok(Self::new(pool))
+    }
+
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            caches: AirportsCaches::new(&CacheConfig::default()),
+        }
+    }
+
+    pub fn with_cache_config(mut self, cache_config: &CacheConfig) -> Self {
+        self.caches = AirportsCaches::new(cache_config);
+        self
+    }
+
+    /// Every airport of the dataset, used to preload it into memory.
+    #[tracing::instrument(skip_all)]
+    pub async fn all_airports(&self) -> Result<Vec<Airport>, sqlx::Error> {
+        let query = "SELECT * FROM airports WHERE name != 'N/A' ORDER BY id";
+
+        sqlx::query(query)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(airport_from_row)
+            .collect()
+    }
+}
+
+// `Airport` can't be decoded with `FromRow`, as Postgres has no unsigned integers
+fn airport_from_row(row: &PgRow) -> Result<Airport, sqlx::Error> {
+    Ok(Airport {
+        id: row.try_get::<i32, _>("id")? as u32,
+        icao_code: row.try_get("icao_code")?,
+        iata_code: row.try_get("iata_code")?,
+        name: row.try_get("name")?,
+        city: row.try_get("city")?,
+        country: row.try_get("country")?,
+        lat_deg: row.try_get("lat_deg")?,
+        lat_min: row.try_get("lat_min")?,
+        lat_sec: row.try_get("lat_sec")?,
+        lat_dir: row.try_get("lat_dir")?,
+        lon_deg: row.try_get("lon_deg")?,
+        lon_min: row.try_get("lon_min")?,
+        lon_sec: row.try_get("lon_sec")?,
+        lon_dir: row.try_get("lon_dir")?,
+        altitude: row.try_get("altitude")?,
+        lat_decimal: row.try_get("lat_decimal")?,
+        lon_decimal: row.try_get("lon_decimal")?,
+    })
+}
+
+#[async_trait]
+impl AirportsRepository for PostgresAirportsRepository {
+    #[tracing::instrument(skip(self))]
+    async fn fetch_airport_by_iata<'a>(
+        &self,
+        iata_code: &'a str,
+    ) -> Result<Option<Airport>, sqlx::Error> {
+        if let Some(airport) = self.caches.airport(iata_code) {
+            return Ok(airport);
+        }
+
+        let query = "SELECT * FROM airports WHERE iata_code = $1 AND name != 'N/A' LIMIT 1";
+        let airport = sqlx::query(query)
+            .bind(iata_code)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|row| airport_from_row(&row))
+            .transpose()?;
+
+        self.caches.insert_airport(iata_code, airport.clone()).await;
+
+        Ok(airport)
+    }
+
+    #[tracing::instrument(skip_all, fields(count = iata_codes.len()))]
+    async fn fetch_airports_by_iata(
+        &self,
+        iata_codes: &[String],
+    ) -> Result<HashMap<String, Airport>, sqlx::Error> {
+        let mut airports = HashMap::new();
+        let mut uncached_codes = Vec::new();
+
+        for iata_code in iata_codes {
+            match self.caches.airport(iata_code) {
+                Some(Some(airport)) => {
+                    airports.insert(iata_code.to_owned(), airport);
+                }
+                Some(None) => {}
+                None => uncached_codes.push(iata_code.to_owned()),
+            }
+        }
+        uncached_codes.sort();
+        uncached_codes.dedup();
+
+        if uncached_codes.is_empty() {
+            return Ok(airports);
+        }
+
+        let query =
+            "SELECT * FROM airports WHERE iata_code = ANY($1) AND name != 'N/A' ORDER BY id";
+        let mut found = HashMap::new();
+        for row in sqlx::query(query)
+            .bind(&uncached_codes)
+            .fetch_all(&self.pool)
+            .await?
+        {
+            let airport = airport_from_row(&row)?;
+            found.entry(airport.iata_code.to_owned()).or_insert(airport);
+        }
+
+        for iata_code in &uncached_codes {
+            self.caches
+                .insert_airport(iata_code, found.get(iata_code).cloned())
+                .await;
+        }
+
+        airports.extend(found);
+
+        Ok(airports)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn unique_airport_iatas<'a>(&self) -> Result<Vec<String>, sqlx::Error> {
+        if let Some(iatas) = self.caches.iatas.get(&()) {
+            return Ok(iatas);
+        }
+
+        let query = "SELECT DISTINCT iata_code FROM airports WHERE name != 'N/A'";
+        let iatas = sqlx::query(query)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| row.get::<String, _>("iata_code"))
+            .collect::<Vec<String>>();
+
+        self.caches.iatas.insert((), iatas.clone()).await;
+
+        Ok(iatas)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn list_airports(&self, query: &AirportsQuery) -> Result<Vec<Airport>, sqlx::Error> {
+        let mut builder = QueryBuilder::new("SELECT * FROM airports");
+        // the default collation depends on the locale, while listings are ordered bytewise
+        query.push_sql(&mut builder, "\"C\"");
+
+        builder
+            .build()
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(airport_from_row)
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn count_airports(&self) -> Result<usize, sqlx::Error> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM airports WHERE name != 'N/A'")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count as usize)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn dataset_metadata(&self) -> Result<Option<DatasetMetadata>, sqlx::Error> {
+        if let Some(metadata) = self.caches.dataset_metadata.get(&()) {
+            return Ok(metadata);
+        }
+
+        let metadata =
+            sqlx::query_as::<_, DatasetMetadata>("SELECT * FROM dataset_metadata LIMIT 1")
+                .fetch_optional(&self.pool)
+                .await?;
+
+        self.caches
+            .dataset_metadata
+            .insert((), metadata.clone())
+            .await;
+
+        Ok(metadata)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1 FROM airports LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    async fn invalidate_caches(&self) {
+        self.caches.invalidate_all();
+    }
+
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        self.caches.stats()
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+        })
+    }
+}
+
+/// Runs against the database given in `POSTGRES_TEST_DATABASE_URL`, e.g. the one started with
+/// `docker-compose --profile postgres up postgres`, see README.
+#[cfg(test)]
+mod tests {
+    use crate::services::airports::{AirportFilter, AirportSort};
+
+    use super::*;
+
+    async fn repository() -> PostgresAirportsRepository {
+        let database_url = std::env::var("POSTGRES_TEST_DATABASE_URL")
+            .expect("POSTGRES_TEST_DATABASE_URL has to point to a test database");
+        let repository = PostgresAirportsRepository::open(&database_url)
+            .await
+            .unwrap();
+
+        sqlx::query("TRUNCATE airports, dataset_metadata RESTART IDENTITY")
+            .execute(&repository.pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO airports (
+                icao_code, iata_code, name, city, country,
+                lat_deg, lat_min, lat_sec, lat_dir, lon_deg, lon_min, lon_sec, lon_dir,
+                altitude, lat_decimal, lon_decimal
+            ) VALUES
+                ('EGLL', 'LHR', 'HEATHROW', 'LONDON', 'ENGLAND', 51, 28, 39, 'N', 0, 27, 41, 'W', 25, 51.478, -0.461),
+                ('KJFK', 'JFK', 'JOHN F KENNEDY INTERNATIONAL', 'NEW YORK', 'USA', 40, 38, 23, 'N', 73, 46, 44, 'W', 4, 40.64, -73.779),
+                ('AYLA', 'LAE', 'N/A', 'LAE', 'PAPUA NEW GUINEA', 0, 0, 0, 'U', 0, 0, 0, 'U', 0, 0.0, 0.0)",
+        )
+        .execute(&repository.pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO dataset_metadata VALUES ('partow', '0.0.2', 1683000000, 3, 2, 1, 'abc')",
+        )
+        .execute(&repository.pool)
+        .await
+        .unwrap();
+
+        repository
+    }
+
+    // all cases share a single database, so they run sequentially within one test
+    #[actix_web::test]
+    #[ignore = "requires PostgreSQL, set POSTGRES_TEST_DATABASE_URL and run with --ignored"]
+    async fn test_postgres_airports_repository() {
+        let repository = repository().await;
+
+        let heathrow = repository
+            .fetch_airport_by_iata("LHR")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(heathrow.id, 1);
+        assert_eq!(heathrow.icao_code, "EGLL");
+        assert_eq!(heathrow.lon_decimal, -0.461);
+        assert_eq!(heathrow.lat_sec, 39);
+
+        assert_eq!(repository.fetch_airport_by_iata("LAE").await.unwrap(), None);
+        assert_eq!(repository.fetch_airport_by_iata("XXX").await.unwrap(), None);
+
+        let mut iatas = repository.unique_airport_iatas().await.unwrap();
+        iatas.sort();
+        assert_eq!(iatas, vec!["JFK".to_owned(), "LHR".to_owned()]);
+
+        let query = AirportsQuery {
+            filter: AirportFilter {
+                max_altitude: Some(20),
+                ..AirportFilter::default()
+            },
+            sort: AirportSort::Name,
+            descending: true,
+            after: None,
+            limit: 10,
+        };
+        let found = repository
+            .fetch_airports_by_iata(&["JFK".to_owned(), "LAE".to_owned(), "LHR".to_owned()])
+            .await
+            .unwrap();
+        let mut found_codes = found.keys().collect::<Vec<&String>>();
+        found_codes.sort();
+        assert_eq!(found_codes, vec!["JFK", "LHR"]);
+
+        let listed = repository.list_airports(&query).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].iata_code, "JFK");
+
+        let metadata = repository.dataset_metadata().await.unwrap().unwrap();
+        assert_eq!(metadata.version, "0.0.2");
+        assert_eq!(metadata.airports_count, 2);
+
+        assert!(repository
+            .fetch_airport_by_iata("JFK")
+            .await
+            .unwrap()
+            .is_some());
+
+        // cached airports are served until caches are invalidated
+        sqlx::query("DELETE FROM airports WHERE iata_code = 'JFK'")
+            .execute(&repository.pool)
+            .await
+            .unwrap();
+        assert!(repository
+            .fetch_airport_by_iata("JFK")
+            .await
+            .unwrap()
+            .is_some());
+
+        repository.invalidate_caches().await;
+        assert_eq!(repository.fetch_airport_by_iata("JFK").await.unwrap(), None);
+    }
+}
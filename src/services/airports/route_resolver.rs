@@ -0,0 +1,359 @@
+use futures::future::try_join_all;
+
+use crate::models::{Airport, Coordinates};
+use crate::services::distance::DistanceCalculator;
+
+use super::metro_areas::metro_area_airports;
+use super::AirportsRepository;
+
+/// Single entry of a route after resolving it against the airports database.
+#[derive(Debug, Clone)]
+pub struct ResolvedStop {
+    /// Code exactly as requested by the client.
+    pub requested_code: String,
+    /// Airport chosen for the stop, `None` if the code couldn't be resolved.
+    pub airport: Option<Airport>,
+    /// Metropolitan area code the airport was chosen from, if the stop was a metro code.
+    pub metro_code: Option<String>,
+}
+
+/// Airports a route entry may resolve to; metro area entries have several of them.
+struct Candidates {
+    airports: Vec<Airport>,
+    metro_area: bool,
+}
+
+impl Candidates {
+    fn airport(airport: Airport) -> Self {
+        Self {
+            airports: vec![airport],
+            metro_area: false,
+        }
+    }
+
+    fn missing() -> Self {
+        Self {
+            airports: vec![],
+            metro_area: false,
+        }
+    }
+}
+
+/// Resolves route codes to airports. Airport codes take precedence, codes which aren't
+/// airports but are metropolitan area codes (e.g. `LON`, `NYC`) are expanded to the airports
+/// of their areas which make the route the shortest.
+pub async fn resolve_route(
+    repository: &dyn AirportsRepository,
+    route: &[String],
+    calculator: &dyn DistanceCalculator,
+) -> Result<Vec<ResolvedStop>, sqlx::Error> {
+    let airports = repository.fetch_airports_by_iata(route).await?;
+
+    // metro areas of the route are fetched concurrently
+    let candidates = try_join_all(route.iter().map(|code| async {
+        match airports.get(code) {
+            Some(airport) => Ok(Candidates::airport(airport.clone())),
+            None => match metro_area_airports(code) {
+                Some(metro_airports) => fetch_metro_area_airports(repository, metro_airports).await,
+                None => Ok(Candidates::missing()),
+            },
+        }
+    }))
+    .await?;
+
+    let airports = choose_airports(&candidates, calculator);
+
+    let stops = route
+        .iter()
+        .zip(candidates.iter().zip(airports))
+        .map(|(code, (stop_candidates, airport))| ResolvedStop {
+            requested_code: code.to_owned(),
+            airport: airport.cloned(),
+            metro_code: stop_candidates.metro_area.then(|| code.to_uppercase()),
+        })
+        .collect();
+
+    Ok(stops)
+}
+
+async fn fetch_metro_area_airports(
+    repository: &dyn AirportsRepository,
+    metro_airports: &[&str],
+) -> Result<Candidates, sqlx::Error> {
+    let iata_codes = metro_airports
+        .iter()
+        .map(|iata| iata.to_string())
+        .collect::<Vec<String>>();
+    let mut found = repository.fetch_airports_by_iata(&iata_codes).await?;
+
+    // keep the order of the metro area table
+    let airports = iata_codes
+        .iter()
+        .filter_map(|iata| found.remove(iata))
+        .collect::<Vec<Airport>>();
+
+    match airports.is_empty() {
+        true => Ok(Candidates::missing()),
+        false => Ok(Candidates {
+            airports,
+            metro_area: true,
+        }),
+    }
+}
+
+/// Picks an airport for every stop, so that the sum of the legs between consecutive stops
+/// is the lowest. Stops which couldn't be resolved split the route, as no legs lead through
+/// them.
+fn choose_airports<'a>(
+    candidates: &'a [Candidates],
+    calculator: &dyn DistanceCalculator,
+) -> Vec<Option<&'a Airport>> {
+    let mut airports = vec![None; candidates.len()];
+
+    let mut start = 0;
+    while start < candidates.len() {
+        let end = (start..candidates.len())
+            .find(|idx| candidates[*idx].airports.is_empty())
+            .unwrap_or(candidates.len());
+
+        if start < end {
+            for (idx, airport) in shortest_path(&candidates[start..end], calculator)
+                .into_iter()
+                .enumerate()
+            {
+                airports[start + idx] = Some(airport);
+            }
+        }
+        start = end + 1;
+    }
+
+    airports
+}
+
+/// Viterbi pass over the candidates of `stops`, none of which may be empty, returning the
+/// airports of the shortest path through them. Consecutive metro areas are resolved together,
+/// so the choice doesn't depend on the order of the stops.
+fn shortest_path<'a>(
+    stops: &'a [Candidates],
+    calculator: &dyn DistanceCalculator,
+) -> Vec<&'a Airport> {
+    // lengths of the shortest paths ending at each airport of the current stop, and for every
+    // stop after the first, the airports of the previous stop these paths lead from
+    let mut lengths = vec![0.; stops[0].airports.len()];
+    let mut previous = Vec::<Vec<usize>>::with_capacity(stops.len() - 1);
+
+    for legs in stops.windows(2) {
+        let (from, to) = (&legs[0].airports, &legs[1].airports);
+
+        let (to_lengths, from_idxs) = to
+            .iter()
+            .map(|airport| {
+                let (from_idx, length) = shortest(
+                    from.iter()
+                        .zip(&lengths)
+                        .map(|(other, length)| length + distance(other, airport, calculator)),
+                );
+                (length, from_idx)
+            })
+            .unzip();
+
+        lengths = to_lengths;
+        previous.push(from_idxs);
+    }
+
+    let (mut idx, _) = shortest(lengths.into_iter());
+    let mut path = Vec::with_capacity(stops.len());
+    for (stop, from_idxs) in stops
+        .iter()
+        .rev()
+        .zip(previous.iter().rev().map(Some).chain(std::iter::once(None)))
+    {
+        path.push(&stop.airports[idx]);
+        if let Some(from_idxs) = from_idxs {
+            idx = from_idxs[idx];
+        }
+    }
+    path.reverse();
+
+    path
+}
+
+/// Index and value of the lowest of `lengths`, the first one on ties.
+fn shortest(lengths: impl Iterator<Item = f64>) -> (usize, f64) {
+    lengths
+        .enumerate()
+        .fold((0, f64::INFINITY), |best, (idx, length)| {
+            match length < best.1 {
+                true => (idx, length),
+                false => best,
+            }
+        })
+}
+
+fn distance(from: &Airport, to: &Airport, calculator: &dyn DistanceCalculator) -> f64 {
+    let from = Coordinates::new(from.lat_decimal, from.lon_decimal);
+    let to = Coordinates::new(to.lat_decimal, to.lon_decimal);
+
+    // formulas which fail to compute the distance make the airport the least preferred one
+    calculator
+        .calculate_distance(&from, &to)
+        .unwrap_or(f64::INFINITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{Datum, Formula};
+    use crate::services::airports::DummyAirportsRepository;
+    use crate::services::distance::DistanceCalculatorFactory;
+
+    use super::*;
+
+    fn airport(iata_code: &str, lat_decimal: f64, lon_decimal: f64) -> Airport {
+        Airport {
+            id: 0,
+            icao_code: String::new(),
+            iata_code: iata_code.to_owned(),
+            name: iata_code.to_owned(),
+            city: String::new(),
+            country: String::new(),
+            lat_deg: 0,
+            lat_min: 0,
+            lat_sec: 0,
+            lat_dir: String::new(),
+            lon_deg: 0,
+            lon_min: 0,
+            lon_sec: 0,
+            lon_dir: String::new(),
+            altitude: 0,
+            lat_decimal,
+            lon_decimal,
+        }
+    }
+
+    fn repository() -> DummyAirportsRepository {
+        DummyAirportsRepository::new(vec![
+            airport("LHR", 51.477, -0.461),
+            airport("LGW", 51.148, -0.190),
+            airport("STN", 51.885, 0.235),
+            airport("JFK", 40.640, -73.779),
+            airport("LGA", 40.777, -73.872),
+            airport("EWR", 40.692, -74.169),
+            airport("BHX", 52.454, -1.748),
+            airport("CDG", 49.013, 2.550),
+            airport("ORY", 48.726, 2.365),
+            airport("BVA", 49.454, 2.113),
+        ])
+    }
+
+    async fn resolve(route: &[&str]) -> Vec<ResolvedStop> {
+        let route = route
+            .iter()
+            .map(|code| code.to_string())
+            .collect::<Vec<_>>();
+        let calculator = DistanceCalculatorFactory::create(&Formula::Haversine, &Datum::WGS84);
+
+        resolve_route(&repository(), &route, calculator.as_ref())
+            .await
+            .expect("Dummy repository should not fail")
+    }
+
+    fn iatas(stops: &[ResolvedStop]) -> Vec<Option<&str>> {
+        stops
+            .iter()
+            .map(|stop| {
+                stop.airport
+                    .as_ref()
+                    .map(|airport| airport.iata_code.as_str())
+            })
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_resolves_plain_airport_codes() {
+        let stops = resolve(&["LHR", "JFK"]).await;
+
+        assert_eq!(iatas(&stops), vec![Some("LHR"), Some("JFK")]);
+        assert!(stops.iter().all(|stop| stop.metro_code.is_none()));
+    }
+
+    #[actix_web::test]
+    async fn test_resolves_metro_code_to_airport_closest_to_neighbours() {
+        let stops = resolve(&["BHX", "LON", "CDG"]).await;
+
+        assert_eq!(iatas(&stops), vec![Some("BHX"), Some("LHR"), Some("CDG")]);
+        assert_eq!(stops[1].metro_code.as_deref(), Some("LON"));
+        assert_eq!(stops[1].requested_code, "LON");
+    }
+
+    #[actix_web::test]
+    async fn test_resolves_consecutive_metro_codes_to_closest_pair() {
+        let stops = resolve(&["lon", "nyc"]).await;
+
+        assert_eq!(iatas(&stops), vec![Some("LHR"), Some("LGA")]);
+        assert_eq!(stops[0].metro_code.as_deref(), Some("LON"));
+        assert_eq!(stops[1].metro_code.as_deref(), Some("NYC"));
+    }
+
+    #[actix_web::test]
+    async fn test_resolves_runs_of_metro_codes_to_shortest_route() {
+        let calculator = DistanceCalculatorFactory::create(&Formula::Haversine, &Datum::WGS84);
+        let length = |airports: &[&Airport]| -> f64 {
+            airports
+                .windows(2)
+                .map(|leg| distance(leg[0], leg[1], calculator.as_ref()))
+                .sum()
+        };
+
+        for route in [
+            vec!["LON", "NYC", "PAR"],
+            vec!["PAR", "LON", "NYC", "LON"],
+            vec!["BHX", "LON", "PAR", "LON"],
+        ] {
+            let stops = resolve(&route).await;
+            let resolved = stops
+                .iter()
+                .map(|stop| stop.airport.as_ref().unwrap())
+                .collect::<Vec<&Airport>>();
+
+            // every combination of the areas' airports is at least as long
+            let repository = repository();
+            let mut routes = vec![Vec::<&Airport>::new()];
+            for code in &route {
+                let area = metro_area_airports(code).unwrap_or(std::slice::from_ref(code));
+                let airports = repository
+                    .airports
+                    .iter()
+                    .filter(|airport| area.contains(&airport.iata_code.as_str()))
+                    .collect::<Vec<&Airport>>();
+
+                routes = routes
+                    .into_iter()
+                    .flat_map(|route| {
+                        airports.iter().map(move |airport| {
+                            let mut route = route.clone();
+                            route.push(*airport);
+                            route
+                        })
+                    })
+                    .collect();
+            }
+            let shortest = routes
+                .iter()
+                .map(|route| length(route))
+                .fold(f64::INFINITY, f64::min);
+
+            assert_eq!(length(&resolved), shortest, "{route:?}");
+        }
+
+        let stops = resolve(&["LON", "NYC", "PAR"]).await;
+        assert_eq!(iatas(&stops), vec![Some("LHR"), Some("LGA"), Some("BVA")]);
+    }
+
+    #[actix_web::test]
+    async fn test_marks_unknown_codes_as_missing() {
+        let stops = resolve(&["LHR", "XXX", "PAR"]).await;
+
+        assert_eq!(iatas(&stops), vec![Some("LHR"), None, Some("CDG")]);
+        assert_eq!(stops[1].metro_code, None);
+    }
+}
@@ -1,12 +1,20 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
 use super::{
-    airports::AirportsRepository,
+    airports::{AirportsRepository, AirportsRepositoryHandle},
     healthcheck::{hostname_provider::HostnameProvider, time_provider::TimeProvider},
 };
 
 pub struct AppState {
     pub hostname_provider: Box<dyn HostnameProvider>,
     pub time_provider: Box<dyn TimeProvider>,
-    pub airports_repository: Box<dyn AirportsRepository>,
+    pub airports_repository: AirportsRepositoryHandle,
+    /// When the service started, according to `time_provider`.
+    pub started_at: SystemTime,
+    /// Number of server workers.
+    pub workers: usize,
+    shutting_down: AtomicBool,
 }
 
 impl AppState {
@@ -17,8 +25,26 @@ impl AppState {
     ) -> Self {
         Self {
             hostname_provider,
+            started_at: time_provider.now(),
             time_provider,
-            airports_repository,
+            airports_repository: AirportsRepositoryHandle::new(airports_repository.into()),
+            // the default of the server, one worker per physical CPU
+            workers: num_cpus::get_physical(),
+            shutting_down: AtomicBool::new(false),
         }
     }
+
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Makes readiness probes fail, so that no new requests are routed to the service.
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
 }
@@ -1,10 +1,11 @@
+use crate::metrics::metrics;
 use crate::models::earth::{nad27, nad83, wgs84};
-use crate::models::{Datum, Formula};
+use crate::models::{Coordinates, Datum, Formula};
 
 use super::great_circle::GreatCircleDistanceCalculator;
 use super::haversine::HaversineDistanceCalculator;
 use super::vincenty::VincentyDistanceCalculator;
-use super::DistanceCalculator;
+use super::{DistanceCalculationError, DistanceCalculator};
 
 pub struct DistanceCalculatorFactory;
 
@@ -32,7 +33,7 @@ impl DistanceCalculatorFactory {
             }
         }
 
-        match formula {
+        let calculator: Box<dyn DistanceCalculator> = match formula {
             Formula::GreatCircle => Box::new(GreatCircleDistanceCalculator::new(
                 earth_radius_in_kilometers,
             )),
@@ -44,6 +45,37 @@ impl DistanceCalculatorFactory {
                 semi_minor_axis_in_kilometers,
                 inverse_flattening_factor,
             )),
-        }
+        };
+
+        Box::new(InstrumentedDistanceCalculator {
+            formula: *formula,
+            datum: *datum,
+            calculator,
+        })
+    }
+}
+
+/// Counts and traces computations of the wrapped calculator by formula and datum.
+struct InstrumentedDistanceCalculator {
+    formula: Formula,
+    datum: Datum,
+    calculator: Box<dyn DistanceCalculator>,
+}
+
+impl DistanceCalculator for InstrumentedDistanceCalculator {
+    fn calculate_distance(
+        &self,
+        from: &Coordinates,
+        to: &Coordinates,
+    ) -> Result<f64, DistanceCalculationError> {
+        let _span = tracing::info_span!(
+            "calculate_distance",
+            formula = ?self.formula,
+            datum = ?self.datum
+        )
+        .entered();
+        metrics().record_distance_computation(&self.formula, &self.datum);
+
+        self.calculator.calculate_distance(from, to)
     }
 }
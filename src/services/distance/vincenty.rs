@@ -1,4 +1,5 @@
 use super::{DistanceCalculationError, DistanceCalculator};
+use crate::metrics::metrics;
 use crate::models::Coordinates;
 
 const MAX_ITERATIONS: u8 = 200;
@@ -90,6 +91,7 @@ impl DistanceCalculator for VincentyDistanceCalculator {
         }
 
         if !converged {
+            metrics().vincenty_non_convergence.inc();
             return Err(DistanceCalculationError(
                 "Failed to converge after 200 iterations".to_string(),
             ));
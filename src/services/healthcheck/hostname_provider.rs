@@ -1,6 +1,6 @@
 use std::{ffi::OsString, io};
 
-pub trait HostnameProvider {
+pub trait HostnameProvider: Send + Sync {
     fn get(&self) -> io::Result<OsString>;
 }
 
@@ -57,6 +57,6 @@ impl MockFailedHostnameProvider {
 #[cfg(test)]
 impl HostnameProvider for MockFailedHostnameProvider {
     fn get(&self) -> io::Result<OsString> {
-        Err(io::Error::new(io::ErrorKind::Other, "mock error"))
+        Err(io::Error::other("mock error"))
     }
 }
@@ -1,6 +1,6 @@
 use std::time::SystemTime;
 
-pub trait TimeProvider {
+pub trait TimeProvider: Send + Sync {
     fn now(&self) -> SystemTime;
 }
 
@@ -40,6 +40,6 @@ impl MockTimeProvider {
 #[cfg(test)]
 impl TimeProvider for MockTimeProvider {
     fn now(&self) -> SystemTime {
-        self.mock_now.clone()
+        self.mock_now
     }
 }
@@ -0,0 +1,414 @@
+use std::collections::HashSet;
+use std::future::{ready, Future, Ready};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{forward_ready, Server, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::web::Data;
+use actix_web::Error;
+use futures::future::{select, Either, LocalBoxFuture};
+use tracing::log;
+
+use crate::auth::ApiKeyStore;
+use crate::metrics::metrics;
+use crate::services::airports::AirportsOverlayStore;
+use crate::services::app_state::AppState;
+use crate::usage::UsageTracker;
+
+/// How the server is stopped once a termination signal is received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ShutdownConfig {
+    /// How long readiness fails before new connections are refused.
+    pub delay: Duration,
+    /// How long requests in flight are waited for, has to be set as the shutdown timeout of
+    /// the server too.
+    pub drain_timeout: Duration,
+}
+
+/// What happened to the requests in flight when the server was stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ShutdownSummary {
+    pub signal: &'static str,
+    /// Requests in flight once new connections were refused.
+    pub in_flight: u64,
+    /// Requests in flight which didn't finish before the drain timeout.
+    pub cut_off: u64,
+    pub drained_in: Duration,
+    /// Requests responded to since the start.
+    pub requests_served: u64,
+}
+
+impl std::fmt::Display for ShutdownSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Shut down on {} after draining {} requests in flight in {:.1?}, {} of them were cut \
+             off, {} requests were served in total",
+            self.signal, self.in_flight, self.drained_in, self.cut_off, self.requests_served
+        )
+    }
+}
+
+/// Requests the server is handling, so that the ones in flight when it stops are followed
+/// until they finish or are cut off. Requests arriving later aren't counted.
+#[derive(Debug, Default)]
+pub(crate) struct RequestsInFlight {
+    next_id: AtomicU64,
+    state: Mutex<RequestsState>,
+}
+
+#[derive(Debug, Default)]
+struct RequestsState {
+    in_flight: HashSet<u64>,
+    /// Requests in flight when draining started, which haven't finished yet.
+    draining: HashSet<u64>,
+    /// Requests of `draining` which finished with a response.
+    drained: u64,
+}
+
+impl RequestsInFlight {
+    fn start(&self) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.state().in_flight.insert(id);
+        id
+    }
+
+    /// Forgets the request, which `responded` or was dropped without a response.
+    fn finish(&self, id: u64, responded: bool) {
+        let mut state = self.state();
+        state.in_flight.remove(&id);
+        if state.draining.remove(&id) && responded {
+            state.drained += 1;
+        }
+    }
+
+    /// Starts following the requests in flight, returning their number.
+    fn begin_draining(&self) -> u64 {
+        let mut state = self.state();
+        state.draining = state.in_flight.clone();
+        state.drained = 0;
+        state.draining.len() as u64
+    }
+
+    /// Number of the requests followed since `begin_draining` which finished.
+    fn drained(&self) -> u64 {
+        self.state().drained
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, RequestsState> {
+        self.state.lock().expect("Requests lock poisoned")
+    }
+}
+
+/// Registers requests in `RequestsInFlight`, has to wrap all routes of the server.
+pub(crate) struct TrackRequests(Arc<RequestsInFlight>);
+
+impl TrackRequests {
+    pub fn new(requests: Arc<RequestsInFlight>) -> Self {
+        Self(requests)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for TrackRequests
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = TrackRequestsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(TrackRequestsMiddleware {
+            service,
+            requests: self.0.clone(),
+        }))
+    }
+}
+
+pub(crate) struct TrackRequestsMiddleware<S> {
+    service: S,
+    requests: Arc<RequestsInFlight>,
+}
+
+impl<S, B> Service<ServiceRequest> for TrackRequestsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let tracked = TrackedRequest {
+            id: self.requests.start(),
+            requests: self.requests.clone(),
+            responded: false,
+        };
+        let response = self.service.call(request);
+
+        Box::pin(async move {
+            let response = response.await;
+            tracked.responded();
+            response
+        })
+    }
+}
+
+/// Finishes the request once it's dropped, with or without a response.
+struct TrackedRequest {
+    id: u64,
+    requests: Arc<RequestsInFlight>,
+    responded: bool,
+}
+
+impl TrackedRequest {
+    fn responded(mut self) {
+        self.responded = true;
+    }
+}
+
+impl Drop for TrackedRequest {
+    fn drop(&mut self) {
+        self.requests.finish(self.id, self.responded);
+    }
+}
+
+/// Runs `server` until `signal` resolves to the name of a termination signal, then makes
+/// readiness fail, waits for `config.delay`, refuses new connections and waits for requests
+/// in flight. Returns `None` if the server stopped on its own. The server has to be built with
+/// its own signal handling disabled.
+pub(crate) async fn run_until_stopped(
+    server: Server,
+    app_state: &AppState,
+    requests: &RequestsInFlight,
+    config: ShutdownConfig,
+    signal: impl Future<Output = &'static str>,
+) -> std::io::Result<Option<ShutdownSummary>> {
+    let handle = server.handle();
+    let server = actix_web::rt::spawn(server);
+
+    let (signal, server) = match select(server, Box::pin(signal)).await {
+        Either::Left((result, _)) => return result.map_err(std::io::Error::other)?.map(|_| None),
+        Either::Right((signal, server)) => (signal, server),
+    };
+
+    log::info!(
+        "Received {signal}, failing readiness for {:?} before refusing new connections",
+        config.delay
+    );
+    app_state.begin_shutdown();
+    actix_web::rt::time::sleep(config.delay).await;
+
+    let in_flight = requests.begin_draining();
+    log::info!(
+        "Waiting up to {:?} for {in_flight} requests in flight",
+        config.drain_timeout
+    );
+
+    let draining_since = Instant::now();
+    handle.stop(true).await;
+    server.await.map_err(std::io::Error::other)??;
+
+    Ok(Some(ShutdownSummary {
+        signal,
+        in_flight,
+        cut_off: in_flight - requests.drained(),
+        drained_in: draining_since.elapsed(),
+        requests_served: metrics().http_requests_total(),
+    }))
+}
+
+/// Resolves to the name of the first SIGTERM or SIGINT the process receives.
+pub(crate) async fn termination_signal() -> &'static str {
+    let interrupt = Box::pin(async {
+        if let Err(e) = actix_web::rt::signal::ctrl_c().await {
+            log::error!("Failed to listen for SIGINT: {e}");
+            futures::future::pending::<()>().await;
+        }
+        "SIGINT"
+    });
+
+    #[cfg(unix)]
+    {
+        use actix_web::rt::signal::unix::{signal, SignalKind};
+
+        let terminate = Box::pin(async {
+            match signal(SignalKind::terminate()) {
+                Ok(mut terminations) => {
+                    terminations.recv().await;
+                }
+                Err(e) => {
+                    log::error!("Failed to listen for SIGTERM: {e}");
+                    futures::future::pending::<()>().await;
+                }
+            }
+            "SIGTERM"
+        });
+
+        match select(terminate, interrupt).await {
+            Either::Left((signal, _)) | Either::Right((signal, _)) => signal,
+        }
+    }
+
+    #[cfg(not(unix))]
+    interrupt.await
+}
+
+/// Closes the connection pools of all databases, once no requests use them anymore.
+pub(crate) async fn close_databases(
+    app_state: &AppState,
+    overlay_store: Option<&Data<AirportsOverlayStore>>,
+    api_key_store: Option<&Data<ApiKeyStore>>,
+    usage_tracker: Option<&Data<UsageTracker>>,
+) {
+    app_state.airports_repository.snapshot().close().await;
+    if let Some(overlay_store) = overlay_store {
+        overlay_store.close().await;
+    }
+    if let Some(api_key_store) = api_key_store {
+        api_key_store.close().await;
+    }
+    if let Some(usage_tracker) = usage_tracker {
+        usage_tracker.close().await;
+    }
+
+    log::info!("Closed database connections");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use futures::channel::oneshot;
+
+    use crate::metrics::HttpMetrics;
+    use crate::services::airports::DummyAirportsRepository;
+
+    use super::*;
+
+    /// Stops a server once a request taking `handling` is in flight, with `shutdown_timeout`
+    /// seconds to drain it. Returns the summary and the response the client received.
+    async fn stop_with_request_in_flight(
+        handling: Duration,
+        shutdown_timeout: u64,
+    ) -> (AppState, ShutdownSummary, String) {
+        let app_state = AppState::new(
+            Box::default(),
+            Box::default(),
+            Box::new(DummyAirportsRepository::new(vec![])),
+        );
+        let requests = Arc::new(RequestsInFlight::default());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (started, request_started) = oneshot::channel::<()>();
+        let started = Arc::new(Mutex::new(Some(started)));
+        let tracked_requests = requests.clone();
+        let server = HttpServer::new(move || {
+            let started = started.clone();
+            App::new()
+                .wrap(HttpMetrics)
+                .wrap(TrackRequests::new(tracked_requests.clone()))
+                .route(
+                    "/shutdown-test",
+                    web::get().to(move || {
+                        let started = started.lock().unwrap().take();
+                        async move {
+                            if let Some(started) = started {
+                                started.send(()).unwrap();
+                            }
+                            actix_web::rt::time::sleep(handling).await;
+                            HttpResponse::Ok().body("done")
+                        }
+                    }),
+                )
+        })
+        .workers(1)
+        .disable_signals()
+        .shutdown_timeout(shutdown_timeout)
+        .listen(listener)
+        .unwrap()
+        .run();
+
+        // the request is sent on a thread of its own, as the server runs on this one
+        let response = std::thread::spawn(move || {
+            let mut stream = std::net::TcpStream::connect(address).unwrap();
+            std::io::Write::write_all(
+                &mut stream,
+                b"GET /shutdown-test HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+
+            // connections of cut off requests are closed without a response
+            let mut response = String::new();
+            let _ = std::io::Read::read_to_string(&mut stream, &mut response);
+            response
+        });
+
+        let config = ShutdownConfig {
+            delay: Duration::from_millis(50),
+            drain_timeout: Duration::from_secs(shutdown_timeout),
+        };
+        let signal = async {
+            request_started.await.unwrap();
+            "SIGTERM"
+        };
+        let summary = run_until_stopped(server, &app_state, &requests, config, signal)
+            .await
+            .unwrap()
+            .unwrap();
+
+        (app_state, summary, response.join().unwrap())
+    }
+
+    #[actix_web::test]
+    async fn test_drains_requests_in_flight() {
+        let (app_state, summary, response) =
+            stop_with_request_in_flight(Duration::from_millis(300), 5).await;
+
+        assert!(app_state.is_shutting_down());
+        assert_eq!(summary.signal, "SIGTERM");
+        assert_eq!((summary.in_flight, summary.cut_off), (1, 0));
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with("done"), "{response}");
+    }
+
+    #[actix_web::test]
+    async fn test_counts_requests_cut_off() {
+        let (_, summary, response) = stop_with_request_in_flight(Duration::from_secs(10), 1).await;
+
+        assert_eq!((summary.in_flight, summary.cut_off), (1, 1));
+        assert!(summary.drained_in < Duration::from_secs(5));
+        assert!(!response.contains("done"), "{response}");
+    }
+
+    #[test]
+    fn test_follows_only_requests_in_flight_when_draining_starts() {
+        let requests = RequestsInFlight::default();
+        let finished = requests.start();
+        let cut_off = requests.start();
+        requests.finish(finished, true);
+        let drained = requests.start();
+        let dropped = requests.start();
+
+        assert_eq!(requests.begin_draining(), 3);
+        let later = requests.start();
+        requests.finish(later, true);
+        requests.finish(drained, true);
+        requests.finish(dropped, false);
+
+        assert_eq!(requests.drained(), 1);
+        // the request finishing after the server stopped isn't counted as drained
+        requests.finish(cut_off, false);
+        assert_eq!(requests.drained(), 1);
+    }
+}
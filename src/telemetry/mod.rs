@@ -0,0 +1,80 @@
+mod request_id;
+
+use opentelemetry::trace::TraceError;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::Tracer;
+use opentelemetry_sdk::{runtime, Resource};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+use crate::config::{LogFormat, TracingConfig};
+
+pub(crate) use request_id::RequestId;
+
+/// Format of access log lines, the default one of `Logger` followed by the request ID, as
+/// they're logged once responses are sent, outside of request spans.
+pub(crate) const ACCESS_LOG_FORMAT: &str =
+    r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{X-Request-Id}o"#;
+
+/// Logs events in `format`, together with the fields of the spans they happened in, such as
+/// the request ID, and exports spans to the OTLP collector of `config` if there's one.
+pub(crate) fn init(format: LogFormat, config: &TracingConfig) -> Result<(), TraceError> {
+    let fmt_layer = match format {
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+    };
+    let otlp_layer = match &config.otlp_endpoint {
+        Some(otlp_endpoint) => Some(
+            tracing_opentelemetry::layer()
+                .with_tracer(otlp_tracer(otlp_endpoint, &config.service_name)?),
+        ),
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(otlp_layer)
+        .with(LevelFilter::INFO)
+        .init();
+
+    match format {
+        LogFormat::Json => tracing::info!("Initialized json logger successfully."),
+        LogFormat::Text => tracing::info!("Initialized text logger successfully."),
+    }
+    if let Some(otlp_endpoint) = &config.otlp_endpoint {
+        tracing::info!("Exporting spans to {otlp_endpoint}");
+    }
+
+    Ok(())
+}
+
+fn otlp_tracer(otlp_endpoint: &str, service_name: &str) -> Result<Tracer, TraceError> {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(otlp_endpoint),
+        )
+        .with_trace_config(
+            opentelemetry_sdk::trace::config().with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                service_name.to_owned(),
+            )])),
+        )
+        .install_batch(runtime::Tokio)
+}
+
+/// Exports spans which are still batched, before the service exits.
+pub(crate) async fn shutdown(config: &TracingConfig) {
+    if config.otlp_endpoint.is_none() {
+        return;
+    }
+
+    // shutting the provider down blocks until the batches are exported by a task of this thread
+    let _ =
+        actix_web::rt::task::spawn_blocking(opentelemetry::global::shutdown_tracer_provider).await;
+}
@@ -0,0 +1,444 @@
+use std::future::{ready, Ready};
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpMessage, HttpResponse};
+use futures::future::LocalBoxFuture;
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use serde_json::{Map, Value};
+use tracing::field::{display, Empty};
+use tracing::{Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+
+/// Longest request ID taken over from clients, longer ones are replaced.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// IDs of the request being handled, stored in the request extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RequestContext {
+    /// `X-Request-Id` of the request, or a generated one.
+    pub request_id: String,
+    /// W3C trace ID, of the `traceparent` of the request or a new trace.
+    pub trace_id: String,
+}
+
+/// Middleware running requests in a span with their request and trace IDs, so that every log
+/// line of a request carries them. The request ID is taken from `X-Request-Id`, or generated,
+/// returned in the same header and added to error bodies; traces are continued from
+/// `traceparent`. Has to be wrapped by `Compress`, so that error bodies can be read.
+pub(crate) struct RequestId;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestId
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequestIdMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddleware { service }))
+    }
+}
+
+pub(crate) struct RequestIdMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let request_id = request
+            .headers()
+            .get(X_REQUEST_ID)
+            .and_then(|request_id| request_id.to_str().ok())
+            .filter(|request_id| is_valid_request_id(request_id))
+            .map_or_else(generate_request_id, str::to_owned);
+        let traceparent = request
+            .headers()
+            .get(TRACEPARENT)
+            .and_then(|traceparent| traceparent.to_str().ok())
+            .and_then(TraceParent::parse);
+
+        let route = request
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_owned());
+        let span = tracing::info_span!(
+            "http_request",
+            otel.name = %format!("{} {route}", request.method()),
+            otel.kind = "server",
+            http.method = %request.method(),
+            http.route = %route,
+            http.status_code = Empty,
+            request_id = %request_id,
+            trace_id = Empty,
+        );
+        if let Some(traceparent) = &traceparent {
+            span.set_parent(traceparent.context());
+        }
+        let trace_id = trace_id(&span, traceparent.as_ref()).to_string();
+        span.record("trace_id", display(&trace_id));
+
+        request.extensions_mut().insert(RequestContext {
+            request_id: request_id.clone(),
+            trace_id,
+        });
+
+        let response = span.in_scope(|| self.service.call(request));
+
+        Box::pin(
+            async move {
+                match response.await {
+                    Ok(response) => {
+                        Span::current().record("http.status_code", response.status().as_u16());
+
+                        let (http_request, response) = response.into_parts();
+                        let response = with_request_id(response, &request_id).await?;
+                        Ok(ServiceResponse::new(http_request, response))
+                    }
+                    // errors of other middleware, such as authentication, are rendered later,
+                    // so they're passed on with a response carrying the request ID instead
+                    Err(e) => {
+                        let response = e.error_response();
+                        Span::current().record("http.status_code", response.status().as_u16());
+
+                        let response = with_request_id(response, &request_id)
+                            .await?
+                            .map_into_boxed_body();
+                        Err(InternalError::from_response(e, response).into())
+                    }
+                }
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Request IDs of clients are logged and returned, so only visible ASCII is taken over.
+fn is_valid_request_id(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.len() <= MAX_REQUEST_ID_LENGTH
+        && request_id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+fn generate_request_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// Trace ID of the request span. With an OTLP exporter the span has its own, which follows
+/// the remote parent; otherwise it's the one of the parent or a new one.
+fn trace_id(span: &Span, traceparent: Option<&TraceParent>) -> TraceId {
+    let trace_id = span.context().span().span_context().trace_id();
+    if trace_id != TraceId::INVALID {
+        return trace_id;
+    }
+
+    traceparent.map_or_else(
+        || TraceId::from_bytes(rand::random()),
+        |traceparent| traceparent.trace_id,
+    )
+}
+
+/// Adds the request ID header to `response`, and the request ID to its body if it's an error.
+async fn with_request_id<B>(
+    mut response: HttpResponse<B>,
+    request_id: &str,
+) -> Result<HttpResponse<EitherBody<B>>, Error>
+where
+    B: MessageBody + 'static,
+{
+    response.headers_mut().insert(
+        X_REQUEST_ID,
+        HeaderValue::from_str(request_id).expect("Request IDs are visible ASCII"),
+    );
+
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return Ok(response.map_into_left_body());
+    }
+
+    let (mut response, body) = response.into_parts();
+    let body = actix_web::body::to_bytes(body)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.into().to_string()))?;
+
+    response.headers_mut().remove(CONTENT_LENGTH);
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Ok(response
+        .set_body(error_body(status, &body, request_id).to_string())
+        .map_into_boxed_body()
+        .map_into_right_body())
+}
+
+/// Error body with the request ID. JSON objects get a `request_id` field, other bodies are
+/// wrapped in an object like `{"error": "..."}` first.
+fn error_body(status: StatusCode, body: &[u8], request_id: &str) -> Value {
+    let mut error = match serde_json::from_slice(body) {
+        Ok(Value::Object(error)) => error,
+        _ => {
+            let message = String::from_utf8_lossy(body).trim().to_owned();
+            let message = match message.is_empty() {
+                true => status.canonical_reason().unwrap_or("Error").to_owned(),
+                false => message,
+            };
+
+            Map::from_iter([("error".to_owned(), Value::String(message))])
+        }
+    };
+    error.insert("request_id".to_owned(), request_id.into());
+
+    Value::Object(error)
+}
+
+/// W3C trace context of a `traceparent` header, like
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TraceParent {
+    trace_id: TraceId,
+    parent_id: SpanId,
+    flags: TraceFlags,
+}
+
+impl TraceParent {
+    fn parse(traceparent: &str) -> Option<Self> {
+        let is_hex = |field: &str, length: usize| {
+            field.len() == length
+                && field
+                    .bytes()
+                    .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+        };
+
+        let mut fields = traceparent.trim().split('-');
+        let (version, trace_id, parent_id, flags) = (
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+        );
+        // later versions may add fields, but have to keep these
+        if !is_hex(version, 2) || version == "ff" || (version == "00" && fields.next().is_some()) {
+            return None;
+        }
+        if !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+
+        let traceparent = Self {
+            trace_id: TraceId::from_hex(trace_id).ok()?,
+            parent_id: SpanId::from_hex(parent_id).ok()?,
+            flags: TraceFlags::new(u8::from_str_radix(flags, 16).ok()?),
+        };
+        if traceparent.trace_id == TraceId::INVALID || traceparent.parent_id == SpanId::INVALID {
+            return None;
+        }
+
+        Some(traceparent)
+    }
+
+    fn context(&self) -> opentelemetry::Context {
+        opentelemetry::Context::new().with_remote_span_context(SpanContext::new(
+            self.trace_id,
+            self.parent_id,
+            self.flags,
+            true,
+            TraceState::default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::error::{ErrorBadRequest, ErrorInternalServerError, ErrorServiceUnavailable};
+    use actix_web::web::{self, Data};
+    use actix_web::{App, HttpRequest};
+    use actix_web_httpauth::middleware::HttpAuthentication;
+    use serde_json::json;
+
+    use crate::auth::{api_auth_validator, ApiCredentials};
+
+    use super::*;
+
+    async fn request_context(request: HttpRequest) -> HttpResponse {
+        let context = request
+            .extensions()
+            .get::<RequestContext>()
+            .cloned()
+            .unwrap();
+
+        HttpResponse::Ok().json(json!({
+            "request_id": context.request_id,
+            "trace_id": context.trace_id,
+        }))
+    }
+
+    #[test]
+    fn test_parses_traceparent() {
+        let traceparent =
+            TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        assert_eq!(
+            traceparent.trace_id.to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(traceparent.parent_id.to_string(), "00f067aa0ba902b7");
+        assert!(traceparent.flags.is_sampled());
+
+        assert!(TraceParent::parse(
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra"
+        )
+        .is_some());
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        ] {
+            assert!(TraceParent::parse(invalid).is_none(), "{invalid}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_propagates_request_and_trace_ids() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(RequestId)
+                .route("/test", web::get().to(request_context)),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::get()
+            .uri("/test")
+            .insert_header(("X-Request-Id", "checkout-42"))
+            .insert_header((
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get("X-Request-Id").unwrap(), "checkout-42");
+        let body: Value = actix_web::test::read_body_json(resp).await;
+        assert_eq!(
+            body,
+            json!({
+                "request_id": "checkout-42",
+                "trace_id": "4bf92f3577b34da6a3ce929d0e0e4736",
+            })
+        );
+
+        // request IDs which can't be logged as they are are replaced, like missing ones
+        let req = actix_web::test::TestRequest::get()
+            .uri("/test")
+            .insert_header(("X-Request-Id", "checkout 42"))
+            .insert_header(("traceparent", "00-invalid"))
+            .to_request();
+        let resp = actix_web::test::call_service(&app, req).await;
+        let request_id = resp.headers().get("X-Request-Id").unwrap().clone();
+        let body: Value = actix_web::test::read_body_json(resp).await;
+        assert_eq!(body["request_id"], request_id.to_str().unwrap());
+        assert_eq!(body["request_id"].as_str().unwrap().len(), 32);
+        assert_eq!(body["trace_id"].as_str().unwrap().len(), 32);
+        assert_ne!(body["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    }
+
+    #[actix_web::test]
+    async fn test_adds_request_ids_to_error_bodies() {
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap(RequestId)
+                .route(
+                    "/invalid",
+                    web::get().to(|| async {
+                        Err::<HttpResponse, _>(ErrorBadRequest(json!({"error": "Invalid cursor"})))
+                    }),
+                )
+                .route(
+                    "/failing",
+                    web::get().to(|| async {
+                        Err::<HttpResponse, _>(ErrorInternalServerError("Failed to fetch airports"))
+                    }),
+                )
+                .service(
+                    web::scope("/api")
+                        .app_data(Data::new(ApiCredentials::new("test", "test")))
+                        .wrap(HttpAuthentication::with_fn(api_auth_validator))
+                        .route("/test", web::get().to(|| async { "test" })),
+                ),
+        )
+        .await;
+
+        for (uri, status, error) in [
+            ("/invalid", StatusCode::BAD_REQUEST, "Invalid cursor"),
+            (
+                "/failing",
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to fetch airports",
+            ),
+            ("/missing", StatusCode::NOT_FOUND, "Not Found"),
+            ("/api/test", StatusCode::UNAUTHORIZED, "Unauthorized"),
+        ] {
+            let req = actix_web::test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("X-Request-Id", "checkout-42"))
+                .to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status);
+            assert_eq!(
+                resp.headers().get(CONTENT_TYPE).unwrap(),
+                "application/json"
+            );
+
+            let body: Value = actix_web::test::read_body_json(resp).await;
+            assert_eq!(body, json!({"error": error, "request_id": "checkout-42"}));
+        }
+
+        // errors of other app middleware are rendered by the server, as the response they carry
+        let app = actix_web::test::init_service(
+            App::new()
+                .wrap_fn(|_, _| async {
+                    Err::<ServiceResponse, _>(ErrorServiceUnavailable("Shutting down"))
+                })
+                .wrap(RequestId)
+                .route("/test", web::get().to(|| async { "test" })),
+        )
+        .await;
+        let req = actix_web::test::TestRequest::get()
+            .uri("/test")
+            .insert_header(("X-Request-Id", "checkout-42"))
+            .to_request();
+        let resp = actix_web::test::try_call_service(&app, req)
+            .await
+            .unwrap_err()
+            .error_response();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get("X-Request-Id").unwrap(), "checkout-42");
+
+        let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!({"error": "Shutting down", "request_id": "checkout-42"})
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::{any_supported_type, CertifiedKey};
+use rustls::{Certificate, PrivateKey};
+use rustls_pemfile::Item;
+
+use super::TlsError;
+
+/// Serves the certificate and key read from PEM files, which are read again when they change
+/// so that renewed certificates are picked up by new connections.
+pub(crate) struct CertificateResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    certified_key: RwLock<Arc<CertifiedKey>>,
+    /// Contents of the certificate and key files the served certificate was read from.
+    pem: Mutex<(Vec<u8>, Vec<u8>)>,
+}
+
+impl std::fmt::Debug for CertificateResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CertificateResolver")
+            .field("cert_path", &self.cert_path)
+            .field("key_path", &self.key_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CertificateResolver {
+    pub fn open(cert_path: &Path, key_path: &Path) -> Result<Self, TlsError> {
+        let cert_pem = std::fs::read(cert_path)?;
+        let key_pem = std::fs::read(key_path)?;
+        let certified_key = read_certified_key(&cert_pem, &key_pem)?;
+
+        Ok(Self {
+            cert_path: cert_path.to_owned(),
+            key_path: key_path.to_owned(),
+            certified_key: RwLock::new(Arc::new(certified_key)),
+            pem: Mutex::new((cert_pem, key_pem)),
+        })
+    }
+
+    /// Reads the files again if either of them changed, keeping the current certificate if
+    /// they're invalid, e.g. while only one of them has been replaced. Returns whether the
+    /// certificate was replaced.
+    pub fn reload_if_changed(&self) -> Result<bool, TlsError> {
+        let cert_pem = std::fs::read(&self.cert_path)?;
+        let key_pem = std::fs::read(&self.key_path)?;
+
+        let mut pem = self.pem.lock().unwrap();
+        if pem.0 == cert_pem && pem.1 == key_pem {
+            return Ok(false);
+        }
+
+        let certified_key = read_certified_key(&cert_pem, &key_pem)?;
+        *self.certified_key.write().unwrap() = Arc::new(certified_key);
+        *pem = (cert_pem, key_pem);
+
+        Ok(true)
+    }
+
+    pub fn certified_key(&self) -> Arc<CertifiedKey> {
+        self.certified_key.read().unwrap().clone()
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.certified_key())
+    }
+}
+
+/// Returns DER encoded certificates of a PEM file, failing if it has none.
+pub(crate) fn read_certificates(pem: &[u8]) -> Result<Vec<Vec<u8>>, TlsError> {
+    let certificates = rustls_pemfile::certs(&mut &pem[..])
+        .map_err(|e| TlsError::InvalidCertificate(format!("invalid PEM file: {e}")))?;
+
+    if certificates.is_empty() {
+        return Err(TlsError::InvalidCertificate(
+            "no certificates found in PEM file".to_owned(),
+        ));
+    }
+
+    Ok(certificates)
+}
+
+fn read_certified_key(cert_pem: &[u8], key_pem: &[u8]) -> Result<CertifiedKey, TlsError> {
+    let certificates = read_certificates(cert_pem)?;
+
+    let key = rustls_pemfile::read_all(&mut &key_pem[..])
+        .map_err(|e| TlsError::InvalidKey(format!("invalid PEM file: {e}")))?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => Some(key),
+            _ => None,
+        })
+        .ok_or_else(|| TlsError::InvalidKey("no private key found in PEM file".to_owned()))?;
+    let signing_key = any_supported_type(&PrivateKey(key))
+        .map_err(|_| TlsError::InvalidKey("unsupported private key".to_owned()))?;
+
+    Ok(CertifiedKey::new(
+        certificates.into_iter().map(Certificate).collect(),
+        signing_key,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::TestCertificate;
+    use super::*;
+
+    fn served_certificate(resolver: &CertificateResolver) -> Vec<u8> {
+        resolver.certified_key().cert[0].0.clone()
+    }
+
+    #[test]
+    fn test_reloads_changed_certificates() {
+        let ca = TestCertificate::ca("Test CA");
+        let first = TestCertificate::issued_by("localhost", &ca);
+        let (cert_path, key_path) = first.write("tls-reload");
+
+        let resolver = CertificateResolver::open(&cert_path, &key_path).unwrap();
+        assert_eq!(
+            served_certificate(&resolver),
+            first.certificate.to_der().unwrap()
+        );
+        assert!(!resolver.reload_if_changed().unwrap());
+
+        // a renewed certificate is only picked up once both files are replaced
+        let renewed = TestCertificate::issued_by("localhost", &ca);
+        std::fs::write(&cert_path, renewed.certificate.to_pem().unwrap()).unwrap();
+        std::fs::write(&key_path, "").unwrap();
+        assert!(resolver.reload_if_changed().is_err());
+        assert_eq!(
+            served_certificate(&resolver),
+            first.certificate.to_der().unwrap()
+        );
+
+        std::fs::write(&key_path, renewed.key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        assert!(resolver.reload_if_changed().unwrap());
+        assert_eq!(
+            served_certificate(&resolver),
+            renewed.certificate.to_der().unwrap()
+        );
+
+        std::fs::remove_file(cert_path).unwrap();
+        std::fs::remove_file(key_path).unwrap();
+    }
+}
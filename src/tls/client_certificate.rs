@@ -0,0 +1,99 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::path::Path;
+
+use actix_tls::accept::rustls::TlsStream;
+use actix_web::dev::Extensions;
+use actix_web::rt::net::TcpStream;
+use serde::Deserialize;
+
+use super::TlsError;
+
+/// Subject of the verified certificate a client presented, stored in the connection data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ClientCertificate {
+    pub subject: String,
+}
+
+/// Users of client certificates by their subjects, read from a TOML file:
+///
+/// ```toml
+/// [subjects]
+/// "O=Example, CN=importer" = "importer"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ClientSubjects {
+    subjects: HashMap<String, String>,
+}
+
+impl ClientSubjects {
+    pub fn from_file(path: &Path) -> Result<Self, TlsError> {
+        toml::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| TlsError::InvalidSubjects(e.to_string()))
+    }
+
+    /// User a certificate with `subject` authenticates as.
+    pub fn user(&self, subject: &str) -> Option<&str> {
+        self.subjects.get(subject).map(String::as_str)
+    }
+}
+
+/// Subject of a DER encoded certificate, formatted like `O=Example, CN=importer`.
+pub(crate) fn certificate_subject(certificate: &[u8]) -> Option<String> {
+    x509_parser::parse_x509_certificate(certificate)
+        .ok()
+        .map(|(_, certificate)| certificate.subject().to_string())
+}
+
+/// Stores the subject of the client certificate of TLS connections in their connection data.
+/// Certificates are verified during the handshake, so only ones signed by the client CA make
+/// it here.
+pub(crate) fn client_certificate_on_connect(connection: &dyn Any, data: &mut Extensions) {
+    let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() else {
+        return;
+    };
+
+    let (_, session) = stream.get_ref();
+    if let Some(subject) = session
+        .peer_certificates()
+        .and_then(|certificates| certificates.first())
+        .and_then(|certificate| certificate_subject(&certificate.0))
+    {
+        data.insert(ClientCertificate { subject });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::TestCertificate;
+    use super::*;
+
+    #[test]
+    fn test_maps_certificate_subjects_to_users() {
+        let path = std::env::temp_dir().join(format!("tls-subjects-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[subjects]\n\"O=Example, CN=importer\" = \"importer\"\n",
+        )
+        .unwrap();
+        let client_subjects = ClientSubjects::from_file(&path).unwrap();
+
+        let ca = TestCertificate::ca("Test CA");
+        let subject = certificate_subject(
+            &TestCertificate::issued_by("importer", &ca)
+                .certificate
+                .to_der()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(subject, "O=Example, CN=importer");
+        assert_eq!(client_subjects.user(&subject), Some("importer"));
+        assert_eq!(client_subjects.user("O=Example, CN=Test CA"), None);
+
+        std::fs::write(&path, "importer = \"importer\"").unwrap();
+        assert!(ClientSubjects::from_file(&path).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
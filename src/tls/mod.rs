@@ -0,0 +1,223 @@
+mod certificates;
+mod client_certificate;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::server::{AllowAnyAnonymousOrAuthenticatedClient, ServerConfig};
+use rustls::{Certificate, RootCertStore};
+use tracing::log;
+
+use certificates::read_certificates;
+pub(crate) use certificates::CertificateResolver;
+pub(crate) use client_certificate::{
+    client_certificate_on_connect, ClientCertificate, ClientSubjects,
+};
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(std::io::Error),
+    InvalidCertificate(String),
+    InvalidKey(String),
+    InvalidSubjects(String),
+}
+
+impl std::error::Error for TlsError {}
+impl std::fmt::Display for TlsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TlsError::Io(e) => write!(f, "TlsError: {e}"),
+            TlsError::InvalidCertificate(reason) => write!(f, "TlsError: {reason}"),
+            TlsError::InvalidKey(reason) => write!(f, "TlsError: {reason}"),
+            TlsError::InvalidSubjects(reason) => write!(f, "TlsError: {reason}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for TlsError {
+    fn from(e: std::io::Error) -> Self {
+        TlsError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TlsConfig {
+    /// PEM file with the certificate chain of the server, leaf first.
+    pub cert_path: PathBuf,
+    /// PEM file with the private key of the server certificate.
+    pub key_path: PathBuf,
+    /// PEM file with the CAs client certificates are verified against, clients can't
+    /// authenticate with certificates without it.
+    pub client_ca_path: Option<PathBuf>,
+    /// TOML file mapping subjects of client certificates to API users.
+    pub client_subjects_path: Option<PathBuf>,
+    /// How often the certificate and key files are checked for changes.
+    pub reload_interval: Duration,
+}
+
+/// Builds the rustls configuration serving certificates of `resolver`. With a client CA,
+/// clients may present a certificate signed by it, but aren't required to, so that they can
+/// still authenticate in other ways.
+pub(crate) fn server_config(
+    config: &TlsConfig,
+    resolver: Arc<CertificateResolver>,
+) -> Result<ServerConfig, TlsError> {
+    let builder = ServerConfig::builder().with_safe_defaults();
+
+    let builder = match &config.client_ca_path {
+        Some(client_ca_path) => builder.with_client_cert_verifier(
+            AllowAnyAnonymousOrAuthenticatedClient::new(read_client_roots(client_ca_path)?),
+        ),
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(builder.with_cert_resolver(resolver))
+}
+
+fn read_client_roots(path: &Path) -> Result<RootCertStore, TlsError> {
+    let mut roots = RootCertStore::empty();
+
+    for certificate in read_certificates(&std::fs::read(path)?)? {
+        roots
+            .add(&Certificate(certificate))
+            .map_err(|e| TlsError::InvalidCertificate(format!("invalid client CA: {e}")))?;
+    }
+
+    Ok(roots)
+}
+
+/// Checks the certificate and key files every `reload_interval`, to pick up renewed
+/// certificates without restarting.
+pub(crate) async fn reload_certificates_periodically(
+    resolver: Arc<CertificateResolver>,
+    reload_interval: Duration,
+) {
+    let mut interval = actix_web::rt::time::interval(reload_interval);
+    // the first tick completes immediately, right after the files were read
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        match resolver.reload_if_changed() {
+            Ok(true) => log::info!("Reloaded TLS certificate"),
+            Ok(false) => {}
+            Err(e) => log::error!("Failed to reload TLS certificate, keeping previous one: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::{PKey, Private};
+    use openssl::rsa::Rsa;
+    use openssl::x509::extension::BasicConstraints;
+    use openssl::x509::{X509Builder, X509NameBuilder, X509};
+
+    use super::*;
+
+    /// Certificate generated for a test, with its private key.
+    pub(crate) struct TestCertificate {
+        pub certificate: X509,
+        pub key: PKey<Private>,
+    }
+
+    impl TestCertificate {
+        /// Self-signed CA with the given common name.
+        pub fn ca(common_name: &str) -> Self {
+            let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+            let mut builder = certificate_builder(common_name, &key);
+            builder
+                .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+            builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+            Self {
+                certificate: builder.build(),
+                key,
+            }
+        }
+
+        /// Leaf certificate with the given common name, signed by `issuer`.
+        pub fn issued_by(common_name: &str, issuer: &TestCertificate) -> Self {
+            let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+            let mut builder = certificate_builder(common_name, &key);
+            builder
+                .set_issuer_name(issuer.certificate.subject_name())
+                .unwrap();
+            builder.sign(&issuer.key, MessageDigest::sha256()).unwrap();
+
+            Self {
+                certificate: builder.build(),
+                key,
+            }
+        }
+
+        pub fn write(&self, name: &str) -> (PathBuf, PathBuf) {
+            let cert_path = std::env::temp_dir().join(format!("{name}-{}.crt", std::process::id()));
+            let key_path = std::env::temp_dir().join(format!("{name}-{}.key", std::process::id()));
+            std::fs::write(&cert_path, self.certificate.to_pem().unwrap()).unwrap();
+            std::fs::write(&key_path, self.key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+
+            (cert_path, key_path)
+        }
+    }
+
+    fn certificate_builder(common_name: &str, key: &PKey<Private>) -> X509Builder {
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("O", "Example").unwrap();
+        name.append_entry_by_text("CN", common_name).unwrap();
+        let name = name.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&name).unwrap();
+        // self-signed unless replaced
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+
+        builder
+    }
+
+    #[test]
+    fn test_builds_server_config_with_client_verification() {
+        let ca = TestCertificate::ca("Test CA");
+        let server = TestCertificate::issued_by("localhost", &ca);
+        let (cert_path, key_path) = server.write("tls-server");
+        let (client_ca_path, client_ca_key_path) = ca.write("tls-client-ca");
+
+        let config = TlsConfig {
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+            client_ca_path: Some(client_ca_path.clone()),
+            client_subjects_path: None,
+            reload_interval: Duration::from_secs(60),
+        };
+        let resolver = Arc::new(CertificateResolver::open(&cert_path, &key_path).unwrap());
+        assert!(server_config(&config, resolver.clone()).is_ok());
+
+        // keys aren't certificates
+        let config = TlsConfig {
+            client_ca_path: Some(client_ca_key_path.clone()),
+            ..config
+        };
+        assert!(server_config(&config, resolver).is_err());
+
+        for path in [cert_path, key_path, client_ca_path, client_ca_key_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
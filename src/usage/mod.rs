@@ -0,0 +1,557 @@
+mod usage_store;
+
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::web::Data;
+use actix_web::{Error, HttpMessage, HttpRequest, HttpResponse};
+use futures::future::LocalBoxFuture;
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::log;
+
+use crate::auth::Principal;
+use crate::models::{ClientUsage, DailyUsage, UsageCounts};
+
+pub(crate) use usage_store::UsageStore;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// Quotas of `/api` requests per client, read from a TOML file:
+///
+/// ```toml
+/// [default]
+/// daily_requests = 1000
+/// monthly_legs = 100000
+///
+/// [clients."user:importer"]
+/// monthly_requests = 1000000
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct QuotaConfig {
+    /// Quota of clients without their own one, usage isn't limited without it.
+    #[serde(default)]
+    pub default: Option<Quota>,
+    /// Quotas of clients `user:<name>`, `api-key:<id>` or `jwt:<subject>`.
+    #[serde(default)]
+    pub clients: HashMap<String, Quota>,
+}
+
+impl QuotaConfig {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+
+        toml::from_str::<Self>(&content)
+            .map_err(|e| format!("failed to parse {}: {e}", path.display()))
+    }
+
+    fn quota(&self, client: &str) -> Option<&Quota> {
+        self.clients.get(client).or(self.default.as_ref())
+    }
+}
+
+/// Limits of a client's usage per UTC day and calendar month, unset limits aren't enforced.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Apiv2Schema)]
+#[serde(deny_unknown_fields)]
+pub struct Quota {
+    pub daily_requests: Option<u32>,
+    pub monthly_requests: Option<u32>,
+    pub daily_legs: Option<u32>,
+    pub monthly_legs: Option<u32>,
+    pub daily_airport_lookups: Option<u32>,
+    pub monthly_airport_lookups: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuotaPeriod {
+    Day,
+    Month,
+}
+
+impl Quota {
+    /// First quota used up by `today` and `month`, with its limit and period.
+    fn exceeded(
+        &self,
+        today: &UsageCounts,
+        month: &UsageCounts,
+    ) -> Option<(&'static str, u32, QuotaPeriod)> {
+        [
+            (
+                "daily_requests",
+                self.daily_requests,
+                today.requests,
+                QuotaPeriod::Day,
+            ),
+            (
+                "monthly_requests",
+                self.monthly_requests,
+                month.requests,
+                QuotaPeriod::Month,
+            ),
+            ("daily_legs", self.daily_legs, today.legs, QuotaPeriod::Day),
+            (
+                "monthly_legs",
+                self.monthly_legs,
+                month.legs,
+                QuotaPeriod::Month,
+            ),
+            (
+                "daily_airport_lookups",
+                self.daily_airport_lookups,
+                today.airport_lookups,
+                QuotaPeriod::Day,
+            ),
+            (
+                "monthly_airport_lookups",
+                self.monthly_airport_lookups,
+                month.airport_lookups,
+                QuotaPeriod::Month,
+            ),
+        ]
+        .into_iter()
+        .find_map(|(name, limit, used, period)| {
+            let limit = limit?;
+            (used >= i64::from(limit)).then_some((name, limit, period))
+        })
+    }
+}
+
+/// UTC day and month of a timestamp, the periods usage is counted and limited in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UsagePeriod {
+    /// `YYYY-MM-DD`
+    pub day: String,
+    /// `YYYY-MM`
+    pub month: String,
+    day_ends_at: i64,
+    month_ends_at: i64,
+}
+
+impl UsagePeriod {
+    pub fn at(timestamp: i64) -> Self {
+        let days = timestamp.div_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        let (next_year, next_month) = match month {
+            12 => (year + 1, 1),
+            _ => (year, month + 1),
+        };
+
+        Self {
+            day: format!("{year:04}-{month:02}-{day:02}"),
+            month: format!("{year:04}-{month:02}"),
+            day_ends_at: (days + 1) * SECONDS_PER_DAY,
+            month_ends_at: days_from_civil(next_year, next_month, 1) * SECONDS_PER_DAY,
+        }
+    }
+
+    fn seconds_until_end(&self, period: QuotaPeriod, timestamp: i64) -> i64 {
+        match period {
+            QuotaPeriod::Day => self.day_ends_at - timestamp,
+            QuotaPeriod::Month => self.month_ends_at - timestamp,
+        }
+    }
+}
+
+/// First and last day of a `YYYY-MM` month, as bounds of a range of `YYYY-MM-DD` days.
+pub(crate) fn month_days(month: &str) -> (String, String) {
+    (format!("{month}-01"), format!("{month}-31"))
+}
+
+/// Year, month and day of the date `days` after 1970-01-01, by Howard Hinnant's algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = match shifted_month < 10 {
+        true => shifted_month + 3,
+        false => shifted_month - 9,
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month as u32, day as u32)
+}
+
+/// Days between 1970-01-01 and the given date, the inverse of `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let shifted_month = i64::from((month + 9) % 12);
+    let day_of_year = (153 * shifted_month + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+/// Usage counters of clients together with their quotas.
+pub(crate) struct UsageTracker {
+    store: UsageStore,
+    quotas: QuotaConfig,
+}
+
+impl UsageTracker {
+    pub fn new(store: UsageStore, quotas: QuotaConfig) -> Self {
+        Self { store, quotas }
+    }
+
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        self.store.ping().await
+    }
+
+    pub async fn close(&self) {
+        self.store.close().await
+    }
+
+    pub fn quota(&self, client: &str) -> Option<Quota> {
+        self.quotas.quota(client).copied()
+    }
+
+    pub async fn record(
+        &self,
+        client: &str,
+        timestamp: i64,
+        usage: &UsageCounts,
+    ) -> Result<(), sqlx::Error> {
+        self.store
+            .record(client, &UsagePeriod::at(timestamp).day, usage)
+            .await
+    }
+
+    /// Daily usage of `client` in the month of `period`.
+    pub async fn month_usage(
+        &self,
+        client: &str,
+        period: &UsagePeriod,
+    ) -> Result<Vec<DailyUsage>, sqlx::Error> {
+        let (from, to) = month_days(&period.month);
+        self.store.client_usage(client, &from, &to).await
+    }
+
+    /// Usage of all clients in the `YYYY-MM` month.
+    pub async fn summary(&self, month: &str) -> Result<Vec<ClientUsage>, sqlx::Error> {
+        let (from, to) = month_days(month);
+        self.store.summary(&from, &to).await
+    }
+
+    /// Rejects requests of clients who've used up any of their quotas with
+    /// `429 Too Many Requests`, retried once the period of the quota ends.
+    async fn check_quota(&self, client: &str, timestamp: i64) -> Result<(), Error> {
+        let Some(quota) = self.quota(client) else {
+            return Ok(());
+        };
+
+        let period = UsagePeriod::at(timestamp);
+        let days = self.month_usage(client, &period).await.map_err(|e| {
+            log::error!("Failed to read usage of {client}: {e}");
+            ErrorInternalServerError(json!({"error": "Database fail"}))
+        })?;
+
+        let mut today = UsageCounts::default();
+        let mut month = UsageCounts::default();
+        for daily in days {
+            if daily.day == period.day {
+                today += daily.usage;
+            }
+            month += daily.usage;
+        }
+
+        let Some((name, limit, quota_period)) = quota.exceeded(&today, &month) else {
+            return Ok(());
+        };
+
+        log::warn!("{client} exceeded the {name} quota of {limit}");
+        let retry_after = period.seconds_until_end(quota_period, timestamp);
+        let response = HttpResponse::TooManyRequests()
+            .insert_header((RETRY_AFTER, retry_after))
+            .json(json!({
+                "error": "Quota exceeded",
+                "details": {"quota": name, "limit": limit, "retry_after": retry_after}
+            }));
+
+        Err(InternalError::from_response("Quota exceeded", response).into())
+    }
+}
+
+/// Checks quotas of the authenticated `principal`, if usage is tracked at all.
+pub(crate) async fn check_quota(
+    request: &ServiceRequest,
+    principal: &Principal,
+) -> Result<(), Error> {
+    match request.app_data::<Data<UsageTracker>>() {
+        Some(usage_tracker) => {
+            usage_tracker
+                .check_quota(&principal.client_id(), now())
+                .await
+        }
+        None => Ok(()),
+    }
+}
+
+/// Counts work done by a handler towards the caller's usage, recorded by `UsageRecorder`
+/// once the response is ready.
+pub(crate) fn record_usage(request: &HttpRequest, legs: usize, airport_lookups: usize) {
+    request.extensions_mut().insert(UsageCounts {
+        requests: 0,
+        legs: legs as i64,
+        airport_lookups: airport_lookups as i64,
+    });
+}
+
+/// Middleware recording usage of authenticated requests in the `UsageTracker` app data. Has
+/// to be wrapped by the authentication middleware, so that requests are counted per principal.
+pub(crate) struct UsageRecorder;
+
+impl<S, B> Transform<S, ServiceRequest> for UsageRecorder
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = UsageRecorderMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(UsageRecorderMiddleware { service }))
+    }
+}
+
+pub(crate) struct UsageRecorderMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for UsageRecorderMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let usage_tracker = request.app_data::<Data<UsageTracker>>().cloned();
+        let response = self.service.call(request);
+
+        Box::pin(async move {
+            let response = response.await?;
+            let Some(usage_tracker) = usage_tracker else {
+                return Ok(response);
+            };
+
+            let (principal, usage) = {
+                let extensions = response.request().extensions();
+                (
+                    extensions.get::<Principal>().cloned(),
+                    extensions.get::<UsageCounts>().copied().unwrap_or_default(),
+                )
+            };
+            let Some(principal) = principal else {
+                return Ok(response);
+            };
+
+            let client = principal.client_id();
+            let usage = UsageCounts {
+                requests: 1,
+                ..usage
+            };
+            // failing to count a request shouldn't fail the request itself
+            if let Err(e) = usage_tracker.record(&client, now(), &usage).await {
+                log::error!("Failed to record usage of {client}: {e}");
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::{post, App};
+    use actix_web_httpauth::headers::authorization::{Authorization, Basic};
+    use actix_web_httpauth::middleware::HttpAuthentication;
+
+    use crate::auth::{api_auth_validator, ApiCredentials};
+
+    use super::*;
+
+    #[post("/api/calculate_distance/test")]
+    async fn distance_handler(request: HttpRequest) -> &'static str {
+        record_usage(&request, 2, 3);
+        "distance"
+    }
+
+    #[test]
+    fn test_splits_timestamps_into_periods() {
+        // 2023-05-02T04:00:00Z
+        let period = UsagePeriod::at(1683000000);
+        assert_eq!(period.day, "2023-05-02");
+        assert_eq!(period.month, "2023-05");
+        assert_eq!(
+            period.seconds_until_end(QuotaPeriod::Day, 1683000000),
+            72000
+        );
+        assert_eq!(
+            period.seconds_until_end(QuotaPeriod::Month, 1683000000),
+            29 * SECONDS_PER_DAY + 72000
+        );
+
+        assert_eq!(UsagePeriod::at(0).day, "1970-01-01");
+        // 2024-02-29T23:59:59Z
+        let leap_day = UsagePeriod::at(1709251199);
+        assert_eq!(leap_day.day, "2024-02-29");
+        assert_eq!(
+            leap_day.seconds_until_end(QuotaPeriod::Month, 1709251199),
+            1
+        );
+        // 2023-12-31T12:00:00Z
+        let new_years_eve = UsagePeriod::at(1704024000);
+        assert_eq!(new_years_eve.day, "2023-12-31");
+        assert_eq!(
+            new_years_eve.seconds_until_end(QuotaPeriod::Month, 1704024000),
+            43200
+        );
+    }
+
+    #[test]
+    fn test_finds_exceeded_quotas() {
+        let quota = Quota {
+            daily_requests: Some(10),
+            monthly_legs: Some(100),
+            ..Quota::default()
+        };
+        let usage = |requests, legs| UsageCounts {
+            requests,
+            legs,
+            airport_lookups: 0,
+        };
+
+        assert_eq!(quota.exceeded(&usage(9, 0), &usage(50, 99)), None);
+        assert_eq!(
+            quota.exceeded(&usage(10, 0), &usage(50, 99)),
+            Some(("daily_requests", 10, QuotaPeriod::Day))
+        );
+        assert_eq!(
+            quota.exceeded(&usage(0, 0), &usage(50, 100)),
+            Some(("monthly_legs", 100, QuotaPeriod::Month))
+        );
+        assert_eq!(
+            Quota::default().exceeded(&usage(0, 0), &usage(1000, 1000)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reads_quotas() {
+        let path = std::env::temp_dir().join(format!("quotas-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[default]\ndaily_requests = 100\n\n[clients.importer]\nmonthly_legs = 1000\n",
+        )
+        .unwrap();
+
+        let config = QuotaConfig::from_file(&path).unwrap();
+        assert_eq!(config.quota("alice").unwrap().daily_requests, Some(100));
+        assert_eq!(
+            config.quota("importer"),
+            Some(&Quota {
+                monthly_legs: Some(1000),
+                ..Quota::default()
+            })
+        );
+
+        std::fs::write(&path, "[default]\ndaily_request = 100\n").unwrap();
+        assert!(QuotaConfig::from_file(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_records_and_limits_usage() {
+        let usage_tracker = Data::new(UsageTracker::new(
+            UsageStore::open("sqlite::memory:").await.unwrap(),
+            QuotaConfig {
+                default: None,
+                clients: HashMap::from([(
+                    "user:alice".to_owned(),
+                    Quota {
+                        daily_legs: Some(4),
+                        ..Quota::default()
+                    },
+                )]),
+            },
+        ));
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(usage_tracker.clone())
+                .app_data(Data::new(ApiCredentials::new("alice", "secret")))
+                .service(distance_handler)
+                .wrap(UsageRecorder)
+                .wrap(HttpAuthentication::with_fn(api_auth_validator)),
+        )
+        .await;
+
+        let mut statuses = vec![];
+        for _ in 0..3 {
+            let req = actix_web::test::TestRequest::post()
+                .uri("/api/calculate_distance/test")
+                .insert_header(Authorization::<Basic>::from(Basic::new(
+                    "alice",
+                    Some("secret"),
+                )))
+                .to_request();
+            let resp = actix_web::test::call_service(&app, req).await;
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                assert!(resp.headers().contains_key(RETRY_AFTER));
+            }
+            statuses.push(resp.status());
+        }
+        assert_eq!(
+            statuses,
+            vec![
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::TOO_MANY_REQUESTS
+            ]
+        );
+
+        let period = UsagePeriod::at(now());
+        let days = usage_tracker
+            .month_usage("user:alice", &period)
+            .await
+            .unwrap();
+        assert_eq!(
+            days,
+            vec![DailyUsage {
+                day: period.day,
+                usage: UsageCounts {
+                    requests: 2,
+                    legs: 4,
+                    airport_lookups: 6,
+                },
+            }]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::str::FromStr;
+
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::SqlitePool;
+
+use crate::models::{ClientUsage, DailyUsage, UsageCounts};
+
+const CREATE_USAGE_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS usage
+    (
+        client          text not null,
+        day             text not null,
+        requests        int  not null default 0,
+        legs            int  not null default 0,
+        airport_lookups int  not null default 0,
+        primary key (client, day)
+    )";
+
+/// SQLite store of usage counters, aggregated per client and UTC day. Days are stored as
+/// `YYYY-MM-DD`, so that ranges of them can be compared as text.
+pub struct UsageStore {
+    pool: SqlitePool,
+}
+
+impl UsageStore {
+    /// Opens the usage database at `database_url`, creating it if it doesn't exist yet.
+    pub async fn open(database_url: &str) -> Result<Self, sqlx::Error> {
+        let db_opts = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+        let pool = SqlitePool::connect_with(db_opts).await?;
+
+        sqlx::query(CREATE_USAGE_TABLE).execute(&pool).await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Checks that the usage database can be queried, for readiness probes.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1 FROM usage LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Adds `usage` to the counters of `client` on `day`.
+    pub async fn record(
+        &self,
+        client: &str,
+        day: &str,
+        usage: &UsageCounts,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO usage (client, day, requests, legs, airport_lookups)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (client, day) DO UPDATE SET
+                requests = requests + excluded.requests,
+                legs = legs + excluded.legs,
+                airport_lookups = airport_lookups + excluded.airport_lookups",
+        )
+        .bind(client)
+        .bind(day)
+        .bind(usage.requests)
+        .bind(usage.legs)
+        .bind(usage.airport_lookups)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns daily usage of `client` between `from` and `to` inclusive, oldest first. Days
+    /// without requests are left out.
+    pub async fn client_usage(
+        &self,
+        client: &str,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<DailyUsage>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (String, i64, i64, i64)>(
+            "SELECT day, requests, legs, airport_lookups FROM usage
+             WHERE client = ? AND day BETWEEN ? AND ?
+             ORDER BY day",
+        )
+        .bind(client)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(day, requests, legs, airport_lookups)| DailyUsage {
+                day,
+                usage: UsageCounts {
+                    requests,
+                    legs,
+                    airport_lookups,
+                },
+            })
+            .collect())
+    }
+
+    /// Returns usage of every client between `from` and `to` inclusive, heaviest users first.
+    pub async fn summary(&self, from: &str, to: &str) -> Result<Vec<ClientUsage>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, (String, i64, i64, i64)>(
+            "SELECT client, SUM(requests), SUM(legs), SUM(airport_lookups) FROM usage
+             WHERE day BETWEEN ? AND ?
+             GROUP BY client
+             ORDER BY SUM(requests) DESC, client",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(client, requests, legs, airport_lookups)| ClientUsage {
+                client,
+                usage: UsageCounts {
+                    requests,
+                    legs,
+                    airport_lookups,
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(requests: i64, legs: i64, airport_lookups: i64) -> UsageCounts {
+        UsageCounts {
+            requests,
+            legs,
+            airport_lookups,
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_aggregates_usage_per_client_and_day() {
+        let store = UsageStore::open("sqlite::memory:").await.unwrap();
+
+        for (client, day, counts) in [
+            ("alice", "2023-04-30", usage(1, 0, 0)),
+            ("alice", "2023-05-01", usage(1, 2, 3)),
+            ("alice", "2023-05-01", usage(1, 1, 2)),
+            ("alice", "2023-05-02", usage(1, 0, 5)),
+            ("api-key:1", "2023-05-02", usage(1, 4, 0)),
+        ] {
+            store.record(client, day, &counts).await.unwrap();
+        }
+
+        assert_eq!(
+            store
+                .client_usage("alice", "2023-05-01", "2023-05-31")
+                .await
+                .unwrap(),
+            vec![
+                DailyUsage {
+                    day: "2023-05-01".to_owned(),
+                    usage: usage(2, 3, 5),
+                },
+                DailyUsage {
+                    day: "2023-05-02".to_owned(),
+                    usage: usage(1, 0, 5),
+                },
+            ]
+        );
+
+        assert_eq!(
+            store.summary("2023-05-01", "2023-05-31").await.unwrap(),
+            vec![
+                ClientUsage {
+                    client: "alice".to_owned(),
+                    usage: usage(3, 3, 10),
+                },
+                ClientUsage {
+                    client: "api-key:1".to_owned(),
+                    usage: usage(1, 4, 0),
+                },
+            ]
+        );
+    }
+}